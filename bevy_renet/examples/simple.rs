@@ -48,8 +48,10 @@ fn new_renet_client() -> RenetClient {
     let authentication = ClientAuthentication::Unsecure {
         client_id,
         protocol_id: PROTOCOL_ID,
-        server_addr,
+        server_addresses: vec![server_addr],
         user_data: None,
+        expire_seconds: 300,
+        timeout_seconds: 15,
     };
     RenetClient::new(current_time, socket, connection_config, authentication).unwrap()
 }
@@ -135,8 +137,8 @@ fn server_update_system(
                 let message = bincode::serialize(&ServerMessages::PlayerConnected { id: *id }).unwrap();
                 server.broadcast_message(0, message);
             }
-            ServerEvent::ClientDisconnected(id) => {
-                println!("Player {} disconnected.", id);
+            ServerEvent::ClientDisconnected { client_id: id, reason, .. } => {
+                println!("Player {} disconnected: {}", id, reason);
                 if let Some(player_entity) = lobby.players.remove(id) {
                     commands.entity(player_entity).despawn();
                 }
@@ -144,6 +146,7 @@ fn server_update_system(
                 let message = bincode::serialize(&ServerMessages::PlayerDisconnected { id: *id }).unwrap();
                 server.broadcast_message(0, message);
             }
+            ServerEvent::ConnectionRequested { .. } => {}
         }
     }
 
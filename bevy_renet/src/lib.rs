@@ -211,9 +211,11 @@ mod tests {
         let ip = SERVER_IP;
         let authentication = ClientAuthentication::Unsecure {
             client_id,
-            server_addr: SocketAddr::new(ip, SERVER_PORT),
+            server_addresses: vec![SocketAddr::new(ip, SERVER_PORT)],
             protocol_id: PROTOCOL_ID,
             user_data: None,
+            expire_seconds: 300,
+            timeout_seconds: 15,
         };
         RenetClient::new(
             current_time,
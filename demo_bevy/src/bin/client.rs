@@ -45,8 +45,10 @@ fn new_renet_client() -> RenetClient {
     let authentication = ClientAuthentication::Unsecure {
         client_id,
         protocol_id: PROTOCOL_ID,
-        server_addr,
+        server_addresses: vec![server_addr],
         user_data: None,
+        expire_seconds: 300,
+        timeout_seconds: 15,
     };
 
     RenetClient::new(current_time, socket, connection_config, authentication).unwrap()
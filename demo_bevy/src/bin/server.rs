@@ -128,8 +128,8 @@ fn server_update_system(
                 .unwrap();
                 server.broadcast_message(ServerChannel::ServerMessages.id(), message);
             }
-            ServerEvent::ClientDisconnected(id) => {
-                println!("Player {} disconnected.", id);
+            ServerEvent::ClientDisconnected { client_id: id, reason, .. } => {
+                println!("Player {} disconnected: {}", id, reason);
                 visualizer.remove_client(*id);
                 client_ticks.0.remove(id);
                 if let Some(player_entity) = lobby.players.remove(id) {
@@ -139,6 +139,7 @@ fn server_update_system(
                 let message = bincode::serialize(&ServerMessages::PlayerRemove { id: *id }).unwrap();
                 server.broadcast_message(ServerChannel::ServerMessages.id(), message);
             }
+            ServerEvent::ConnectionRequested { .. } => {}
         }
     }
 
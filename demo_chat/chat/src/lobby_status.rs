@@ -1,9 +1,10 @@
 use std::{
     sync::{Arc, RwLock},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
-use matcher::{RegisterServer, ServerUpdate};
+use matcher::{RegisterServer, ServerUpdate, REGISTRATION_KEY};
+use renet_token_issuer::ServerRegistration;
 use reqwest::blocking::Client;
 
 pub enum Status {
@@ -11,7 +12,11 @@ pub enum Status {
     Updating { server_id: u64 },
 }
 
-pub fn update_lobby_status(mut register_server: RegisterServer, server_update: Arc<RwLock<ServerUpdate>>) {
+pub fn update_lobby_status(
+    mut register_server: RegisterServer,
+    mut server_registration: ServerRegistration,
+    server_update: Arc<RwLock<ServerUpdate>>,
+) {
     let client = Client::new();
     let mut status = Status::Registering;
     loop {
@@ -29,6 +34,10 @@ pub fn update_lobby_status(mut register_server: RegisterServer, server_update: A
         match status {
             Status::Registering => {
                 register_server.current_clients = server_update.read().unwrap().current_clients;
+                // Registrations are only accepted once and for a short time, sign a new one for each attempt
+                let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+                server_registration.timestamp = current_time.as_millis() as u64;
+                register_server.registration = server_registration.sign(REGISTRATION_KEY);
                 match register_server_request(&register_server, &client) {
                     Err(e) => {
                         log::error!("Failed to register server: {}", e);
@@ -6,13 +6,17 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use matcher::{RegisterServer, ServerUpdate, Username, PROTOCOL_ID};
-use renet::{generate_random_bytes, DefaultChannel, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig, ServerEvent};
+use matcher::{RegisterServer, ServerUpdate, Username, PROTOCOL_ID, REGISTRATION_KEY};
+use renet::{
+    from_user_data, generate_random_bytes, DefaultChannel, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig,
+    ServerEvent,
+};
+use renet_token_issuer::ServerRegistration;
 use renet_visualizer::RenetServerVisualizer;
 
 use crate::{lobby_status::update_lobby_status, ClientMessages, Message, ServerMessages};
 use bincode::Options;
-use log::info;
+use log::{error, info};
 
 pub struct ChatServer {
     pub server: RenetServer,
@@ -28,19 +32,23 @@ impl ChatServer {
         let server_addr = socket.local_addr().unwrap();
         let connection_config = RenetConnectionConfig::default();
         let private_key = generate_random_bytes();
-        let server_config = ServerConfig::new(64, PROTOCOL_ID, server_addr, ServerAuthentication::Secure { private_key });
+        let authentication = ServerAuthentication::Secure {
+            private_key,
+            previous_private_keys: Vec::new(),
+        };
+        let server_config = ServerConfig::new(64, PROTOCOL_ID, server_addr, authentication);
 
         let password = if password.is_empty() { None } else { Some(password) };
 
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let server_registration = ServerRegistration::new(lobby_name.clone(), PROTOCOL_ID, server_addr, private_key, current_time);
         let register_server = RegisterServer {
             name: lobby_name,
-            address: server_addr,
             max_clients: server_config.max_clients as u64,
-            private_key,
             password,
             current_clients: 0,
+            registration: server_registration.sign(REGISTRATION_KEY),
         };
-        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
         let server = RenetServer::new(current_time, server_config, connection_config, socket).unwrap();
         let mut usernames = HashMap::new();
         usernames.insert(1, host_username);
@@ -53,7 +61,7 @@ impl ChatServer {
         // Create thread to register/update server status to matcher service
         let server_update_clone = server_update.clone();
         std::thread::spawn(move || {
-            update_lobby_status(register_server, server_update_clone);
+            update_lobby_status(register_server, server_registration, server_update_clone);
         });
 
         Self {
@@ -73,7 +81,14 @@ impl ChatServer {
             match event {
                 ServerEvent::ClientConnected(client_id, user_data) => {
                     self.visualizer.add_client(client_id);
-                    let username = Username::from_user_data(&user_data).0;
+                    let username = match from_user_data::<Username>(&user_data) {
+                        Ok(username) => username.0,
+                        Err(e) => {
+                            error!("Failed to read username from client {}: {}", client_id, e);
+                            self.server.disconnect(client_id);
+                            continue;
+                        }
+                    };
                     self.usernames.insert(client_id, username.clone());
                     let message = bincode::options()
                         .serialize(&ServerMessages::ClientConnected { client_id, username })
@@ -85,7 +100,7 @@ impl ChatServer {
                     let init_message = bincode::options().serialize(&init_message).unwrap();
                     self.server.send_message(client_id, DefaultChannel::Reliable, init_message);
                 }
-                ServerEvent::ClientDisconnected(client_id) => {
+                ServerEvent::ClientDisconnected { client_id, .. } => {
                     self.visualizer.remove_client(client_id);
                     self.usernames.remove(&client_id);
                     let message = bincode::options()
@@ -93,6 +108,7 @@ impl ChatServer {
                         .unwrap();
                     self.server.broadcast_message(DefaultChannel::Reliable, message);
                 }
+                ServerEvent::ConnectionRequested { .. } => {}
             }
         }
 
@@ -1,7 +1,6 @@
 use std::{
     collections::HashMap,
-    net::SocketAddr,
-    sync::{atomic::AtomicU64, RwLock},
+    sync::{Mutex, RwLock},
     time::{Duration, Instant, SystemTime},
 };
 
@@ -12,37 +11,36 @@ use actix_web::{
     App, HttpResponse, HttpServer,
 };
 
-use renet::{ConnectToken, NETCODE_KEY_BYTES};
+use renet::to_user_data;
+use renet_token_issuer::{TokenIssuer, TokenIssuerConfig};
 
-use matcher::{LobbyListing, RegisterServer, RequestConnection, ServerUpdate, Username, PROTOCOL_ID};
+use matcher::{LobbyListing, RegisterServer, RequestConnection, ServerUpdate, Username, REGISTRATION_KEY};
 
 struct Server {
     name: String,
     current_clients: u64,
     max_clients: u64,
     last_updated: Instant,
-    address: SocketAddr,
     password: Option<String>,
-    private_key: [u8; NETCODE_KEY_BYTES],
 }
 
 impl From<RegisterServer> for Server {
     fn from(register_server: RegisterServer) -> Self {
         Self {
             name: register_server.name.clone(),
-            address: register_server.address,
             current_clients: register_server.current_clients,
             max_clients: register_server.max_clients,
-            private_key: register_server.private_key,
             password: register_server.password,
             last_updated: Instant::now(),
         }
     }
 }
 
-#[derive(Default)]
+// The token issuer keeps the address and private key of the servers, the lobby list keeps the
+// information shown to the players.
 struct LobbyList {
     servers: RwLock<HashMap<u64, Server>>,
+    issuer: Mutex<TokenIssuer>,
 }
 
 #[actix_web::main]
@@ -50,8 +48,10 @@ async fn main() -> std::io::Result<()> {
     env_logger::init();
     println!("starting HTTP server at http://localhost:7000");
 
-    let lobby_list = Data::new(LobbyList::default());
-    let server_id = Data::new(AtomicU64::new(0));
+    let lobby_list = Data::new(LobbyList {
+        servers: RwLock::new(HashMap::new()),
+        issuer: Mutex::new(TokenIssuer::new(TokenIssuerConfig::new(*REGISTRATION_KEY))),
+    });
 
     let lobby_list_clone = lobby_list.clone();
     spawn(async move {
@@ -62,7 +62,6 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(middleware::Logger::default())
             .app_data(lobby_list.clone())
-            .app_data(server_id.clone())
             .service(server_register)
             .service(server_connect)
             .service(server_list)
@@ -80,19 +79,30 @@ async fn cleanup_server_list(lobby_list: Data<LobbyList>) {
         interval.tick().await;
 
         let mut servers = lobby_list.servers.write().unwrap();
-        servers.retain(|_, lobby| lobby.last_updated.elapsed() < Duration::from_secs(15));
+        let mut issuer = lobby_list.issuer.lock().unwrap();
+        servers.retain(|server_id, lobby| {
+            let active = lobby.last_updated.elapsed() < Duration::from_secs(15);
+            if !active {
+                issuer.remove_server(*server_id);
+            }
+            active
+        });
     }
 }
 
 #[post("/server")]
-async fn server_register(server_id: Data<AtomicU64>, lobby_list: Data<LobbyList>, info: Json<RegisterServer>) -> HttpResponse {
-    let new_server_id: u64 = server_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+async fn server_register(lobby_list: Data<LobbyList>, info: Json<RegisterServer>) -> HttpResponse {
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let server_id = match lobby_list.issuer.lock().unwrap().register(current_time, &info.registration) {
+        Ok(server_id) => server_id,
+        Err(e) => return HttpResponse::Unauthorized().body(e.to_string()),
+    };
 
     let mut servers = lobby_list.servers.write().unwrap();
     let server: Server = info.into_inner().into();
-    servers.insert(new_server_id, server);
+    servers.insert(server_id, server);
 
-    HttpResponse::Ok().json(new_server_id)
+    HttpResponse::Ok().json(server_id)
 }
 
 #[put("/server/{server_id}")]
@@ -118,7 +128,10 @@ async fn server_remove(path: web::Path<u64>, lobby_list: Data<LobbyList>) -> Htt
     let server_id = path.into_inner();
 
     match servers.remove(&server_id) {
-        Some(_) => HttpResponse::Ok().finish(),
+        Some(_) => {
+            lobby_list.issuer.lock().unwrap().remove_server(server_id);
+            HttpResponse::Ok().finish()
+        }
         None => HttpResponse::NotFound().finish(),
     }
 }
@@ -134,19 +147,16 @@ async fn server_connect(path: web::Path<u64>, lobby_list: Data<LobbyList>, reque
                 return HttpResponse::Unauthorized().finish();
             }
             let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-            let user_data = Username(request_connection.username.clone()).to_netcode_user_data();
+            let user_data = match to_user_data(&Username(request_connection.username.clone())) {
+                Ok(user_data) => user_data,
+                Err(_) => return HttpResponse::BadRequest().body("username is too big"),
+            };
             let client_id = current_time.as_millis() as u64;
-            let connect_token = ConnectToken::generate(
-                current_time,
-                PROTOCOL_ID,
-                300,
-                client_id,
-                15,
-                vec![server.address],
-                Some(&user_data),
-                &server.private_key,
-            )
-            .unwrap();
+            let issuer = lobby_list.issuer.lock().unwrap();
+            let connect_token = match issuer.issue_token(current_time, server_id, client_id, Some(user_data)) {
+                Ok(connect_token) => connect_token,
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            };
             let mut bytes = Vec::new();
             connect_token.write(&mut bytes).unwrap();
             HttpResponse::Ok().body(bytes)
@@ -12,14 +12,14 @@ use crate::{
 };
 use log::{debug, error, info};
 
-use super::{ReceiveChannel, SendChannel};
+use super::{ReceiveChannel, SendChannel, SendChannelStats};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct SliceMessage {
-    chunk_id: u16,
-    slice_id: u32,
-    num_slices: u32,
-    data: Payload,
+    pub(crate) chunk_id: u16,
+    pub(crate) slice_id: u32,
+    pub(crate) num_slices: u32,
+    pub(crate) data: Payload,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +58,7 @@ enum Sending {
         current_slice_id: usize,
         num_acked_slices: usize,
         acked: Vec<bool>,
+        first_sent_times: Vec<Option<Duration>>,
         data: Bytes,
         resend_timers: Vec<Timer>,
     },
@@ -76,6 +77,7 @@ pub struct SendBlockChannel {
     message_send_queue_size: usize,
     packets_sent: SequenceBuffer<PacketSent>,
     messages_to_send: VecDeque<Bytes>,
+    stats: SendChannelStats,
     error: Option<ChannelError>,
 }
 
@@ -96,6 +98,7 @@ pub struct ReceiveBlockChannel {
     channel_id: u8,
     receiving: Receiving,
     messages_received: VecDeque<Payload>,
+    num_messages_received: u64,
     slice_size: usize,
     max_message_size: u64,
     error: Option<ChannelError>,
@@ -140,6 +143,7 @@ impl SendBlockChannel {
             sending: Sending::No,
             packets_sent: SequenceBuffer::with_capacity(config.sent_packet_buffer_size),
             messages_to_send: VecDeque::with_capacity(config.message_send_queue_size),
+            stats: SendChannelStats::default(),
             error: None,
         }
     }
@@ -152,6 +156,7 @@ impl SendBlockChannel {
                 num_slices,
                 current_slice_id,
                 acked,
+                first_sent_times,
                 resend_timers,
                 data,
                 ..
@@ -189,6 +194,19 @@ impl SendBlockChannel {
                     }
 
                     available_bytes -= message_size;
+                    if first_sent_times[slice_id].is_some() {
+                        trace_event!(
+                            trace,
+                            "resending slice message",
+                            channel_id = self.channel_id,
+                            chunk_id = self.chunk_id,
+                            slice_id = slice_id
+                        );
+                        self.stats.messages_resent += 1;
+                    } else {
+                        first_sent_times[slice_id] = Some(current_time);
+                        self.stats.messages_sent += 1;
+                    }
                     resend_timer.reset(current_time);
 
                     info!(
@@ -210,7 +228,7 @@ impl SendChannel for SendBlockChannel {
     fn get_messages_to_send(&mut self, available_bytes: u64, sequence: u16, current_time: Duration) -> Option<ChannelPacketData> {
         if let Sending::No = self.sending {
             if let Some(message) = self.messages_to_send.pop_front() {
-                self.send_message(message, current_time);
+                self.start_sending(message, current_time);
             }
         }
 
@@ -253,13 +271,14 @@ impl SendChannel for SendBlockChannel {
         })
     }
 
-    fn process_ack(&mut self, ack: u16) {
+    fn process_ack(&mut self, ack: u16, current_time: Duration) {
         match &mut self.sending {
             Sending::No => {}
             Sending::Yes {
                 num_acked_slices,
                 num_slices,
                 acked,
+                first_sent_times,
                 ..
             } => {
                 if let Some(sent_packet) = self.packets_sent.get_mut(ack) {
@@ -272,6 +291,10 @@ impl SendChannel for SendBlockChannel {
                         if !acked[slice_id as usize] {
                             acked[slice_id as usize] = true;
                             *num_acked_slices += 1;
+                            if let Some(first_sent_time) = first_sent_times[slice_id as usize] {
+                                self.stats.total_ack_time += current_time.saturating_sub(first_sent_time);
+                            }
+                            self.stats.messages_acked += 1;
                             info!(
                                 "Acked SliceMessage {} from chunk_id {}. ({}/{})",
                                 slice_id, self.chunk_id, num_acked_slices, num_slices
@@ -300,6 +323,7 @@ impl SendChannel for SendBlockChannel {
                 payload.len(),
                 self.max_message_size
             );
+            self.stats.messages_dropped += 1;
             self.error = Some(ChannelError::SentMessageAboveMaxSize);
             return;
         }
@@ -314,9 +338,33 @@ impl SendChannel for SendBlockChannel {
                 return;
             }
             self.messages_to_send.push_back(payload);
+            self.stats.messages_queued += 1;
             return;
         }
 
+        self.stats.messages_queued += 1;
+        self.start_sending(payload, current_time);
+    }
+
+    fn can_send_message(&self) -> bool {
+        self.messages_to_send.len() < self.message_send_queue_size
+    }
+
+    fn stats(&self) -> SendChannelStats {
+        let sending = matches!(self.sending, Sending::Yes { .. }) as usize;
+        SendChannelStats {
+            queue_depth: self.messages_to_send.len() + sending,
+            ..self.stats
+        }
+    }
+
+    fn error(&self) -> Option<ChannelError> {
+        self.error
+    }
+}
+
+impl SendBlockChannel {
+    fn start_sending(&mut self, payload: Bytes, current_time: Duration) {
         let num_slices = (payload.len() + self.slice_size - 1) / self.slice_size;
         let mut resend_timer = Timer::new(current_time, self.resend_time);
         resend_timer.finish();
@@ -327,19 +375,12 @@ impl SendChannel for SendBlockChannel {
             current_slice_id: 0,
             num_acked_slices: 0,
             acked: vec![false; num_slices],
+            first_sent_times: vec![None; num_slices],
             num_slices,
             resend_timers,
             data: payload,
         };
     }
-
-    fn can_send_message(&self) -> bool {
-        self.messages_to_send.len() < self.message_send_queue_size
-    }
-
-    fn error(&self) -> Option<ChannelError> {
-        self.error
-    }
 }
 
 impl ReceiveBlockChannel {
@@ -352,6 +393,7 @@ impl ReceiveBlockChannel {
             channel_id: config.channel_id,
             receiving: Receiving::No,
             messages_received: VecDeque::new(),
+            num_messages_received: 0,
             error: None,
         }
     }
@@ -495,7 +537,13 @@ impl ReceiveChannel for ReceiveBlockChannel {
     }
 
     fn receive_message(&mut self) -> Option<Payload> {
-        self.messages_received.pop_front()
+        let message = self.messages_received.pop_front()?;
+        self.num_messages_received += 1;
+        Some(message)
+    }
+
+    fn num_messages_received(&self) -> u64 {
+        self.num_messages_received
     }
 
     fn error(&self) -> Option<ChannelError> {
@@ -523,8 +571,8 @@ mod tests {
 
         let slice_messages = send_channel.generate_slice_packets(u64::MAX, current_time).unwrap();
         assert_eq!(slice_messages.len(), 2);
-        send_channel.process_ack(0);
-        send_channel.process_ack(1);
+        send_channel.process_ack(0, current_time);
+        send_channel.process_ack(1, current_time);
 
         for slice_message in slice_messages.into_iter() {
             receive_channel.process_slice_message(&slice_message).unwrap();
@@ -552,7 +600,7 @@ mod tests {
                 None => break,
                 Some(data) => {
                     receive_channel.process_messages(data.messages);
-                    send_channel.process_ack(sequence);
+                    send_channel.process_ack(sequence, Duration::ZERO);
                     sequence += 1;
                 }
             }
@@ -583,7 +631,7 @@ mod tests {
         receive_channel.process_messages(block_channel_data.messages);
         let received_first_message = receive_channel.receive_message().unwrap();
         assert_eq!(first_message, received_first_message);
-        send_channel.process_ack(0);
+        send_channel.process_ack(0, current_time);
 
         // Second message
         let block_channel_data = send_channel.get_messages_to_send(u64::MAX, 1, current_time).unwrap();
@@ -591,10 +639,17 @@ mod tests {
         receive_channel.process_messages(block_channel_data.messages);
         let received_second_message = receive_channel.receive_message().unwrap();
         assert_eq!(second_message, received_second_message);
-        send_channel.process_ack(1);
+        send_channel.process_ack(1, current_time);
 
         // Check there is no message to send
         assert!(matches!(send_channel.sending, Sending::No));
+
+        let stats = send_channel.stats();
+        assert_eq!(stats.messages_queued, 2);
+        assert_eq!(stats.messages_sent, 10);
+        assert_eq!(stats.messages_acked, 10);
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(receive_channel.num_messages_received(), 2);
     }
 
     #[test]
@@ -613,13 +668,13 @@ mod tests {
         let _ = send_channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
         let _ = send_channel.get_messages_to_send(u64::MAX, 1, current_time).unwrap();
 
-        send_channel.process_ack(0);
+        send_channel.process_ack(0, current_time);
         let _ = send_channel.get_messages_to_send(u64::MAX, 2, current_time).unwrap();
 
-        send_channel.process_ack(1);
+        send_channel.process_ack(1, current_time);
         assert!(matches!(send_channel.sending, Sending::Yes { .. }));
 
-        send_channel.process_ack(2);
+        send_channel.process_ack(2, current_time);
         assert!(matches!(send_channel.sending, Sending::No));
     }
 
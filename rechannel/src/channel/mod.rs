@@ -28,17 +28,76 @@ pub enum ChannelConfig {
     Block(BlockChannelConfig),
 }
 
+/// Traffic counters of a channel in a connection.
+///
+/// For the block channel, sent, resent and acked messages are counted in slices.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ChannelStats {
+    pub channel_id: u8,
+    /// Bytes of the messages sent in the channel, including resent messages.
+    pub bytes_sent: u64,
+    /// Bytes of the messages received in the channel.
+    pub bytes_received: u64,
+    /// Bytes per second sent in the channel, measured over the last second.
+    pub sent_bytes_per_second: f32,
+    /// Bytes per second received in the channel, measured over the last second.
+    pub received_bytes_per_second: f32,
+    /// Number of messages accepted in the send queue.
+    pub messages_queued: u64,
+    /// Number of messages sent for the first time.
+    pub messages_sent: u64,
+    /// Number of messages or slices resent because they were not acked in time.
+    pub messages_resent: u64,
+    /// Number of messages acked by the remote connection.
+    pub messages_acked: u64,
+    /// Number of messages dropped because they were bigger than the allowed size.
+    pub messages_dropped: u64,
+    /// Number of unreliable messages discarded because they did not fit in the packet budget.
+    pub messages_discarded: u64,
+    /// Number of messages received by the application.
+    pub messages_received: u64,
+    /// Number of messages waiting to be sent or acked.
+    pub queue_depth: usize,
+    /// Average time between sending a message for the first time and its ack.
+    pub average_ack_time: Duration,
+}
+
+/// Counters kept by the send channels.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SendChannelStats {
+    pub messages_queued: u64,
+    pub messages_sent: u64,
+    pub messages_resent: u64,
+    pub messages_acked: u64,
+    pub messages_dropped: u64,
+    pub messages_discarded: u64,
+    pub queue_depth: usize,
+    pub total_ack_time: Duration,
+}
+
+impl SendChannelStats {
+    pub fn average_ack_time(&self) -> Duration {
+        if self.messages_acked == 0 {
+            return Duration::ZERO;
+        }
+
+        self.total_ack_time.div_f64(self.messages_acked as f64)
+    }
+}
+
 pub(crate) trait SendChannel: std::fmt::Debug {
     fn get_messages_to_send(&mut self, available_bytes: u64, sequence: u16, current_time: Duration) -> Option<ChannelPacketData>;
     fn send_message(&mut self, payload: Bytes, current_time: Duration);
-    fn process_ack(&mut self, ack: u16);
+    fn process_ack(&mut self, ack: u16, current_time: Duration);
     fn can_send_message(&self) -> bool;
+    fn stats(&self) -> SendChannelStats;
     fn error(&self) -> Option<ChannelError>;
 }
 
 pub(crate) trait ReceiveChannel: std::fmt::Debug {
     fn process_messages(&mut self, messages: Vec<Payload>);
     fn receive_message(&mut self) -> Option<Payload>;
+    fn num_messages_received(&self) -> u64;
     fn error(&self) -> Option<ChannelError>;
 }
 
@@ -11,18 +11,19 @@ use serde::{Deserialize, Serialize};
 
 use std::time::Duration;
 
-use super::{ReceiveChannel, SendChannel};
+use super::{ReceiveChannel, SendChannel, SendChannelStats};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub(crate) struct ReliableMessage {
-    id: u16,
-    payload: Bytes,
+    pub(crate) id: u16,
+    pub(crate) payload: Bytes,
 }
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct ReliableMessageSent {
     reliable_message: ReliableMessage,
     resend_timer: Timer,
+    first_sent_time: Option<Duration>,
 }
 
 #[derive(Debug, Clone)]
@@ -63,7 +64,7 @@ pub(crate) struct SendReliableChannel {
     packets_sent: SequenceBuffer<PacketSent>,
     messages_send: SequenceBuffer<ReliableMessageSent>,
     send_message_id: u16,
-    num_messages_sent: u64,
+    stats: SendChannelStats,
     oldest_unacked_message_id: u16,
     error: Option<ChannelError>,
 }
@@ -91,6 +92,7 @@ impl ReliableMessageSent {
         Self {
             reliable_message,
             resend_timer,
+            first_sent_time: None,
         }
     }
 }
@@ -134,7 +136,7 @@ impl SendReliableChannel {
             packets_sent: SequenceBuffer::with_capacity(config.sent_packet_buffer_size),
             messages_send: SequenceBuffer::with_capacity(config.message_send_queue_size),
             message_resend_time: config.message_resend_time,
-            num_messages_sent: 0,
+            stats: SendChannelStats::default(),
             error: None,
         }
     }
@@ -169,6 +171,18 @@ impl SendChannel for SendReliableChannel {
 
                 if serialized_size <= available_bytes {
                     available_bytes -= serialized_size;
+                    if message_send.first_sent_time.is_some() {
+                        trace_event!(
+                            trace,
+                            "resending reliable message",
+                            channel_id = self.channel_id,
+                            message_id = message_id
+                        );
+                        self.stats.messages_resent += 1;
+                    } else {
+                        message_send.first_sent_time = Some(current_time);
+                        self.stats.messages_sent += 1;
+                    }
                     message_send.resend_timer.reset(current_time);
                     message_ids.push(message_id);
                     let message = match bincode::options().serialize(&message_send.reliable_message) {
@@ -197,7 +211,7 @@ impl SendChannel for SendReliableChannel {
         })
     }
 
-    fn process_ack(&mut self, ack: u16) {
+    fn process_ack(&mut self, ack: u16, current_time: Duration) {
         if let Some(sent_packet) = self.packets_sent.get_mut(ack) {
             if sent_packet.acked {
                 return;
@@ -205,8 +219,11 @@ impl SendChannel for SendReliableChannel {
             sent_packet.acked = true;
 
             for &message_id in sent_packet.messages_id.iter() {
-                if self.messages_send.exists(message_id) {
-                    self.messages_send.remove(message_id);
+                if let Some(message_send) = self.messages_send.remove(message_id) {
+                    if let Some(first_sent_time) = message_send.first_sent_time {
+                        self.stats.total_ack_time += current_time.saturating_sub(first_sent_time);
+                    }
+                    self.stats.messages_acked += 1;
                 }
             }
 
@@ -236,6 +253,7 @@ impl SendChannel for SendReliableChannel {
                 payload.len(),
                 self.max_message_size
             );
+            self.stats.messages_dropped += 1;
             self.error = Some(ChannelError::SentMessageAboveMaxSize);
             return;
         }
@@ -246,13 +264,20 @@ impl SendChannel for SendReliableChannel {
         let entry = ReliableMessageSent::new(reliable_message, self.message_resend_time, current_time);
         self.messages_send.insert(message_id, entry);
 
-        self.num_messages_sent += 1;
+        self.stats.messages_queued += 1;
     }
 
     fn can_send_message(&self) -> bool {
         self.messages_send.available(self.send_message_id)
     }
 
+    fn stats(&self) -> SendChannelStats {
+        SendChannelStats {
+            queue_depth: self.send_message_id.wrapping_sub(self.oldest_unacked_message_id) as usize,
+            ..self.stats
+        }
+    }
+
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
@@ -333,6 +358,10 @@ impl ReceiveChannel for ReceiveReliableChannel {
         self.messages_received.remove(received_message_id).map(|m| m.payload.to_vec())
     }
 
+    fn num_messages_received(&self) -> u64 {
+        self.num_messages_received
+    }
+
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
@@ -373,12 +402,12 @@ mod tests {
         let sequence = 0;
 
         assert!(!send_channel.has_messages_to_send());
-        assert_eq!(send_channel.num_messages_sent, 0);
+        assert_eq!(send_channel.stats().messages_queued, 0);
 
         let message = TestMessages::Second(0).serialize();
 
         send_channel.send_message(message.clone(), current_time);
-        assert_eq!(send_channel.num_messages_sent, 1);
+        assert_eq!(send_channel.stats().messages_queued, 1);
         assert!(receive_channel.receive_message().is_none());
 
         let channel_data = send_channel.get_messages_to_send(u64::MAX, sequence, current_time).unwrap();
@@ -389,7 +418,7 @@ mod tests {
         assert_eq!(received_message, message);
 
         assert!(send_channel.has_messages_to_send());
-        send_channel.process_ack(sequence);
+        send_channel.process_ack(sequence, current_time);
         assert!(!send_channel.has_messages_to_send());
     }
 
@@ -411,7 +440,7 @@ mod tests {
         let channel_data = channel.get_messages_to_send(message_size, 0, current_time).unwrap();
         assert_eq!(channel_data.messages.len(), 1);
 
-        channel.process_ack(0);
+        channel.process_ack(0, current_time);
 
         let channel_data = channel.get_messages_to_send(message_size, 1, current_time).unwrap();
         assert_eq!(channel_data.messages.len(), 1);
@@ -440,6 +469,40 @@ mod tests {
         assert_eq!(channel_data.messages.len(), 1);
     }
 
+    #[test]
+    fn send_stats() {
+        let mut current_time = Duration::ZERO;
+        let message_resend_time = Duration::from_millis(100);
+        let config = ReliableChannelConfig {
+            message_resend_time,
+            ..Default::default()
+        };
+        let mut channel = SendReliableChannel::new(config);
+
+        channel.send_message(TestMessages::First.serialize(), current_time);
+        channel.send_message(TestMessages::Second(0).serialize(), current_time);
+        assert_eq!(channel.stats().queue_depth, 2);
+
+        channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+        current_time += message_resend_time;
+        channel.get_messages_to_send(u64::MAX, 1, current_time).unwrap();
+
+        current_time += Duration::from_millis(50);
+        channel.process_ack(1, current_time);
+
+        let stats = channel.stats();
+        assert_eq!(stats.messages_queued, 2);
+        assert_eq!(stats.messages_sent, 2);
+        assert_eq!(stats.messages_resent, 2);
+        assert_eq!(stats.messages_acked, 2);
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.total_ack_time, Duration::from_millis(300));
+
+        channel.send_message(vec![0; 4000].into(), current_time);
+        assert_eq!(channel.stats().messages_dropped, 1);
+        assert_eq!(channel.error(), Some(ChannelError::SentMessageAboveMaxSize));
+    }
+
     #[test]
     fn out_of_sync() {
         let current_time = Duration::ZERO;
@@ -7,7 +7,7 @@ use std::{collections::VecDeque, time::Duration};
 
 use bytes::Bytes;
 
-use super::{ReceiveChannel, SendChannel};
+use super::{ReceiveChannel, SendChannel, SendChannelStats};
 
 /// Configuration for a unreliable and unordered channel.
 /// Messages sent in this channel can be lost and arrive in an different order that they were sent.
@@ -33,6 +33,7 @@ pub(crate) struct SendUnreliableChannel {
     max_message_size: u64,
     message_send_queue_size: usize,
     messages_to_send: VecDeque<Bytes>,
+    stats: SendChannelStats,
     error: Option<ChannelError>,
 }
 
@@ -42,6 +43,7 @@ pub(crate) struct ReceiveUnreliableChannel {
     max_message_size: u64,
     message_receive_queue_size: usize,
     messages_received: VecDeque<Payload>,
+    num_messages_received: u64,
     error: Option<ChannelError>,
 }
 
@@ -67,6 +69,7 @@ impl SendUnreliableChannel {
             max_message_size: config.max_message_size,
             message_send_queue_size: config.message_send_queue_size,
             messages_to_send: VecDeque::with_capacity(config.message_send_queue_size),
+            stats: SendChannelStats::default(),
             error: None,
         }
     }
@@ -84,10 +87,12 @@ impl SendChannel for SendUnreliableChannel {
         while let Some(message) = self.messages_to_send.pop_front() {
             let message_size = message.len() as u64;
             if message_size > available_bytes {
+                self.stats.messages_discarded += 1;
                 continue;
             }
 
             available_bytes -= message_size;
+            self.stats.messages_sent += 1;
             messages.push(message.to_vec());
         }
 
@@ -101,7 +106,7 @@ impl SendChannel for SendUnreliableChannel {
         })
     }
 
-    fn process_ack(&mut self, _ack: u16) {}
+    fn process_ack(&mut self, _ack: u16, _current_time: Duration) {}
 
     fn send_message(&mut self, payload: Bytes, _current_time: Duration) {
         if self.error.is_some() {
@@ -114,6 +119,7 @@ impl SendChannel for SendUnreliableChannel {
                 payload.len(),
                 self.max_message_size
             );
+            self.stats.messages_dropped += 1;
             self.error = Some(ChannelError::SentMessageAboveMaxSize);
             return;
         }
@@ -125,12 +131,20 @@ impl SendChannel for SendUnreliableChannel {
         }
 
         self.messages_to_send.push_back(payload);
+        self.stats.messages_queued += 1;
     }
 
     fn can_send_message(&self) -> bool {
         self.messages_to_send.len() < self.message_send_queue_size
     }
 
+    fn stats(&self) -> SendChannelStats {
+        SendChannelStats {
+            queue_depth: self.messages_to_send.len(),
+            ..self.stats
+        }
+    }
+
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
@@ -145,6 +159,7 @@ impl ReceiveUnreliableChannel {
             max_message_size: config.max_message_size,
             message_receive_queue_size: config.message_receive_queue_size,
             messages_received: VecDeque::with_capacity(config.message_receive_queue_size),
+            num_messages_received: 0,
             error: None,
         }
     }
@@ -181,10 +196,45 @@ impl ReceiveChannel for ReceiveUnreliableChannel {
     }
 
     fn receive_message(&mut self) -> Option<Payload> {
-        self.messages_received.pop_front()
+        let message = self.messages_received.pop_front()?;
+        self.num_messages_received += 1;
+        Some(message)
+    }
+
+    fn num_messages_received(&self) -> u64 {
+        self.num_messages_received
     }
 
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discard_messages_above_budget() {
+        let config = UnreliableChannelConfig {
+            packet_budget: 100,
+            max_message_size: 100,
+            ..Default::default()
+        };
+        let mut channel = SendUnreliableChannel::new(config);
+
+        channel.send_message(vec![0; 60].into(), Duration::ZERO);
+        channel.send_message(vec![0; 60].into(), Duration::ZERO);
+        let packet = channel.get_messages_to_send(u64::MAX, 0, Duration::ZERO).unwrap();
+        assert_eq!(packet.messages.len(), 1);
+
+        let stats = channel.stats();
+        assert_eq!(stats.messages_sent, 1);
+        assert_eq!(stats.messages_discarded, 1);
+        assert_eq!(stats.messages_dropped, 0);
+
+        channel.send_message(vec![0; 200].into(), Duration::ZERO);
+        assert_eq!(channel.stats().messages_dropped, 1);
+        assert_eq!(channel.error(), Some(ChannelError::SentMessageAboveMaxSize));
+    }
+}
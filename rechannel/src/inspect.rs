@@ -0,0 +1,193 @@
+//! Decoding of rechannel packets outside of a connection, used to inspect captures.
+use bincode::Options;
+
+use crate::channel::block::SliceMessage;
+use crate::channel::reliable::ReliableMessage;
+use crate::channel::ChannelConfig;
+use crate::error::DisconnectionReason;
+use crate::packet::{AckData, ChannelPacketData, Packet};
+
+/// A decoded rechannel packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InspectedPacket {
+    Normal {
+        sequence: u16,
+        ack: u16,
+        ack_bits: u32,
+        channels: Vec<InspectedChannel>,
+    },
+    Fragment {
+        sequence: u16,
+        ack: u16,
+        ack_bits: u32,
+        fragment_id: u8,
+        num_fragments: u8,
+        payload: Vec<u8>,
+    },
+    Heartbeat {
+        ack: u16,
+        ack_bits: u32,
+    },
+    Disconnect {
+        reason: DisconnectionReason,
+    },
+}
+
+/// The messages of a channel in a packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InspectedChannel {
+    pub channel_id: u8,
+    pub messages: Vec<InspectedMessage>,
+}
+
+/// A channel message, decoded with the type of the channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InspectedMessage {
+    Reliable {
+        message_id: u16,
+        len: usize,
+    },
+    Unreliable {
+        len: usize,
+    },
+    Slice {
+        chunk_id: u16,
+        slice_id: u32,
+        num_slices: u32,
+        len: usize,
+    },
+    /// A message from a channel not in the configuration, or that could not be decoded with the channel type.
+    Unknown {
+        len: usize,
+    },
+}
+
+/// Decodes the packet, the messages are decoded with the channel types from the configuration.
+pub fn decode_packet(packet: &[u8], channels_config: &[ChannelConfig]) -> Result<InspectedPacket, bincode::Error> {
+    let packet: Packet = bincode::options().deserialize(packet)?;
+    let packet = match packet {
+        Packet::Normal {
+            sequence,
+            ack_data: AckData { ack, ack_bits },
+            channels_packet_data,
+        } => InspectedPacket::Normal {
+            sequence,
+            ack,
+            ack_bits,
+            channels: inspect_channels(channels_packet_data, channels_config),
+        },
+        Packet::Fragment {
+            sequence,
+            ack_data: AckData { ack, ack_bits },
+            fragment_data,
+        } => InspectedPacket::Fragment {
+            sequence,
+            ack,
+            ack_bits,
+            fragment_id: fragment_data.fragment_id,
+            num_fragments: fragment_data.num_fragments,
+            payload: fragment_data.payload,
+        },
+        Packet::Heartbeat {
+            ack_data: AckData { ack, ack_bits },
+        } => InspectedPacket::Heartbeat { ack, ack_bits },
+        Packet::Disconnect { reason } => InspectedPacket::Disconnect { reason },
+    };
+
+    Ok(packet)
+}
+
+/// Decodes the channels of a fragmented packet, from the payloads of all its fragments in order.
+pub fn decode_reassembled(payload: &[u8], channels_config: &[ChannelConfig]) -> Result<Vec<InspectedChannel>, bincode::Error> {
+    let channels_packet_data: Vec<ChannelPacketData> = bincode::options().deserialize(payload)?;
+    Ok(inspect_channels(channels_packet_data, channels_config))
+}
+
+fn inspect_channels(channels_packet_data: Vec<ChannelPacketData>, channels_config: &[ChannelConfig]) -> Vec<InspectedChannel> {
+    channels_packet_data
+        .into_iter()
+        .map(|channel_data| {
+            let config = channels_config.iter().find(|c| c.channel_id() == channel_data.channel_id);
+            let messages = channel_data
+                .messages
+                .iter()
+                .map(|message| inspect_message(message, config))
+                .collect();
+
+            InspectedChannel {
+                channel_id: channel_data.channel_id,
+                messages,
+            }
+        })
+        .collect()
+}
+
+fn inspect_message(message: &[u8], config: Option<&ChannelConfig>) -> InspectedMessage {
+    let unknown = InspectedMessage::Unknown { len: message.len() };
+    match config {
+        Some(ChannelConfig::Reliable(_)) => match bincode::options().deserialize::<ReliableMessage>(message) {
+            Ok(message) => InspectedMessage::Reliable {
+                message_id: message.id,
+                len: message.payload.len(),
+            },
+            Err(_) => unknown,
+        },
+        Some(ChannelConfig::Block(_)) => match bincode::options().deserialize::<SliceMessage>(message) {
+            Ok(message) => InspectedMessage::Slice {
+                chunk_id: message.chunk_id,
+                slice_id: message.slice_id,
+                num_slices: message.num_slices,
+                len: message.data.len(),
+            },
+            Err(_) => unknown,
+        },
+        Some(ChannelConfig::Unreliable(_)) => InspectedMessage::Unreliable { len: message.len() },
+        None => unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::channel::DefaultChannel;
+    use crate::remote_connection::{ConnectionConfig, RemoteConnection};
+
+    #[test]
+    fn inspect_connection_packets() {
+        let config = ConnectionConfig::default();
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        connection.send_message(DefaultChannel::Reliable, vec![1, 2, 3]);
+        connection.send_message(DefaultChannel::Unreliable, vec![4; 5]);
+        connection.send_message(DefaultChannel::Block, vec![5; 10]);
+
+        let packets = connection.get_packets_to_send().unwrap();
+        assert_eq!(packets.len(), 1);
+
+        let packet = decode_packet(&packets[0], &config.send_channels_config).unwrap();
+        let mut channels = match packet {
+            InspectedPacket::Normal { sequence: 0, channels, .. } => channels,
+            packet => panic!("unexpected packet: {:?}", packet),
+        };
+        channels.sort_by_key(|c| c.channel_id);
+        assert_eq!(channels.len(), 3);
+        assert_eq!(channels[0].messages, vec![InspectedMessage::Reliable { message_id: 0, len: 3 }]);
+        assert_eq!(channels[1].messages, vec![InspectedMessage::Unreliable { len: 5 }]);
+        assert_eq!(
+            channels[2].messages,
+            vec![InspectedMessage::Slice {
+                chunk_id: 0,
+                slice_id: 0,
+                num_slices: 1,
+                len: 10
+            }]
+        );
+
+        let packet = decode_packet(&packets[0], &[]).unwrap();
+        match packet {
+            InspectedPacket::Normal { channels, .. } => assert!(matches!(channels[0].messages[0], InspectedMessage::Unknown { .. })),
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+}
@@ -1,5 +1,11 @@
+#[macro_use]
+mod trace;
+
 pub mod channel;
 pub mod error;
+// Used in the renet_inspect crate
+#[doc(hidden)]
+pub mod inspect;
 mod packet;
 mod reassembly_fragment;
 pub mod remote_connection;
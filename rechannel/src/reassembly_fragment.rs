@@ -198,6 +198,13 @@ impl SequenceBuffer<ReassemblyFragment> {
             let reassembly_fragment = self.remove(sequence).expect("ReassemblyFragment always exists here");
 
             let messages: Vec<ChannelPacketData> = bincode::options().deserialize(&reassembly_fragment.buffer)?;
+            trace_event!(
+                debug,
+                "reassembled fragmented packet",
+                sequence = sequence,
+                num_fragments = num_fragments,
+                len = reassembly_fragment.buffer.len()
+            );
 
             log::trace!("Completed the reassembly of packet {}.", reassembly_fragment.sequence);
             return Ok(Some(messages));
@@ -1,9 +1,9 @@
-use crate::channel::{ChannelConfig, DefaultChannel, ReceiveChannel, SendChannel};
+use crate::channel::{ChannelConfig, ChannelStats, DefaultChannel, ReceiveChannel, SendChannel};
 use crate::error::{DisconnectionReason, RechannelError};
 use crate::packet::{Packet, Payload};
 
 use crate::reassembly_fragment::{build_fragments, FragmentConfig, ReassemblyFragment};
-use crate::sequence_buffer::SequenceBuffer;
+use crate::sequence_buffer::{sequence_less_than, SequenceBuffer};
 use crate::timer::Timer;
 
 use bincode::Options;
@@ -13,12 +13,52 @@ use log::error;
 use std::collections::HashMap;
 use std::time::Duration;
 
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 struct SentPacket {
     time: Duration,
     ack: bool,
 }
 
+#[derive(Debug, Default)]
+struct ChannelTraffic {
+    bytes_sent: u64,
+    bytes_received: u64,
+    window_bytes_sent: u64,
+    window_bytes_received: u64,
+    sent_bytes_per_second: f32,
+    received_bytes_per_second: f32,
+}
+
+/// Cumulative packet counters of a connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PacketStats {
+    /// Packets with messages sent, a fragmented packet is counted once.
+    pub packets_sent: u64,
+    /// Packets with messages received, a fragmented packet is counted once it's reassembled.
+    /// Duplicated packets are not counted.
+    pub packets_received: u64,
+    pub heartbeats_sent: u64,
+    pub heartbeats_received: u64,
+    /// Packets sent split in fragments.
+    pub fragmented_packets_sent: u64,
+    /// Fragmented packets received and reassembled.
+    pub fragmented_packets_received: u64,
+    pub fragments_received: u64,
+    /// Fragments that failed to be reassembled.
+    pub fragments_dropped: u64,
+    /// Packets received with a sequence that was already received.
+    pub duplicate_packets: u64,
+    /// Packets received after a packet with a more recent sequence.
+    pub out_of_order_packets: u64,
+    /// Packets sent that were acked by the remote connection.
+    pub packets_acked: u64,
+    /// Packets sent whose ack never arrived and can no longer arrive, the remote connection acked
+    /// more recent packets. Delivered packets are only counted when all the acks for them were lost.
+    pub packets_lost: u64,
+}
+
 #[derive(Debug)]
 enum ConnectionState {
     Connected,
@@ -53,6 +93,10 @@ pub struct RemoteConnection {
     rtt: f32,
     packet_loss: f32,
     acks: Vec<u16>,
+    channels_traffic: HashMap<u8, ChannelTraffic>,
+    bandwidth_window_start: Duration,
+    packet_stats: PacketStats,
+    lost_check_sequence: u16,
 }
 
 impl SentPacket {
@@ -61,6 +105,26 @@ impl SentPacket {
     }
 }
 
+impl ChannelTraffic {
+    fn add_sent(&mut self, bytes: u64) {
+        self.bytes_sent += bytes;
+        self.window_bytes_sent += bytes;
+    }
+
+    fn add_received(&mut self, bytes: u64) {
+        self.bytes_received += bytes;
+        self.window_bytes_received += bytes;
+    }
+
+    fn update_bandwidth(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f32();
+        self.sent_bytes_per_second = self.window_bytes_sent as f32 / secs;
+        self.received_bytes_per_second = self.window_bytes_received as f32 / secs;
+        self.window_bytes_sent = 0;
+        self.window_bytes_received = 0;
+    }
+}
+
 impl Default for ConnectionConfig {
     fn default() -> Self {
         Self {
@@ -94,6 +158,11 @@ impl RemoteConnection {
             assert!(old_channel.is_none(), "already exists send channel with id {}", channel_id);
         }
 
+        let mut channels_traffic = HashMap::new();
+        for channel_config in config.send_channels_config.iter().chain(config.receive_channels_config.iter()) {
+            channels_traffic.insert(channel_config.channel_id(), ChannelTraffic::default());
+        }
+
         let mut receive_channels = HashMap::new();
         for channel_config in config.receive_channels_config.iter() {
             let (_, receive_channel) = channel_config.new_channels();
@@ -116,6 +185,10 @@ impl RemoteConnection {
             rtt: 0.0,
             packet_loss: 0.0,
             acks: vec![],
+            channels_traffic,
+            bandwidth_window_start: current_time,
+            packet_stats: PacketStats::default(),
+            lost_check_sequence: 0,
         }
     }
 
@@ -127,6 +200,45 @@ impl RemoteConnection {
         self.packet_loss
     }
 
+    /// Returns the traffic counters of the channels, ordered by channel id.
+    pub fn channels_stats(&self) -> Vec<ChannelStats> {
+        let mut channels_stats: Vec<ChannelStats> = self
+            .channels_traffic
+            .iter()
+            .map(|(&channel_id, traffic)| {
+                let send_stats = self.send_channels.get(&channel_id).map(|c| c.stats()).unwrap_or_default();
+                ChannelStats {
+                    channel_id,
+                    bytes_sent: traffic.bytes_sent,
+                    bytes_received: traffic.bytes_received,
+                    sent_bytes_per_second: traffic.sent_bytes_per_second,
+                    received_bytes_per_second: traffic.received_bytes_per_second,
+                    messages_queued: send_stats.messages_queued,
+                    messages_sent: send_stats.messages_sent,
+                    messages_resent: send_stats.messages_resent,
+                    messages_acked: send_stats.messages_acked,
+                    messages_dropped: send_stats.messages_dropped,
+                    messages_discarded: send_stats.messages_discarded,
+                    messages_received: self.receive_channels.get(&channel_id).map_or(0, |c| c.num_messages_received()),
+                    queue_depth: send_stats.queue_depth,
+                    average_ack_time: send_stats.average_ack_time(),
+                }
+            })
+            .collect();
+        channels_stats.sort_by_key(|stats| stats.channel_id);
+        channels_stats
+    }
+
+    /// Returns the number of fragments that failed to be reassembled.
+    pub fn num_fragment_errors(&self) -> u64 {
+        self.packet_stats.fragments_dropped
+    }
+
+    /// Returns the cumulative packet counters of the connection.
+    pub fn packet_stats(&self) -> PacketStats {
+        self.packet_stats
+    }
+
     pub fn is_connected(&self) -> bool {
         matches!(self.state, ConnectionState::Connected)
     }
@@ -144,6 +256,7 @@ impl RemoteConnection {
             return;
         }
 
+        trace_event!(info, "connection disconnected", reason = DisconnectionReason::DisconnectedByClient);
         self.state = ConnectionState::Disconnected {
             reason: DisconnectionReason::DisconnectedByClient,
         };
@@ -176,6 +289,7 @@ impl RemoteConnection {
         for (&channel_id, send_channel) in self.send_channels.iter() {
             if let Some(error) = send_channel.error() {
                 let reason = DisconnectionReason::SendChannelError { channel_id, error };
+                trace_event!(warn, "connection disconnected", reason = reason);
                 self.state = ConnectionState::Disconnected { reason };
                 return Err(RechannelError::ClientDisconnected(reason));
             }
@@ -184,6 +298,7 @@ impl RemoteConnection {
         for (&channel_id, receive_channel) in self.receive_channels.iter() {
             if let Some(error) = receive_channel.error() {
                 let reason = DisconnectionReason::ReceiveChannelError { channel_id, error };
+                trace_event!(warn, "connection disconnected", reason = reason);
                 self.state = ConnectionState::Disconnected { reason };
                 return Err(RechannelError::ClientDisconnected(reason));
             }
@@ -191,11 +306,12 @@ impl RemoteConnection {
 
         for ack in self.acks.drain(..) {
             for channel in self.send_channels.values_mut() {
-                channel.process_ack(ack);
+                channel.process_ack(ack, self.current_time);
             }
         }
 
         self.update_packet_loss();
+        self.update_bandwidth();
 
         Ok(())
     }
@@ -213,7 +329,13 @@ impl RemoteConnection {
                 ack_data,
                 channels_packet_data,
             } => {
-                self.received_buffer.insert(sequence, ());
+                trace_event!(
+                    trace,
+                    "received packet",
+                    sequence = sequence,
+                    num_channels = channels_packet_data.len()
+                );
+                self.add_received_packet(sequence);
                 self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
                 channels_packet_data
             }
@@ -223,27 +345,39 @@ impl RemoteConnection {
                 fragment_data,
             } => {
                 self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
-
-                let packet = self.reassembly_buffer.handle_fragment(
-                    sequence,
-                    fragment_data,
-                    self.config.max_packet_size,
-                    &self.config.fragment_config,
-                )?;
+                trace_event!(
+                    trace,
+                    "received fragment",
+                    sequence = sequence,
+                    fragment_id = fragment_data.fragment_id,
+                    num_fragments = fragment_data.num_fragments
+                );
+                self.packet_stats.fragments_received += 1;
+
+                let packet = self
+                    .reassembly_buffer
+                    .handle_fragment(sequence, fragment_data, self.config.max_packet_size, &self.config.fragment_config)
+                    .inspect_err(|e| {
+                        trace_event!(debug, "failed to process fragment", sequence = sequence, error = e);
+                        self.packet_stats.fragments_dropped += 1;
+                    })?;
                 match packet {
                     None => return Ok(()),
                     Some(packet) => {
                         // Only consider the packet received when the fragment is completed
-                        self.received_buffer.insert(sequence, ());
+                        self.add_received_packet(sequence);
+                        self.packet_stats.fragmented_packets_received += 1;
                         packet
                     }
                 }
             }
             Packet::Heartbeat { ack_data } => {
+                self.packet_stats.heartbeats_received += 1;
                 self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
                 return Ok(());
             }
             Packet::Disconnect { reason } => {
+                trace_event!(info, "connection disconnected by the remote", reason = reason);
                 self.state = ConnectionState::Disconnected { reason };
                 return Ok(());
             }
@@ -254,11 +388,15 @@ impl RemoteConnection {
                 Some(c) => c,
                 None => {
                     let reason = DisconnectionReason::InvalidChannelId(channel_packet_data.channel_id);
+                    trace_event!(warn, "connection disconnected", reason = reason);
                     self.state = ConnectionState::Disconnected { reason };
                     return Err(RechannelError::ClientDisconnected(reason));
                 }
             };
 
+            if let Some(traffic) = self.channels_traffic.get_mut(&channel_packet_data.channel_id) {
+                traffic.add_received(channel_packet_data.messages.iter().map(|m| m.len() as u64).sum());
+            }
             receive_channel.process_messages(channel_packet_data.messages);
         }
 
@@ -278,6 +416,9 @@ impl RemoteConnection {
         for send_channel in self.send_channels.values_mut() {
             if let Some(channel_packet_data) = send_channel.get_messages_to_send(available_bytes, sequence, self.current_time) {
                 available_bytes -= bincode::options().serialized_size(&channel_packet_data)?;
+                if let Some(traffic) = self.channels_traffic.get_mut(&channel_packet_data.channel_id) {
+                    traffic.add_sent(channel_packet_data.messages.iter().map(|m| m.len() as u64).sum());
+                }
                 channels_packet_data.push(channel_packet_data)
             }
         }
@@ -290,7 +431,10 @@ impl RemoteConnection {
             let sent_packet = SentPacket::new(self.current_time);
             self.sent_buffer.insert(sequence, sent_packet);
 
+            self.packet_stats.packets_sent += 1;
+
             let packets: Vec<Payload> = if packet_size > self.config.fragment_config.fragment_above {
+                self.packet_stats.fragmented_packets_sent += 1;
                 build_fragments(channels_packet_data, sequence, ack_data, &self.config.fragment_config)?
             } else {
                 let packet = Packet::Normal {
@@ -301,6 +445,13 @@ impl RemoteConnection {
                 let packet = bincode::options().serialize(&packet)?;
                 vec![packet]
             };
+            trace_event!(
+                trace,
+                "sending packet",
+                sequence = sequence,
+                len = packet_size,
+                num_fragments = packets.len()
+            );
 
             self.heartbeat_timer.reset(self.current_time);
             return Ok(packets);
@@ -308,6 +459,8 @@ impl RemoteConnection {
             let ack_data = self.received_buffer.ack_data();
             let packet = Packet::Heartbeat { ack_data };
             let packet = bincode::options().serialize(&packet)?;
+            trace_event!(trace, "sending heartbeat");
+            self.packet_stats.heartbeats_sent += 1;
 
             self.heartbeat_timer.reset(self.current_time);
             return Ok(vec![packet]);
@@ -325,6 +478,7 @@ impl RemoteConnection {
                     if !sent_packet.ack {
                         self.acks.push(ack_sequence);
                         sent_packet.ack = true;
+                        self.packet_stats.packets_acked += 1;
 
                         // Update RTT
                         let rtt = (self.current_time - sent_packet.time).as_secs_f32() * 1000.;
@@ -339,6 +493,46 @@ impl RemoteConnection {
             }
             ack_bits >>= 1;
         }
+
+        self.update_lost_packets(ack);
+    }
+
+    // The ack bits cover the last 32 packets, older packets without ack will never be acked.
+    fn update_lost_packets(&mut self, ack: u16) {
+        let oldest_ackable = ack.wrapping_sub(31);
+        while sequence_less_than(self.lost_check_sequence, oldest_ackable) && sequence_less_than(self.lost_check_sequence, self.sequence) {
+            if let Some(sent_packet) = self.sent_buffer.get(self.lost_check_sequence) {
+                if !sent_packet.ack {
+                    self.packet_stats.packets_lost += 1;
+                }
+            }
+            self.lost_check_sequence = self.lost_check_sequence.wrapping_add(1);
+        }
+    }
+
+    fn add_received_packet(&mut self, sequence: u16) {
+        if self.received_buffer.exists(sequence) {
+            self.packet_stats.duplicate_packets += 1;
+            return;
+        }
+
+        if sequence_less_than(sequence, self.received_buffer.sequence().wrapping_sub(1)) {
+            self.packet_stats.out_of_order_packets += 1;
+        }
+        self.packet_stats.packets_received += 1;
+        self.received_buffer.insert(sequence, ());
+    }
+
+    fn update_bandwidth(&mut self) {
+        let elapsed = self.current_time.saturating_sub(self.bandwidth_window_start);
+        if elapsed < BANDWIDTH_WINDOW {
+            return;
+        }
+
+        for traffic in self.channels_traffic.values_mut() {
+            traffic.update_bandwidth(elapsed);
+        }
+        self.bandwidth_window_start = self.current_time;
     }
 
     fn update_packet_loss(&mut self) {
@@ -436,5 +630,103 @@ mod tests {
 
         let received_message = connection.receive_message(0).unwrap();
         assert_eq!(message, received_message);
+
+        let stats = connection.packet_stats();
+        assert_eq!(stats.packets_sent, 1);
+        assert_eq!(stats.fragmented_packets_sent, 1);
+        assert_eq!(stats.fragments_received, packets.len() as u64);
+        assert_eq!(stats.fragmented_packets_received, 1);
+        assert_eq!(stats.packets_received, 1);
+    }
+
+    #[test]
+    fn packet_stats() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        let mut packets = vec![];
+        for _ in 0..40 {
+            connection.send_message(DefaultChannel::Unreliable, vec![0; 10]);
+            packets.extend(connection.get_packets_to_send().unwrap());
+        }
+        assert_eq!(packets.len(), 40);
+
+        // Only the odd packets arrive, the packet 5 after the packet 7 and the packet 7 twice
+        let send_heartbeat = |remote_connection: &mut RemoteConnection, connection: &mut RemoteConnection| {
+            remote_connection.advance_time(Duration::from_secs(1));
+            let heartbeat = remote_connection.get_packets_to_send().unwrap();
+            connection.process_packet(&heartbeat[0]).unwrap();
+        };
+        for sequence in [1, 3, 7, 5] {
+            remote_connection.process_packet(&packets[sequence]).unwrap();
+        }
+        send_heartbeat(&mut remote_connection, &mut connection);
+        for sequence in (9..40).step_by(2) {
+            remote_connection.process_packet(&packets[sequence]).unwrap();
+        }
+        remote_connection.process_packet(&packets[7]).unwrap();
+        send_heartbeat(&mut remote_connection, &mut connection);
+
+        let remote_stats = remote_connection.packet_stats();
+        assert_eq!(remote_stats.packets_received, 20);
+        assert_eq!(remote_stats.out_of_order_packets, 1);
+        assert_eq!(remote_stats.duplicate_packets, 1);
+        assert_eq!(remote_stats.heartbeats_sent, 2);
+
+        // The ack bits of the last heartbeat cover the last 32 packets (8 to 39),
+        // the older packets without ack (0, 2, 4 and 6) are lost
+        let stats = connection.packet_stats();
+        assert_eq!(stats.packets_sent, 40);
+        assert_eq!(stats.heartbeats_received, 2);
+        assert_eq!(stats.packets_acked, 20);
+        assert_eq!(stats.packets_lost, 4);
+    }
+
+    #[test]
+    fn channels_stats() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(DefaultChannel::Reliable, vec![1, 2, 3]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+
+        // Not acked, the message is resent
+        connection.advance_time(Duration::from_secs(1));
+        assert!(!connection.get_packets_to_send().unwrap().is_empty());
+
+        let stats = connection.channels_stats();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].channel_id, 0);
+        assert_eq!(stats[0].messages_resent, 1);
+        assert_eq!(
+            stats[1],
+            ChannelStats {
+                channel_id: 1,
+                ..Default::default()
+            }
+        );
+
+        let remote_stats = remote_connection.channels_stats();
+        assert!(remote_stats[0].bytes_received > 0);
+        assert_eq!(stats[0].bytes_sent, remote_stats[0].bytes_received * 2);
+        assert_eq!(stats[0].messages_queued, 1);
+        assert_eq!(stats[0].messages_sent, 1);
+        assert_eq!(stats[0].queue_depth, 1);
+
+        remote_connection.advance_time(Duration::from_secs(1));
+        for packet in remote_connection.get_packets_to_send().unwrap() {
+            connection.process_packet(&packet).unwrap();
+        }
+        connection.update().unwrap();
+        assert!(remote_connection.receive_message(DefaultChannel::Reliable).is_some());
+
+        let stats = connection.channels_stats();
+        assert_eq!(stats[0].messages_acked, 1);
+        assert_eq!(stats[0].queue_depth, 0);
+        assert_eq!(stats[0].average_ack_time, Duration::from_secs(1));
+        assert_eq!(stats[0].sent_bytes_per_second, stats[0].bytes_sent as f32);
+        assert_eq!(remote_connection.channels_stats()[0].messages_received, 1);
     }
 }
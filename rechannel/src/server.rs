@@ -1,3 +1,4 @@
+use crate::channel::ChannelStats;
 use crate::error::{DisconnectionReason, RechannelError};
 use crate::packet::Payload;
 use crate::remote_connection::{ConnectionConfig, RemoteConnection};
@@ -13,7 +14,7 @@ pub struct RechannelServer<C: ClientId> {
     current_time: Duration,
     connections: HashMap<C, RemoteConnection>,
     connection_config: ConnectionConfig,
-    disconnections: Vec<(C, DisconnectionReason)>,
+    disconnections: Vec<(C, DisconnectionReason, RemoteConnection)>,
 }
 
 impl<C: ClientId> RechannelServer<C> {
@@ -46,9 +47,18 @@ impl<C: ClientId> RechannelServer<C> {
     }
 
     pub fn disconnected_client(&mut self) -> Option<(C, DisconnectionReason)> {
+        self.disconnections.pop().map(|(connection_id, reason, _)| (connection_id, reason))
+    }
+
+    /// Returns a disconnected client with its connection, to read its last stats.
+    pub fn disconnected_connection(&mut self) -> Option<(C, DisconnectionReason, RemoteConnection)> {
         self.disconnections.pop()
     }
 
+    pub fn connection(&self, connection_id: C) -> Option<&RemoteConnection> {
+        self.connections.get(&connection_id)
+    }
+
     pub fn client_rtt(&self, connection_id: C) -> f32 {
         match self.connections.get(&connection_id) {
             Some(connection) => connection.rtt(),
@@ -63,15 +73,30 @@ impl<C: ClientId> RechannelServer<C> {
         }
     }
 
+    /// Returns the traffic counters of the client channels, empty if the client is not connected.
+    pub fn client_channels_stats(&self, connection_id: C) -> Vec<ChannelStats> {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.channels_stats(),
+            None => vec![],
+        }
+    }
+
+    pub fn client_fragment_errors(&self, connection_id: C) -> u64 {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.num_fragment_errors(),
+            None => 0,
+        }
+    }
+
     /// Similar to disconnect but does not emit an event
     pub fn remove_connection(&mut self, connection_id: &C) {
         self.connections.remove(connection_id);
     }
 
     pub fn disconnect(&mut self, connection_id: &C) {
-        if self.connections.remove(connection_id).is_some() {
+        if let Some(connection) = self.connections.remove(connection_id) {
             self.disconnections
-                .push((*connection_id, DisconnectionReason::DisconnectedByServer));
+                .push((*connection_id, DisconnectionReason::DisconnectedByServer, connection));
         }
     }
 
@@ -131,14 +156,18 @@ impl<C: ClientId> RechannelServer<C> {
     }
 
     pub fn update_connections(&mut self, duration: Duration) {
+        let mut disconnected = vec![];
         for (&connection_id, connection) in self.connections.iter_mut() {
             connection.advance_time(duration);
             if connection.update().is_err() {
-                let reason = connection.disconnected().unwrap();
-                self.disconnections.push((connection_id, reason));
+                disconnected.push(connection_id);
             }
         }
-        self.connections.retain(|_, c| c.is_connected());
+        for connection_id in disconnected {
+            let connection = self.connections.remove(&connection_id).unwrap();
+            let reason = connection.disconnected().unwrap();
+            self.disconnections.push((connection_id, reason, connection));
+        }
     }
 
     pub fn get_packets_to_send(&mut self, connection_id: &C) -> Result<Vec<Payload>, RechannelError> {
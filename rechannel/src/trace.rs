@@ -0,0 +1,12 @@
+// Events are sent to `tracing` when the `tracing` feature is enabled, otherwise they are
+// compiled out. The fields are still borrowed without the feature, so they don't become unused.
+macro_rules! trace_event {
+    ($level:ident, $message:literal $(, $field:ident = $value:expr)* $(,)?) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($field = ?$value,)* $message);
+        #[cfg(not(feature = "tracing"))]
+        {
+            $(let _ = &$value;)*
+        }
+    }};
+}
@@ -7,36 +7,10 @@ use std::{
 };
 
 use renet::{
-    ClientAuthentication, DefaultChannel, RenetClient, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig, ServerEvent,
-    NETCODE_USER_DATA_BYTES,
+    from_user_data, to_user_data, ClientAuthentication, DefaultChannel, RenetClient, RenetConnectionConfig, RenetServer,
+    ServerAuthentication, ServerConfig, ServerEvent,
 };
 
-// Helper struct to pass an username in the user data
-struct Username(String);
-
-impl Username {
-    fn to_netcode_user_data(&self) -> [u8; NETCODE_USER_DATA_BYTES] {
-        let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
-        if self.0.len() > NETCODE_USER_DATA_BYTES - 8 {
-            panic!("Username is too big");
-        }
-        user_data[0..8].copy_from_slice(&(self.0.len() as u64).to_le_bytes());
-        user_data[8..self.0.len() + 8].copy_from_slice(self.0.as_bytes());
-
-        user_data
-    }
-
-    fn from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Self {
-        let mut buffer = [0u8; 8];
-        buffer.copy_from_slice(&user_data[0..8]);
-        let mut len = u64::from_le_bytes(buffer) as usize;
-        len = len.min(NETCODE_USER_DATA_BYTES - 8);
-        let data = user_data[8..len + 8].to_vec();
-        let username = String::from_utf8(data).unwrap();
-        Self(username)
-    }
-}
-
 fn main() {
     println!("Usage: server [SERVER_PORT] or client [SERVER_PORT] [USER_NAME]");
     let args: Vec<String> = std::env::args().collect();
@@ -45,7 +19,7 @@ fn main() {
     match exec_type.as_str() {
         "client" => {
             let server_addr: SocketAddr = format!("127.0.0.1:{}", args[2]).parse().unwrap();
-            let username = Username(args[3].clone());
+            let username = args[3].clone();
             client(server_addr, username);
         }
         "server" => {
@@ -80,14 +54,15 @@ fn server(addr: SocketAddr) {
         while let Some(event) = server.get_event() {
             match event {
                 ServerEvent::ClientConnected(id, user_data) => {
-                    let username = Username::from_user_data(&user_data);
-                    usernames.insert(id, username.0);
+                    let username: String = from_user_data(&user_data).unwrap();
+                    usernames.insert(id, username);
                     println!("Client {} connected.", id)
                 }
-                ServerEvent::ClientDisconnected(id) => {
-                    println!("Client {} disconnected", id);
-                    usernames.remove_entry(&id);
+                ServerEvent::ClientDisconnected { client_id, reason, .. } => {
+                    println!("Client {} disconnected: {}", client_id, reason);
+                    usernames.remove_entry(&client_id);
                 }
+                ServerEvent::ConnectionRequested { .. } => {}
             }
         }
 
@@ -110,15 +85,17 @@ fn server(addr: SocketAddr) {
     }
 }
 
-fn client(server_addr: SocketAddr, username: Username) {
+fn client(server_addr: SocketAddr, username: String) {
     let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
     let connection_config = RenetConnectionConfig::default();
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
     let client_id = current_time.as_millis() as u64;
     let authentication = ClientAuthentication::Unsecure {
-        server_addr,
+        server_addresses: vec![server_addr],
         client_id,
-        user_data: Some(username.to_netcode_user_data()),
+        user_data: Some(to_user_data(&username).unwrap()),
+        expire_seconds: 300,
+        timeout_seconds: 15,
         protocol_id: PROTOCOL_ID,
     };
     let mut client = RenetClient::new(current_time, socket, connection_config, authentication).unwrap();
@@ -0,0 +1,436 @@
+use std::{
+    convert::TryInto,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    path::Path,
+    time::Duration,
+};
+
+use renetcode::NETCODE_KEY_BYTES;
+
+use crate::{RenetConnectionConfig, RenetServer, ServerConfig};
+
+const CAPTURE_PREFIX: &[u8; 8] = b"RENETCAP";
+const CAPTURE_VERSION: u8 = 1;
+
+const RECORD_START: u8 = 0;
+const RECORD_CHALLENGE_KEY: u8 = 1;
+const RECORD_UPDATE: u8 = 2;
+const RECORD_RECEIVED: u8 = 3;
+const RECORD_SENT: u8 = 4;
+
+const ADDRESS_IPV4: u8 = 4;
+const ADDRESS_IPV6: u8 = 6;
+
+/// A record in a packet capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureRecord {
+    /// The current time when the capture started.
+    Start { current_time: Duration },
+    /// The key used by the server to encrypt the challenge tokens, needed to replay the handshakes.
+    ChallengeKey([u8; NETCODE_KEY_BYTES]),
+    /// The duration passed to the update.
+    Update { duration: Duration },
+    /// A datagram received from the address.
+    Received {
+        current_time: Duration,
+        addr: SocketAddr,
+        payload: Vec<u8>,
+    },
+    /// A datagram sent to the address.
+    Sent {
+        current_time: Duration,
+        addr: SocketAddr,
+        payload: Vec<u8>,
+    },
+}
+
+/// Writes every datagram received and sent, and the update durations, to a capture.
+/// Set it with [RenetServer::set_recorder][crate::RenetServer::set_recorder] or
+/// [RenetClient::set_recorder][crate::RenetClient::set_recorder].
+///
+/// Server captures contain the challenge key, and can be decrypted with the private key.
+/// They should be kept private like the private key.
+pub struct PacketRecorder {
+    writer: Box<dyn Write + Send + Sync>,
+}
+
+impl fmt::Debug for PacketRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketRecorder").finish_non_exhaustive()
+    }
+}
+
+impl PacketRecorder {
+    /// Creates a recorder that writes the capture to the writer.
+    pub fn new<W: Write + Send + Sync + 'static>(mut writer: W) -> Result<Self, io::Error> {
+        writer.write_all(CAPTURE_PREFIX)?;
+        writer.write_all(&[CAPTURE_VERSION])?;
+
+        Ok(Self { writer: Box::new(writer) })
+    }
+
+    /// Creates a recorder that writes the capture to a new file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Flushes the records written.
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.writer.flush()
+    }
+
+    pub(crate) fn record(&mut self, record: &CaptureRecord) {
+        if let Err(e) = record.write(&mut self.writer) {
+            log::error!("failed to write capture record: {}", e);
+        }
+    }
+
+    pub(crate) fn record_received(&mut self, current_time: Duration, addr: SocketAddr, payload: &[u8]) {
+        self.record_packet(RECORD_RECEIVED, current_time, addr, payload);
+    }
+
+    pub(crate) fn record_sent(&mut self, current_time: Duration, addr: SocketAddr, payload: &[u8]) {
+        self.record_packet(RECORD_SENT, current_time, addr, payload);
+    }
+
+    // Avoids copying the payload to a record
+    fn record_packet(&mut self, record_type: u8, current_time: Duration, addr: SocketAddr, payload: &[u8]) {
+        if let Err(e) = write_packet(&mut self.writer, record_type, current_time, addr, payload) {
+            log::error!("failed to write capture record: {}", e);
+        }
+    }
+}
+
+/// Reads the records from a capture.
+pub struct CaptureReader<R> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, io::Error> {
+        let mut header = [0u8; 9];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != CAPTURE_PREFIX || header[8] != CAPTURE_VERSION {
+            return Err(invalid_data("not a renet capture or unsupported version"));
+        }
+
+        Ok(Self { reader })
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CaptureRecord, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record_type = [0u8; 1];
+        match self.reader.read(&mut record_type) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(e) => return Some(Err(e)),
+        }
+
+        Some(CaptureRecord::read(record_type[0], &mut self.reader))
+    }
+}
+
+impl CaptureRecord {
+    fn write(&self, writer: &mut impl Write) -> Result<(), io::Error> {
+        match self {
+            CaptureRecord::Start { current_time } => {
+                writer.write_all(&[RECORD_START])?;
+                write_duration(writer, *current_time)
+            }
+            CaptureRecord::ChallengeKey(key) => {
+                writer.write_all(&[RECORD_CHALLENGE_KEY])?;
+                writer.write_all(key)
+            }
+            CaptureRecord::Update { duration } => {
+                writer.write_all(&[RECORD_UPDATE])?;
+                write_duration(writer, *duration)
+            }
+            CaptureRecord::Received {
+                current_time,
+                addr,
+                payload,
+            } => write_packet(writer, RECORD_RECEIVED, *current_time, *addr, payload),
+            CaptureRecord::Sent {
+                current_time,
+                addr,
+                payload,
+            } => write_packet(writer, RECORD_SENT, *current_time, *addr, payload),
+        }
+    }
+
+    fn read(record_type: u8, reader: &mut impl Read) -> Result<Self, io::Error> {
+        let record = match record_type {
+            RECORD_START => CaptureRecord::Start {
+                current_time: read_duration(reader)?,
+            },
+            RECORD_CHALLENGE_KEY => {
+                let mut key = [0u8; NETCODE_KEY_BYTES];
+                reader.read_exact(&mut key)?;
+                CaptureRecord::ChallengeKey(key)
+            }
+            RECORD_UPDATE => CaptureRecord::Update {
+                duration: read_duration(reader)?,
+            },
+            RECORD_RECEIVED | RECORD_SENT => {
+                let current_time = read_duration(reader)?;
+                let addr = read_addr(reader)?;
+                let mut len = [0u8; 2];
+                reader.read_exact(&mut len)?;
+                let mut payload = vec![0u8; u16::from_le_bytes(len) as usize];
+                reader.read_exact(&mut payload)?;
+                if record_type == RECORD_RECEIVED {
+                    CaptureRecord::Received {
+                        current_time,
+                        addr,
+                        payload,
+                    }
+                } else {
+                    CaptureRecord::Sent {
+                        current_time,
+                        addr,
+                        payload,
+                    }
+                }
+            }
+            _ => return Err(invalid_data("invalid capture record type")),
+        };
+
+        Ok(record)
+    }
+}
+
+/// Replays a server capture in a new [RenetServer], using the recorded update durations and
+/// received datagrams. The replayed server doesn't send any packets.
+///
+/// The capture must have been recorded from the start of the server, and the server config must
+/// be the same, with the same private key. Calls that change the server state between the updates,
+/// like disconnecting clients, are not recorded and should be repeated in the replay.
+pub struct ServerReplay<R> {
+    reader: CaptureReader<R>,
+    server: RenetServer,
+    next_record: Option<CaptureRecord>,
+}
+
+impl<R: Read> ServerReplay<R> {
+    pub fn new(capture: R, server_config: ServerConfig, connection_config: RenetConnectionConfig) -> Result<Self, io::Error> {
+        let mut reader = CaptureReader::new(capture)?;
+        let current_time = match reader.next().transpose()? {
+            Some(CaptureRecord::Start { current_time }) => current_time,
+            _ => return Err(invalid_data("capture does not start with a start record")),
+        };
+
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let mut server = RenetServer::new(current_time, server_config, connection_config, socket)?;
+        server.set_replaying();
+
+        Ok(Self {
+            reader,
+            server,
+            next_record: None,
+        })
+    }
+
+    /// Returns the replayed server, to read its events and messages between the updates.
+    pub fn server(&mut self) -> &mut RenetServer {
+        &mut self.server
+    }
+
+    /// Replays the next update and the datagrams received in it.
+    /// Returns false when the capture has ended.
+    pub fn update(&mut self) -> Result<bool, io::Error> {
+        let duration = loop {
+            match self.next_record()? {
+                None => return Ok(false),
+                Some(CaptureRecord::Update { duration }) => break duration,
+                Some(CaptureRecord::ChallengeKey(key)) => self.server.set_challenge_key(key),
+                Some(_) => {}
+            }
+        };
+
+        self.server.advance_time(duration);
+        loop {
+            match self.next_record()? {
+                Some(CaptureRecord::Received { addr, mut payload, .. }) => self.server.process_packet_from(addr, &mut payload)?,
+                Some(CaptureRecord::Sent { .. }) => {}
+                record => {
+                    self.next_record = record;
+                    break;
+                }
+            }
+        }
+        self.server.update_connections()?;
+
+        Ok(true)
+    }
+
+    fn next_record(&mut self) -> Result<Option<CaptureRecord>, io::Error> {
+        match self.next_record.take() {
+            Some(record) => Ok(Some(record)),
+            None => self.reader.next().transpose(),
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_packet(
+    writer: &mut impl Write,
+    record_type: u8,
+    current_time: Duration,
+    addr: SocketAddr,
+    payload: &[u8],
+) -> Result<(), io::Error> {
+    let len: u16 = payload
+        .len()
+        .try_into()
+        .map_err(|_| invalid_data("packet too large for the capture"))?;
+    writer.write_all(&[record_type])?;
+    write_duration(writer, current_time)?;
+    write_addr(writer, addr)?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)
+}
+
+fn write_duration(writer: &mut impl Write, duration: Duration) -> Result<(), io::Error> {
+    writer.write_all(&(duration.as_nanos() as u64).to_le_bytes())
+}
+
+fn read_duration(reader: &mut impl Read) -> Result<Duration, io::Error> {
+    let mut nanos = [0u8; 8];
+    reader.read_exact(&mut nanos)?;
+    Ok(Duration::from_nanos(u64::from_le_bytes(nanos)))
+}
+
+fn write_addr(writer: &mut impl Write, addr: SocketAddr) -> Result<(), io::Error> {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            writer.write_all(&[ADDRESS_IPV4])?;
+            writer.write_all(&ip.octets())?;
+        }
+        IpAddr::V6(ip) => {
+            writer.write_all(&[ADDRESS_IPV6])?;
+            writer.write_all(&ip.octets())?;
+        }
+    }
+    writer.write_all(&addr.port().to_le_bytes())
+}
+
+fn read_addr(reader: &mut impl Read) -> Result<SocketAddr, io::Error> {
+    let mut address_type = [0u8; 1];
+    reader.read_exact(&mut address_type)?;
+    let ip: IpAddr = match address_type[0] {
+        ADDRESS_IPV4 => {
+            let mut octets = [0u8; 4];
+            reader.read_exact(&mut octets)?;
+            Ipv4Addr::from(octets).into()
+        }
+        ADDRESS_IPV6 => {
+            let mut octets = [0u8; 16];
+            reader.read_exact(&mut octets)?;
+            Ipv6Addr::from(octets).into()
+        }
+        _ => return Err(invalid_data("invalid address type")),
+    };
+    let mut port = [0u8; 2];
+    reader.read_exact(&mut port)?;
+
+    Ok(SocketAddr::new(ip, u16::from_le_bytes(port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{ClientAuthentication, DefaultChannel, RenetClient, ServerAuthentication, ServerEvent};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_and_replay_server() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        let start_time = Duration::from_secs(1_000);
+        let mut server = RenetServer::new(start_time, server_config.clone(), RenetConnectionConfig::default(), socket).unwrap();
+        let capture = SharedBuffer::default();
+        server.set_recorder(Some(PacketRecorder::new(capture.clone()).unwrap()));
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 7,
+            client_id: 3,
+            server_addresses: vec![server_addr],
+            user_data: None,
+            expire_seconds: 300,
+            timeout_seconds: 15,
+        };
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = RenetClient::new(start_time, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+
+        let mut events = vec![];
+        let mut messages = vec![];
+        for i in 0..100u8 {
+            client.update(Duration::from_millis(10)).unwrap();
+            server.update(Duration::from_millis(10)).unwrap();
+            if client.is_connected() && i % 10 == 0 {
+                client.send_message(DefaultChannel::Reliable, vec![i]);
+            }
+            while let Some(event) = server.get_event() {
+                events.push(event);
+            }
+            while let Some(message) = server.receive_message(3, DefaultChannel::Reliable) {
+                messages.push(message);
+            }
+            client.send_packets().unwrap();
+            server.send_packets().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(matches!(events[0], ServerEvent::ClientConnected(3, _)));
+        assert!(!messages.is_empty());
+
+        let records: Vec<CaptureRecord> = CaptureReader::new(&capture.0.lock().unwrap()[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records[0], CaptureRecord::Start { current_time: start_time });
+        assert!(matches!(records[1], CaptureRecord::ChallengeKey(_)));
+        assert!(records.iter().any(|record| matches!(record, CaptureRecord::Sent { .. })));
+
+        let capture = capture.0.lock().unwrap().clone();
+        let mut replay = ServerReplay::new(&capture[..], server_config, RenetConnectionConfig::default()).unwrap();
+        let mut replayed_events = vec![];
+        let mut replayed_messages = vec![];
+        while replay.update().unwrap() {
+            let server = replay.server();
+            while let Some(event) = server.get_event() {
+                replayed_events.push(event);
+            }
+            while let Some(message) = server.receive_message(3, DefaultChannel::Reliable) {
+                replayed_messages.push(message);
+            }
+            server.send_packets().unwrap();
+        }
+
+        assert_eq!(format!("{:?}", events), format!("{:?}", replayed_events));
+        assert_eq!(messages, replayed_messages);
+    }
+}
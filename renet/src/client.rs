@@ -1,13 +1,18 @@
 use crate::{
+    capture::{CaptureRecord, PacketRecorder},
     error::{DisconnectionReason, RenetError},
     network_info::{ClientPacketInfo, NetworkInfo, PacketInfo},
+    reconnect::{Reconnect, ReconnectPolicy},
     RenetConnectionConfig, NUM_DISCONNECT_PACKETS_TO_SEND,
 };
 
-use log::debug;
 use rechannel::{error::RechannelError, remote_connection::RemoteConnection, Bytes};
-use renetcode::{ConnectToken, NetcodeClient, NetcodeError, NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
+use renetcode::{
+    ConnectToken, DisconnectReason as NetcodeDisconnectReason, NetcodeClient, NetcodeError, NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES,
+    NETCODE_USER_DATA_BYTES,
+};
 
+use std::collections::VecDeque;
 use std::net::UdpSocket;
 use std::time::Duration;
 use std::{io, net::SocketAddr};
@@ -20,16 +25,43 @@ pub enum ClientAuthentication {
     /// See also [ServerAuthentication::Secure][crate::ServerAuthentication::Secure]
     Secure { connect_token: ConnectToken },
     /// Establishes an unsafe connection with the server, useful for testing and prototyping.
+    /// The server addresses are tried in order, up to 32 addresses.
     ///
     /// See also [ServerAuthentication::Unsecure][crate::ServerAuthentication::Unsecure]
     Unsecure {
         protocol_id: u64,
         client_id: u64,
-        server_addr: SocketAddr,
+        server_addresses: Vec<SocketAddr>,
         user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>,
+        /// Seconds until the generated connect token expires.
+        expire_seconds: u64,
+        /// Seconds without receiving packets before the connection times out, negative values disable the timeout.
+        timeout_seconds: i32,
     },
 }
 
+/// Events that can occur in the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// The client started connecting to the server, when created or reconnecting.
+    Connecting,
+    /// The client connected to the server, with the index of its slot in the server.
+    Connected { client_index: u32 },
+    /// The client failed to connect to the server.
+    ConnectionFailed(NetcodeDisconnectReason),
+    /// The client lost the connection with the server.
+    Disconnected(DisconnectionReason),
+    /// The client will try to connect again after the delay, see [RenetClient::set_reconnect_policy].
+    Reconnecting { attempt: u32, delay: Duration },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
 /// A client that establishes an authenticated connection with a server.
 /// Can send/receive encrypted messages from/to the server.
 pub struct RenetClient {
@@ -39,6 +71,12 @@ pub struct RenetClient {
     reliable_connection: RemoteConnection,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
     client_packet_info: ClientPacketInfo,
+    connection_config: RenetConnectionConfig,
+    probe_servers: bool,
+    reconnect: Option<Reconnect>,
+    recorder: Option<PacketRecorder>,
+    connection_state: ConnectionState,
+    events: VecDeque<ClientEvent>,
 }
 
 impl RenetClient {
@@ -47,29 +85,58 @@ impl RenetClient {
         socket: UdpSocket,
         config: RenetConnectionConfig,
         authentication: ClientAuthentication,
+    ) -> Result<Self, RenetError> {
+        Self::new_internal(current_time, socket, config, authentication, false)
+    }
+
+    /// Creates a client that probes all the server addresses at the same time and connects to the
+    /// one with the lowest round trip time, instead of trying them in order.
+    pub fn new_with_server_probing(
+        current_time: Duration,
+        socket: UdpSocket,
+        config: RenetConnectionConfig,
+        authentication: ClientAuthentication,
+    ) -> Result<Self, RenetError> {
+        Self::new_internal(current_time, socket, config, authentication, true)
+    }
+
+    fn new_internal(
+        current_time: Duration,
+        socket: UdpSocket,
+        config: RenetConnectionConfig,
+        authentication: ClientAuthentication,
+        probe_servers: bool,
     ) -> Result<Self, RenetError> {
         socket.set_nonblocking(true)?;
         let reliable_connection = RemoteConnection::new(current_time, config.to_connection_config());
         let connect_token: ConnectToken = match authentication {
             ClientAuthentication::Unsecure {
-                server_addr,
+                server_addresses,
                 protocol_id,
                 client_id,
                 user_data,
-            } => ConnectToken::generate(
-                current_time,
-                protocol_id,
-                300,
-                client_id,
-                15,
-                vec![server_addr],
-                user_data.as_ref(),
-                &[0; NETCODE_KEY_BYTES],
-            )?,
+                expire_seconds,
+                timeout_seconds,
+            } => {
+                let mut builder = ConnectToken::builder()
+                    .protocol_id(protocol_id)
+                    .client_id(client_id)
+                    .server_addresses(server_addresses)
+                    .expire_seconds(expire_seconds)
+                    .timeout_seconds(timeout_seconds);
+                if let Some(user_data) = user_data {
+                    builder = builder.user_data(user_data);
+                }
+                builder.generate(current_time, &[0; NETCODE_KEY_BYTES])?
+            }
             ClientAuthentication::Secure { connect_token } => connect_token,
         };
 
-        let netcode_client = NetcodeClient::new(current_time, connect_token);
+        let netcode_client = if probe_servers {
+            NetcodeClient::new_with_server_probing(current_time, connect_token)
+        } else {
+            NetcodeClient::new(current_time, connect_token)
+        };
         let client_packet_info = ClientPacketInfo::new(config.bandwidth_smoothing_factor);
 
         Ok(Self {
@@ -79,6 +146,12 @@ impl RenetClient {
             reliable_connection,
             netcode_client,
             client_packet_info,
+            connection_config: config,
+            probe_servers,
+            reconnect: None,
+            recorder: None,
+            connection_state: ConnectionState::Connecting,
+            events: VecDeque::from([ClientEvent::Connecting]),
         })
     }
 
@@ -93,14 +166,69 @@ impl RenetClient {
             Default::default(),
             ClientAuthentication::Unsecure {
                 client_id: 0,
-                server_addr,
+                server_addresses: vec![server_addr],
                 user_data: None,
                 protocol_id: 0,
+                expire_seconds: 300,
+                timeout_seconds: 15,
             },
         )
         .unwrap()
     }
 
+    /// Enables the automatic reconnection. When the connection times out or the socket fails, the
+    /// client waits for the delay from the policy and connects again with a token from the token source.
+    /// Messages that were not delivered before the reconnection are lost.
+    ///
+    /// In unsecure mode, the token source can generate the tokens with [ConnectToken::builder].
+    pub fn set_reconnect_policy<F>(&mut self, policy: ReconnectPolicy, token_source: F)
+    where
+        F: FnMut(Duration) -> Option<ConnectToken> + Send + Sync + 'static,
+    {
+        self.reconnect = Some(Reconnect {
+            policy,
+            token_source: Box::new(token_source),
+            attempt: 0,
+            next_attempt_time: None,
+        });
+    }
+
+    /// Disables the automatic reconnection.
+    pub fn clear_reconnect_policy(&mut self) {
+        self.reconnect = None;
+    }
+
+    /// Sets the recorder that writes all the packets received and sent, and the update durations.
+    pub fn set_recorder(&mut self, recorder: Option<PacketRecorder>) {
+        self.recorder = recorder;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&CaptureRecord::Start {
+                current_time: self.current_time,
+            });
+        }
+    }
+
+    /// Removes the recorder, flushing the capture.
+    pub fn take_recorder(&mut self) -> Option<PacketRecorder> {
+        let mut recorder = self.recorder.take();
+        if let Some(recorder) = &mut recorder {
+            if let Err(e) = recorder.flush() {
+                log::error!("failed to flush the capture: {}", e);
+            }
+        }
+        recorder
+    }
+
+    /// Returns true while the client is waiting to reconnect.
+    pub fn is_reconnecting(&self) -> bool {
+        matches!(&self.reconnect, Some(reconnect) if reconnect.next_attempt_time.is_some())
+    }
+
+    /// Returns a client event if available.
+    pub fn get_event(&mut self) -> Option<ClientEvent> {
+        self.events.pop_front()
+    }
+
     pub fn client_id(&self) -> u64 {
         self.netcode_client.client_id()
     }
@@ -109,6 +237,23 @@ impl RenetClient {
         self.netcode_client.connected()
     }
 
+    /// Returns the server address the client is connected or trying to connect.
+    pub fn server_addr(&self) -> SocketAddr {
+        self.netcode_client.server_addr()
+    }
+
+    /// Returns the server addresses the client failed to connect to, in order, with the reason of
+    /// the failure. The client tries the next address from the connect token after a failure.
+    pub fn failed_server_addresses(&self) -> &[(SocketAddr, NetcodeDisconnectReason)] {
+        self.netcode_client.failed_server_addresses()
+    }
+
+    /// Returns the round trip times measured while probing the servers,
+    /// only available for clients created with [RenetClient::new_with_server_probing].
+    pub fn server_rtts(&self) -> Vec<(SocketAddr, Duration)> {
+        self.netcode_client.server_rtts()
+    }
+
     /// If the client is disconnected, returns the reason.
     pub fn disconnected(&self) -> Option<DisconnectionReason> {
         if let Some(reason) = self.reliable_connection.disconnected() {
@@ -124,16 +269,27 @@ impl RenetClient {
 
     /// Disconnect the client from the server.
     pub fn disconnect(&mut self) {
+        if let Some(reconnect) = &mut self.reconnect {
+            reconnect.next_attempt_time = None;
+        }
         match self.netcode_client.disconnect() {
             Ok((addr, payload)) => {
                 for _ in 0..NUM_DISCONNECT_PACKETS_TO_SEND {
-                    if let Err(e) = send_to(self.current_time, &self.socket, &mut self.client_packet_info, payload, addr) {
+                    if let Err(e) = send_to(
+                        self.current_time,
+                        &self.socket,
+                        &mut self.recorder,
+                        &mut self.client_packet_info,
+                        payload,
+                        addr,
+                    ) {
                         log::error!("failed to send disconnect packet to server: {}", e);
                     }
                 }
             }
             Err(e) => log::error!("failed to generate disconnect packet: {}", e),
         }
+        self.update_connection_state();
     }
 
     /// Receive a message from the server over a channel.
@@ -152,12 +308,7 @@ impl RenetClient {
     }
 
     pub fn network_info(&self) -> NetworkInfo {
-        NetworkInfo {
-            sent_kbps: self.client_packet_info.sent_kbps,
-            received_kbps: self.client_packet_info.received_kbps,
-            rtt: self.reliable_connection.rtt(),
-            packet_loss: self.reliable_connection.packet_loss(),
-        }
+        NetworkInfo::new(&self.reliable_connection, &self.client_packet_info)
     }
 
     /// Send packets to the server.
@@ -166,7 +317,14 @@ impl RenetClient {
             let packets = self.reliable_connection.get_packets_to_send()?;
             for packet in packets.into_iter() {
                 let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
-                send_to(self.current_time, &self.socket, &mut self.client_packet_info, payload, addr)?;
+                send_to(
+                    self.current_time,
+                    &self.socket,
+                    &mut self.recorder,
+                    &mut self.client_packet_info,
+                    payload,
+                    addr,
+                )?;
             }
         }
         Ok(())
@@ -174,6 +332,30 @@ impl RenetClient {
 
     /// Advances the client by duration, and receive packets from the network.
     pub fn update(&mut self, duration: Duration) -> Result<(), RenetError> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&CaptureRecord::Update { duration });
+        }
+
+        if let Some(next_attempt_time) = self.reconnect.as_ref().and_then(|reconnect| reconnect.next_attempt_time) {
+            self.current_time += duration;
+            if self.current_time >= next_attempt_time {
+                return self.reconnect();
+            }
+            return Ok(());
+        }
+
+        let result = self.update_connection(duration);
+        self.update_connection_state();
+        match result {
+            Err(e) if self.reconnect.is_some() && should_reconnect(&e) => {
+                log::info!("connection lost, reconnecting: {}", e);
+                self.schedule_reconnect()
+            }
+            result => result,
+        }
+    }
+
+    fn update_connection(&mut self, duration: Duration) -> Result<(), RenetError> {
         self.current_time += duration;
         self.reliable_connection.advance_time(duration);
         if let Some(reason) = self.netcode_client.disconnected() {
@@ -186,46 +368,250 @@ impl RenetClient {
         }
 
         loop {
-            let packet = match self.socket.recv_from(&mut self.buffer) {
-                Ok((len, addr)) => {
-                    if addr != self.netcode_client.server_addr() {
-                        debug!("Discarded packet from unknown server {:?}", addr);
-                        continue;
-                    }
-
-                    &mut self.buffer[..len]
-                }
+            let (addr, packet) = match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, addr)) => (addr, &mut self.buffer[..len]),
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
                 Err(e) => return Err(RenetError::IO(e)),
             };
 
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record_received(self.current_time, addr, packet);
+            }
             let packet_info = PacketInfo::new(self.current_time, packet.len());
             self.client_packet_info.add_packet_received(packet_info);
 
-            if let Some(payload) = self.netcode_client.process_packet(packet) {
+            // Packets from unknown servers are discarded by the netcode client
+            if let Some(payload) = self.netcode_client.process_packet_from(addr, packet) {
                 self.reliable_connection.process_packet(payload)?;
             }
         }
 
         self.reliable_connection.update()?;
         if let Some((packet, addr)) = self.netcode_client.update(duration) {
-            send_to(self.current_time, &self.socket, &mut self.client_packet_info, packet, addr)?;
+            send_to(
+                self.current_time,
+                &self.socket,
+                &mut self.recorder,
+                &mut self.client_packet_info,
+                packet,
+                addr,
+            )?;
         }
 
         self.client_packet_info.update_metrics();
 
         Ok(())
     }
+
+    fn update_connection_state(&mut self) {
+        let state = if self.netcode_client.connected() {
+            ConnectionState::Connected
+        } else if self.disconnected().is_some() {
+            ConnectionState::Disconnected
+        } else {
+            ConnectionState::Connecting
+        };
+        if state == self.connection_state {
+            return;
+        }
+
+        let event = match (self.connection_state, state, self.netcode_client.disconnected()) {
+            (_, ConnectionState::Connecting, _) => ClientEvent::Connecting,
+            (_, ConnectionState::Connected, _) => {
+                if let Some(reconnect) = &mut self.reconnect {
+                    reconnect.attempt = 0;
+                }
+                ClientEvent::Connected {
+                    client_index: self.netcode_client.client_index(),
+                }
+            }
+            (ConnectionState::Connecting, ConnectionState::Disconnected, Some(reason)) => ClientEvent::ConnectionFailed(reason),
+            (_, ConnectionState::Disconnected, _) => ClientEvent::Disconnected(self.disconnected().unwrap()),
+        };
+        self.connection_state = state;
+        self.events.push_back(event);
+    }
+
+    fn schedule_reconnect(&mut self) -> Result<(), RenetError> {
+        let reconnect = self.reconnect.as_mut().expect("reconnect is only scheduled with a policy");
+        reconnect.attempt += 1;
+        if let Some(max_attempts) = reconnect.policy.max_attempts {
+            if reconnect.attempt > max_attempts {
+                log::error!("failed to reconnect after {} attempts", max_attempts);
+                self.reconnect = None;
+                return Err(NetcodeError::NoMoreServers.into());
+            }
+        }
+
+        let delay = reconnect.policy.delay(reconnect.attempt);
+        reconnect.next_attempt_time = Some(self.current_time + delay);
+        self.events.push_back(ClientEvent::Reconnecting {
+            attempt: reconnect.attempt,
+            delay,
+        });
+
+        Ok(())
+    }
+
+    fn reconnect(&mut self) -> Result<(), RenetError> {
+        let reconnect = self.reconnect.as_mut().expect("reconnect is only scheduled with a policy");
+        reconnect.next_attempt_time = None;
+        let connect_token = match (reconnect.token_source)(self.current_time) {
+            Some(connect_token) => connect_token,
+            None => return self.schedule_reconnect(),
+        };
+
+        self.netcode_client = if self.probe_servers {
+            NetcodeClient::new_with_server_probing(self.current_time, connect_token)
+        } else {
+            NetcodeClient::new(self.current_time, connect_token)
+        };
+        self.reliable_connection = RemoteConnection::new(self.current_time, self.connection_config.to_connection_config());
+        self.update_connection_state();
+
+        Ok(())
+    }
+}
+
+/// Timeouts, expired connect tokens and socket errors can be recovered by connecting again.
+fn should_reconnect(error: &RenetError) -> bool {
+    match error {
+        RenetError::IO(_) => true,
+        RenetError::Netcode(NetcodeError::Disconnected(reason)) => matches!(
+            reason,
+            NetcodeDisconnectReason::ConnectTokenExpired
+                | NetcodeDisconnectReason::ConnectionTimedOut
+                | NetcodeDisconnectReason::ConnectionRequestTimedOut
+                | NetcodeDisconnectReason::ConnectionResponseTimedOut
+        ),
+        _ => false,
+    }
 }
 
 fn send_to(
     current_time: Duration,
     socket: &UdpSocket,
+    recorder: &mut Option<PacketRecorder>,
     client_packet_info: &mut ClientPacketInfo,
     packet: &[u8],
     address: SocketAddr,
 ) -> Result<usize, std::io::Error> {
+    if let Some(recorder) = recorder {
+        recorder.record_sent(current_time, address, packet);
+    }
     let packet_info = PacketInfo::new(current_time, packet.len());
     client_packet_info.add_packet_sent(packet_info);
     socket.send_to(packet, address)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RenetServer, ServerAuthentication, ServerConfig, ServerEvent};
+
+    fn connect_token(server_addr: SocketAddr, current_time: Duration) -> ConnectToken {
+        ConnectToken::builder()
+            .protocol_id(7)
+            .client_id(1)
+            .timeout_seconds(1)
+            .server_address(server_addr)
+            .generate(current_time, &[0; NETCODE_KEY_BYTES])
+            .unwrap()
+    }
+
+    fn update(client: &mut RenetClient, server: &mut RenetServer, duration: Duration) {
+        client.update(duration).unwrap();
+        server.update(duration).unwrap();
+        client.send_packets().unwrap();
+        server.send_packets().unwrap();
+    }
+
+    #[test]
+    fn reconnect_after_timeout() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        let mut server = RenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap();
+
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let authentication = ClientAuthentication::Secure {
+            connect_token: connect_token(server_addr, Duration::ZERO),
+        };
+        let mut client = RenetClient::new(Duration::ZERO, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+        let policy = ReconnectPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        client.set_reconnect_policy(policy, move |current_time| Some(connect_token(server_addr, current_time)));
+
+        for _ in 0..100 {
+            update(&mut client, &mut server, Duration::from_millis(10));
+            if client.is_connected() {
+                break;
+            }
+        }
+        assert_eq!(client.get_event(), Some(ClientEvent::Connecting));
+        assert_eq!(client.get_event(), Some(ClientEvent::Connected { client_index: 0 }));
+
+        // The server stops responding
+        client.update(Duration::from_secs(2)).unwrap();
+        client.update(Duration::ZERO).unwrap();
+        assert!(client.is_reconnecting());
+        assert_eq!(
+            client.get_event(),
+            Some(ClientEvent::Disconnected(DisconnectionReason::Netcode(
+                NetcodeDisconnectReason::ConnectionTimedOut
+            )))
+        );
+        assert_eq!(
+            client.get_event(),
+            Some(ClientEvent::Reconnecting {
+                attempt: 1,
+                delay: Duration::from_secs(1)
+            })
+        );
+
+        client.update(Duration::from_secs(1)).unwrap();
+        assert!(!client.is_reconnecting());
+        server.update(Duration::from_secs(2)).unwrap();
+        for _ in 0..100 {
+            update(&mut client, &mut server, Duration::from_millis(10));
+            if client.is_connected() {
+                break;
+            }
+        }
+        assert_eq!(client.get_event(), Some(ClientEvent::Connecting));
+        assert_eq!(client.get_event(), Some(ClientEvent::Connected { client_index: 0 }));
+    }
+
+    #[test]
+    fn connection_failed_event() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let mut server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        server_config.require_connection_approval = true;
+        let mut server = RenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap();
+
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let authentication = ClientAuthentication::Secure {
+            connect_token: connect_token(server_addr, Duration::ZERO),
+        };
+        let mut client = RenetClient::new(Duration::ZERO, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+        assert_eq!(client.get_event(), Some(ClientEvent::Connecting));
+
+        for _ in 0..100 {
+            update(&mut client, &mut server, Duration::from_millis(10));
+            if let Some(ServerEvent::ConnectionRequested { client_id, .. }) = server.get_event() {
+                server.deny_connection(client_id);
+            }
+            if client.disconnected().is_some() {
+                break;
+            }
+        }
+        assert_eq!(
+            client.get_event(),
+            Some(ClientEvent::ConnectionFailed(NetcodeDisconnectReason::ConnectionDenied))
+        );
+        assert_eq!(client.get_event(), None);
+    }
+}
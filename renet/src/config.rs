@@ -4,6 +4,7 @@ use renetcode::NETCODE_MAX_PAYLOAD_BYTES;
 use std::time::Duration;
 
 /// Configuration for a renet connection and its channels.
+#[derive(Debug, Clone)]
 pub struct RenetConnectionConfig {
     /// The maximum size (bytes) that generated packets can have.
     pub max_packet_size: u64,
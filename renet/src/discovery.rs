@@ -0,0 +1,335 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use bincode::Options;
+use renetcode::generate_random_bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::RenetServer;
+
+const DISCOVERY_PREFIX: &[u8; 8] = b"RENETDSC";
+const DISCOVERY_MAX_PACKET_BYTES: usize = 512;
+/// Queries are padded to this size, bigger than any response, so the responder can't be used to
+/// amplify traffic sent with a spoofed source address. Shorter queries are ignored.
+const DISCOVERY_QUERY_BYTES: usize = 256;
+/// Minimum time between responses to the same source address.
+const DISCOVERY_RESPONSE_INTERVAL: Duration = Duration::from_millis(100);
+/// The maximum number of bytes of the server name sent in the discovery responses.
+pub const DISCOVERY_MAX_NAME_BYTES: usize = 128;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum DiscoveryPacket {
+    Query {
+        protocol_id: u64,
+        nonce: u64,
+    },
+    Response {
+        protocol_id: u64,
+        nonce: u64,
+        name: String,
+        addr: SocketAddr,
+        current_clients: u64,
+        max_clients: u64,
+    },
+}
+
+/// A server that answered a discovery query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredServer {
+    pub name: String,
+    pub protocol_id: u64,
+    /// Address to connect to the server.
+    pub addr: SocketAddr,
+    pub current_clients: usize,
+    pub max_clients: usize,
+}
+
+/// Answers the discovery queries from clients in the local network, see [discover].
+pub struct DiscoveryResponder {
+    socket: UdpSocket,
+    name: String,
+    buffer: [u8; DISCOVERY_MAX_PACKET_BYTES],
+    last_responses: HashMap<IpAddr, Instant>,
+}
+
+impl DiscoveryPacket {
+    fn encode(&self, buffer: &mut [u8]) -> Option<usize> {
+        buffer.get_mut(..DISCOVERY_PREFIX.len())?.copy_from_slice(DISCOVERY_PREFIX);
+        let mut writer = &mut buffer[DISCOVERY_PREFIX.len()..];
+        let available = writer.len();
+        options().serialize_into(&mut writer, self).ok()?;
+
+        Some(DISCOVERY_PREFIX.len() + available - writer.len())
+    }
+
+    fn decode(buffer: &[u8]) -> Option<Self> {
+        let payload = buffer.strip_prefix(DISCOVERY_PREFIX)?;
+        options().deserialize(payload).ok()
+    }
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_limit(DISCOVERY_MAX_PACKET_BYTES as u64)
+        .allow_trailing_bytes()
+}
+
+impl DiscoveryResponder {
+    /// Binds the responder to the port on all the IPv4 interfaces,
+    /// it answers the queries broadcasted or sent directly to the port.
+    ///
+    /// The socket is bound without address reuse, so only one responder in the machine can bind the
+    /// port. To run several servers in the same machine, create the sockets with `SO_REUSEADDR` and
+    /// `SO_REUSEPORT` (for example with the socket2 crate) and use [DiscoveryResponder::new].
+    pub fn bind(port: u16, name: impl Into<String>) -> Result<Self, io::Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        Self::new(socket, name)
+    }
+
+    /// Binds the responder to the port and joins the IPv4 multicast group,
+    /// it answers the queries sent to the group.
+    ///
+    /// Like [DiscoveryResponder::bind], the socket is bound without address reuse,
+    /// use [DiscoveryResponder::new_multicast] with a socket that allows it.
+    pub fn bind_multicast(group: Ipv4Addr, port: u16, name: impl Into<String>) -> Result<Self, io::Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        Self::new_multicast(socket, group, name)
+    }
+
+    /// Creates a responder with a socket already bound to the discovery port, and joins the
+    /// IPv4 multicast group with it.
+    pub fn new_multicast(socket: UdpSocket, group: Ipv4Addr, name: impl Into<String>) -> Result<Self, io::Error> {
+        socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+        Self::new(socket, name)
+    }
+
+    /// Creates a responder with a socket already bound to the discovery address, the socket can be
+    /// configured before, for example to allow other responders to bind the same port.
+    /// The name is truncated to [DISCOVERY_MAX_NAME_BYTES] bytes.
+    pub fn new(socket: UdpSocket, name: impl Into<String>) -> Result<Self, io::Error> {
+        socket.set_nonblocking(true)?;
+        let mut name = name.into();
+        if name.len() > DISCOVERY_MAX_NAME_BYTES {
+            let mut len = DISCOVERY_MAX_NAME_BYTES;
+            while !name.is_char_boundary(len) {
+                len -= 1;
+            }
+            name.truncate(len);
+        }
+
+        Ok(Self {
+            socket,
+            name,
+            buffer: [0; DISCOVERY_MAX_PACKET_BYTES],
+            last_responses: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.socket.local_addr()
+    }
+
+    /// Answers the queries received since the last update with the information of the server.
+    /// Queries with a protocol id different from the server, queries that are not padded and
+    /// queries from an address that was answered recently are ignored.
+    pub fn update(&mut self, server: &RenetServer) -> Result<(), io::Error> {
+        let now = Instant::now();
+        self.last_responses
+            .retain(|_, last_response| now.duration_since(*last_response) < DISCOVERY_RESPONSE_INTERVAL);
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(result) => result,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if len < DISCOVERY_QUERY_BYTES || self.last_responses.contains_key(&addr.ip()) {
+                continue;
+            }
+
+            let nonce = match DiscoveryPacket::decode(&self.buffer[..len]) {
+                Some(DiscoveryPacket::Query { protocol_id, nonce }) if protocol_id == server.protocol_id() => nonce,
+                _ => continue,
+            };
+            self.last_responses.insert(addr.ip(), now);
+
+            let response = DiscoveryPacket::Response {
+                protocol_id: server.protocol_id(),
+                nonce,
+                name: self.name.clone(),
+                addr: server.addr(),
+                current_clients: server.connected_clients() as u64,
+                max_clients: server.max_clients() as u64,
+            };
+            // The response is never bigger than the query
+            if let Some(len) = response.encode(&mut self.buffer[..DISCOVERY_QUERY_BYTES]) {
+                if let Err(e) = self.socket.send_to(&self.buffer[..len], addr) {
+                    log::error!("failed to send discovery response to {}: {}", addr, e);
+                }
+            }
+        }
+    }
+}
+
+/// Sends a discovery query to the address and collects the servers that respond until the timeout.
+/// The address can be a broadcast address, a multicast group or a server address.
+/// Only servers with the same protocol id respond.
+///
+/// When the server address is unspecified (`0.0.0.0`), the address that sent the response is used instead.
+pub fn discover(target: SocketAddr, protocol_id: u64, timeout: Duration) -> Result<Vec<DiscoveredServer>, io::Error> {
+    let socket = match target {
+        SocketAddr::V4(_) => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            socket.set_broadcast(true)?;
+            socket
+        }
+        SocketAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+    };
+
+    let mut buffer = [0u8; DISCOVERY_MAX_PACKET_BYTES];
+    let query_nonce = u64::from_le_bytes(generate_random_bytes());
+    let query = DiscoveryPacket::Query {
+        protocol_id,
+        nonce: query_nonce,
+    };
+    query.encode(&mut buffer).expect("discovery query fits in the buffer");
+    socket.send_to(&buffer[..DISCOVERY_QUERY_BYTES], target)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut servers = vec![];
+    let mut discovered_addresses = HashSet::new();
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(result) => result,
+            Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(DiscoveryPacket::Response {
+            protocol_id: server_protocol_id,
+            nonce,
+            name,
+            mut addr,
+            current_clients,
+            max_clients,
+        }) = DiscoveryPacket::decode(&buffer[..len])
+        {
+            if nonce != query_nonce || server_protocol_id != protocol_id {
+                continue;
+            }
+            if addr.ip().is_unspecified() {
+                addr.set_ip(from.ip());
+            }
+            if discovered_addresses.insert(addr) {
+                servers.push(DiscoveredServer {
+                    name,
+                    protocol_id,
+                    addr,
+                    current_clients: current_clients as usize,
+                    max_clients: max_clients as usize,
+                });
+            }
+        }
+    }
+
+    Ok(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{RenetConnectionConfig, ServerAuthentication, ServerConfig};
+
+    fn create_server(protocol_id: u64, public_addr: SocketAddr) -> RenetServer {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_config = ServerConfig::new(16, protocol_id, public_addr, ServerAuthentication::Unsecure);
+        RenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap()
+    }
+
+    fn discover_with_responder(responder: &mut DiscoveryResponder, server: &RenetServer, protocol_id: u64) -> Vec<DiscoveredServer> {
+        let target = responder.local_addr().unwrap();
+        let handle = thread::spawn(move || discover(target, protocol_id, Duration::from_millis(200)).unwrap());
+        while !handle.is_finished() {
+            responder.update(server).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        handle.join().unwrap()
+    }
+
+    #[test]
+    fn discover_servers() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut responder = DiscoveryResponder::new(socket, "LAN party").unwrap();
+        let server = create_server(7, "127.0.0.1:5000".parse().unwrap());
+
+        let servers = discover_with_responder(&mut responder, &server, 7);
+        assert_eq!(
+            servers,
+            vec![DiscoveredServer {
+                name: "LAN party".to_string(),
+                protocol_id: 7,
+                addr: "127.0.0.1:5000".parse().unwrap(),
+                current_clients: 0,
+                max_clients: 16,
+            }]
+        );
+
+        // Servers with another protocol id don't respond
+        let servers = discover_with_responder(&mut responder, &server, 8);
+        assert!(servers.is_empty());
+
+        // The address that responded is used for unspecified server addresses
+        let server = create_server(7, "0.0.0.0:5000".parse().unwrap());
+        let servers = discover_with_responder(&mut responder, &server, 7);
+        assert_eq!(servers[0].addr, "127.0.0.1:5000".parse().unwrap());
+    }
+
+    #[test]
+    fn ignored_queries() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut responder = DiscoveryResponder::new(socket, "LAN party").unwrap();
+        let server = create_server(7, "127.0.0.1:5000".parse().unwrap());
+
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client_socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let mut buffer = [0u8; DISCOVERY_MAX_PACKET_BYTES];
+        let query = DiscoveryPacket::Query { protocol_id: 7, nonce: 0 };
+        let len = query.encode(&mut buffer).unwrap();
+
+        // Queries without padding
+        client_socket.send_to(&buffer[..len], responder.local_addr().unwrap()).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        responder.update(&server).unwrap();
+        assert!(client_socket.recv_from(&mut buffer).is_err());
+
+        // Only the first of the queries sent at the same time is answered
+        client_socket
+            .send_to(&buffer[..DISCOVERY_QUERY_BYTES], responder.local_addr().unwrap())
+            .unwrap();
+        client_socket
+            .send_to(&buffer[..DISCOVERY_QUERY_BYTES], responder.local_addr().unwrap())
+            .unwrap();
+        thread::sleep(Duration::from_millis(10));
+        responder.update(&server).unwrap();
+        let (len, _) = client_socket.recv_from(&mut buffer).unwrap();
+        assert!(len <= DISCOVERY_QUERY_BYTES);
+        assert!(matches!(
+            DiscoveryPacket::decode(&buffer[..len]),
+            Some(DiscoveryPacket::Response { .. })
+        ));
+        assert!(client_socket.recv_from(&mut buffer).is_err());
+    }
+}
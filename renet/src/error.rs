@@ -48,6 +48,9 @@ impl From<std::io::Error> for RenetError {
     }
 }
 
+/// Reason for a disconnection, from the netcode layer (timeouts, client quit, kicked, denied
+/// connections) or the rechannel layer (channel errors).
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DisconnectionReason {
     Rechannel(RechannelDisconnectReason),
     Netcode(NetcodeDisconnectReason),
@@ -71,7 +74,7 @@ impl fmt::Display for DisconnectionReason {
 
         match *self {
             Rechannel(reason) => write!(f, "{}", reason),
-            Netcode(error) => write!(f, "{}", error),
+            Netcode(ref error) => write!(f, "{}", error),
         }
     }
 }
@@ -1,21 +1,45 @@
+mod capture;
 mod circular_buffer;
 mod client;
 mod config;
+mod discovery;
 mod error;
 mod network_info;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod reconnect;
 mod server;
+mod server_metrics;
+mod sharded_server;
+mod threaded_server;
+mod user_data;
 
-pub use rechannel::channel::{BlockChannelConfig, ChannelConfig, DefaultChannel, ReliableChannelConfig, UnreliableChannelConfig};
-pub use rechannel::error::{ChannelError, DisconnectionReason, RechannelError};
+pub use rechannel::channel::{
+    BlockChannelConfig, ChannelConfig, ChannelStats, DefaultChannel, ReliableChannelConfig, UnreliableChannelConfig,
+};
+pub use rechannel::error::{ChannelError, DisconnectionReason as RechannelDisconnectionReason, RechannelError};
+pub use rechannel::remote_connection::PacketStats;
 
-pub use renetcode::{generate_random_bytes, ConnectToken, NetcodeError};
+pub use renetcode::{
+    generate_random_bytes, ApplicationReason, ConnectToken, DisconnectReason as NetcodeDisconnectReason, HandshakeStats, IpRange,
+    NetcodeError, RateLimitConfig, RateLimitStats,
+};
 pub use renetcode::{NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
 
-pub use client::{ClientAuthentication, RenetClient};
+pub use capture::{CaptureReader, CaptureRecord, PacketRecorder, ServerReplay};
+pub use client::{ClientAuthentication, ClientEvent, RenetClient};
 pub use config::RenetConnectionConfig;
-pub use error::RenetError;
+pub use discovery::{discover, DiscoveredServer, DiscoveryResponder, DISCOVERY_MAX_NAME_BYTES};
+pub use error::{DisconnectionReason, RenetError};
 pub use network_info::NetworkInfo;
+#[cfg(feature = "prometheus")]
+pub use prometheus::PrometheusExporter;
+pub use reconnect::{ReconnectPolicy, TokenSource};
 pub use server::{RenetServer, ServerAuthentication, ServerConfig, ServerEvent};
+pub use server_metrics::{ClientMetrics, ServerMetrics};
+pub use sharded_server::ShardedRenetServer;
+pub use threaded_server::ThreadedRenetServer;
+pub use user_data::{from_user_data, to_user_data, UserDataError};
 
 // Reused in the renet_visualizer crate
 #[doc(hidden)]
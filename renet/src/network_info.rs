@@ -1,11 +1,16 @@
 use std::time::Duration;
 
+use rechannel::{
+    channel::ChannelStats,
+    remote_connection::{PacketStats, RemoteConnection},
+};
+
 use crate::CircularBuffer;
 
 const CIRCULAR_BUFFER_SIZE: usize = 60;
 
 /// Network informations about a connection.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 pub struct NetworkInfo {
     /// Round-trip Time
     pub rtt: f32,
@@ -14,6 +19,23 @@ pub struct NetworkInfo {
     /// Received kilobits per second.
     pub received_kbps: f32,
     pub packet_loss: f32,
+    /// Cumulative packet counters.
+    pub packets: PacketStats,
+    /// Traffic counters of the channels, ordered by channel id.
+    pub channels: Vec<ChannelStats>,
+}
+
+impl NetworkInfo {
+    pub(crate) fn new(connection: &RemoteConnection, packet_info: &ClientPacketInfo) -> Self {
+        Self {
+            rtt: connection.rtt(),
+            sent_kbps: packet_info.sent_kbps,
+            received_kbps: packet_info.received_kbps,
+            packet_loss: connection.packet_loss(),
+            packets: connection.packet_stats(),
+            channels: connection.channels_stats(),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -0,0 +1,117 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use log::error;
+use renet_http::Response;
+
+use crate::ServerMetrics;
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Serves the server metrics over HTTP in the Prometheus text format, at `GET /metrics`, with the `prometheus` feature.
+/// The requests are handled in background threads by [renet_http], the served metrics are the ones from
+/// the last [PrometheusExporter::update].
+///
+/// Useful for headless servers without a recorder for the [metrics](https://docs.rs/metrics) crate.
+pub struct PrometheusExporter {
+    addr: SocketAddr,
+    text: Arc<Mutex<String>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PrometheusExporter {
+    /// Binds the HTTP listener and spawns the thread that serves it.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let text = Arc::new(Mutex::new(ServerMetrics::default().to_prometheus()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_text = text.clone();
+        let thread_shutdown = shutdown.clone();
+        let thread = thread::Builder::new()
+            .name("renet prometheus exporter".to_string())
+            .spawn(move || {
+                let result = renet_http::serve(listener, 0, &thread_shutdown, move |request| {
+                    match (request.method.as_str(), request.path.as_str()) {
+                        ("GET", "/metrics") => Response::new(200, PROMETHEUS_CONTENT_TYPE, thread_text.lock().unwrap().clone()),
+                        _ => Response::new(404, PROMETHEUS_CONTENT_TYPE, ""),
+                    }
+                });
+                if let Err(e) = result {
+                    error!("Prometheus exporter stopped: {}", e);
+                }
+            })?;
+
+        Ok(Self {
+            addr,
+            text,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the address the exporter is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Replaces the served metrics.
+    pub fn update(&self, metrics: &ServerMetrics) {
+        *self.text.lock().unwrap() = metrics.to_prometheus();
+    }
+}
+
+impl Drop for PrometheusExporter {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Wake up the listener so the thread sees the shutdown
+        if TcpStream::connect(self.addr).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    fn scrape(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn scrape_prometheus_exporter() {
+        let exporter = PrometheusExporter::bind("127.0.0.1:0").unwrap();
+        let metrics = ServerMetrics {
+            connected_clients: 1,
+            max_clients: 8,
+            ..Default::default()
+        };
+        exporter.update(&metrics);
+
+        let response = scrape(exporter.addr(), "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(response.ends_with(&metrics.to_prometheus()));
+
+        let response = scrape(exporter.addr(), "/other");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
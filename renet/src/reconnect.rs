@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use renetcode::{generate_random_bytes, ConnectToken};
+
+/// Provides a new connect token when the client reconnects, receives the current time.
+/// Returning `None` counts as a failed attempt, it is tried again after the next delay.
+pub type TokenSource = Box<dyn FnMut(Duration) -> Option<ConnectToken> + Send + Sync>;
+
+/// Configuration of the automatic reconnection of the [RenetClient][crate::RenetClient].
+///
+/// The client reconnects after the connection or the connection attempt times out, or after an
+/// error in the socket. The delay between attempts grows exponentially up to `max_delay`.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt.
+    pub initial_delay: Duration,
+    /// Maximum delay between attempts.
+    pub max_delay: Duration,
+    /// Multiplier applied to the delay after each failed attempt, values below 1.0 are used as 1.0.
+    pub multiplier: f64,
+    /// Fraction of the delay that is randomly removed, clamped between 0.0 and 1.0.
+    /// Spreads the reconnections of clients that lost the connection at the same time.
+    pub jitter: f64,
+    /// Maximum number of consecutive attempts, `None` to try forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before the attempt, the first attempt is 1.
+    /// The delay is never bigger than `max_delay`, even with invalid values in the policy.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        // Also replaces a NaN multiplier or jitter
+        let multiplier = if self.multiplier >= 1.0 { self.multiplier } else { 1.0 };
+        let jitter = if self.jitter >= 0.0 { self.jitter.min(1.0) } else { 0.0 };
+
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_delay.as_secs_f64() * multiplier.powi(exponent);
+        let delay = delay.min(self.max_delay.as_secs_f64());
+
+        let random = u32::from_le_bytes(generate_random_bytes()) as f64 / u32::MAX as f64;
+        let delay = delay * (1.0 - jitter * random);
+
+        Duration::try_from_secs_f64(delay).unwrap_or(self.max_delay).min(self.max_delay)
+    }
+}
+
+pub(crate) struct Reconnect {
+    pub policy: ReconnectPolicy,
+    pub token_source: TokenSource,
+    pub attempt: u32,
+    pub next_attempt_time: Option<Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_delay() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_attempts: None,
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(5), Duration::from_secs(10));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(10));
+
+        let policy = ReconnectPolicy { jitter: 0.5, ..policy };
+        for _ in 0..100 {
+            let delay = policy.delay(2);
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn invalid_policy_delay() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::MAX,
+            multiplier: f64::INFINITY,
+            jitter: f64::NAN,
+            max_attempts: None,
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::MAX);
+
+        let policy = ReconnectPolicy {
+            max_delay: Duration::from_secs(10),
+            multiplier: f64::NAN,
+            ..policy
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(100), Duration::from_secs(1));
+
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::MAX,
+            multiplier: 0.5,
+            jitter: -1.0,
+            ..policy
+        };
+        assert_eq!(policy.delay(3), Duration::from_secs(10));
+    }
+}
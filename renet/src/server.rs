@@ -1,47 +1,84 @@
 use crate::{
+    capture::{CaptureRecord, PacketRecorder},
+    error::DisconnectionReason,
     network_info::{ClientPacketInfo, NetworkInfo, PacketInfo},
+    server_metrics::{ClientMetrics, ServerMetrics},
+    user_data::{from_user_data, UserDataError},
     RenetConnectionConfig, NUM_DISCONNECT_PACKETS_TO_SEND,
 };
 
 use std::{
     collections::{HashMap, VecDeque},
     io,
-    net::{SocketAddr, UdpSocket},
+    net::{IpAddr, SocketAddr, UdpSocket},
     time::Duration,
 };
 
 use log::error;
-use rechannel::{disconnect_packet, error::DisconnectionReason, server::RechannelServer, Bytes};
-use renetcode::{NetcodeServer, ServerResult, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
+use rechannel::{disconnect_packet, error::DisconnectionReason as RechannelDisconnectionReason, server::RechannelServer, Bytes};
+use renetcode::{
+    ApplicationReason, IpRange, NetcodeServer, RateLimitConfig, RateLimitStats, ServerResult, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
+    NETCODE_USER_DATA_BYTES,
+};
+use serde::de::DeserializeOwned;
 
 /// A server that can establish authenticated connections with multiple clients.
 /// Can send/receive encrypted messages from/to them.
 #[derive(Debug)]
 pub struct RenetServer {
-    socket: UdpSocket,
+    socket: ServerSocket,
     reliable_server: RechannelServer<u64>,
     netcode_server: NetcodeServer,
     bandwidth_smoothing_factor: f32,
+    per_client_metrics: bool,
     clients_packet_info: HashMap<SocketAddr, ClientPacketInfo>,
     buffer: Box<[u8]>,
     events: VecDeque<ServerEvent>,
 }
 
+/// The server socket, records the packets sent when there is a recorder.
+#[derive(Debug)]
+struct ServerSocket {
+    socket: UdpSocket,
+    recorder: Option<PacketRecorder>,
+    // Replayed servers don't send packets
+    replaying: bool,
+}
+
 /// Events that can occur in the server.
 #[derive(Debug, Clone)]
 pub enum ServerEvent {
     ClientConnected(u64, Box<[u8; NETCODE_USER_DATA_BYTES]>),
-    ClientDisconnected(u64),
+    /// A client disconnected, with the reason and its last network info.
+    ClientDisconnected {
+        client_id: u64,
+        reason: DisconnectionReason,
+        network_info: NetworkInfo,
+    },
+    /// A client with a valid connect token wants to connect, only sent when the connection
+    /// approval is required. Use [RenetServer::approve_connection] or [RenetServer::deny_connection].
+    ConnectionRequested {
+        client_id: u64,
+        addr: SocketAddr,
+        user_data: Box<[u8; NETCODE_USER_DATA_BYTES]>,
+    },
 }
 
 /// Configuration to establish a secure or unsecure connection with the server.
+#[derive(Clone)]
 pub enum ServerAuthentication {
     /// Establishes a safe connection using a private key for encryption. The private key cannot be
     /// shared with the client. Connections are stablished using
     /// [ConnectTokens][crate::ConnectToken].
     ///
+    /// Connect tokens generated with the previous private keys are still accepted, so the key can
+    /// be rotated without dropping the tokens already issued.
+    ///
     /// See also [ClientAuthentication::Secure][crate::ClientAuthentication::Secure]
-    Secure { private_key: [u8; NETCODE_KEY_BYTES] },
+    Secure {
+        private_key: [u8; NETCODE_KEY_BYTES],
+        previous_private_keys: Vec<[u8; NETCODE_KEY_BYTES]>,
+    },
     /// Establishes unsafe connections with clients, useful for testing and prototyping.
     ///
     /// See also [ClientAuthentication::Unsecure][crate::ClientAuthentication::Unsecure]
@@ -49,6 +86,7 @@ pub enum ServerAuthentication {
 }
 
 /// Configuration options for the renet server.
+#[derive(Clone)]
 pub struct ServerConfig {
     /// Maximum numbers of clients that can be connected at a time
     pub max_clients: usize,
@@ -61,6 +99,15 @@ pub struct ServerConfig {
     pub public_addr: SocketAddr,
     /// Authentication configuration for the server
     pub authentication: ServerAuthentication,
+    /// When enabled, connection requests are sent as [ServerEvent::ConnectionRequested] and the
+    /// clients can only connect after being approved. Disabled by default.
+    pub require_connection_approval: bool,
+    /// Rate limits for the connection packets from clients that are not connected.
+    /// Disabled by default.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// When enabled, the [ServerMetrics] also export the metrics of each client.
+    /// Disabled by default, see [ServerMetrics::per_client_metrics].
+    pub per_client_metrics: bool,
 }
 
 impl ServerConfig {
@@ -70,6 +117,9 @@ impl ServerConfig {
             protocol_id,
             public_addr,
             authentication,
+            require_connection_approval: false,
+            rate_limit: None,
+            per_client_metrics: false,
         }
     }
 }
@@ -86,26 +136,37 @@ impl RenetServer {
         let reliable_server = RechannelServer::new(current_time, connection_config.to_connection_config());
 
         // For unsecure connections we use an fixed private key.
-        let private_key = match server_config.authentication {
-            ServerAuthentication::Unsecure => [0; NETCODE_KEY_BYTES],
-            ServerAuthentication::Secure { private_key } => private_key,
+        let (private_key, previous_private_keys) = match server_config.authentication {
+            ServerAuthentication::Unsecure => ([0; NETCODE_KEY_BYTES], Vec::new()),
+            ServerAuthentication::Secure {
+                private_key,
+                previous_private_keys,
+            } => (private_key, previous_private_keys),
         };
 
-        let netcode_server = NetcodeServer::new(
+        let mut netcode_server = NetcodeServer::new(
             current_time,
             server_config.max_clients,
             server_config.protocol_id,
             server_config.public_addr,
             private_key,
         );
+        netcode_server.set_previous_private_keys(previous_private_keys);
+        netcode_server.set_require_connection_approval(server_config.require_connection_approval);
+        netcode_server.set_rate_limit(server_config.rate_limit);
 
         socket.set_nonblocking(true)?;
 
         Ok(Self {
-            socket,
+            socket: ServerSocket {
+                socket,
+                recorder: None,
+                replaying: false,
+            },
             netcode_server,
             reliable_server,
             bandwidth_smoothing_factor,
+            per_client_metrics: server_config.per_client_metrics,
             buffer,
             clients_packet_info: HashMap::new(),
             events: VecDeque::new(),
@@ -123,6 +184,42 @@ impl RenetServer {
         self.netcode_server.address()
     }
 
+    pub fn protocol_id(&self) -> u64 {
+        self.netcode_server.protocol_id()
+    }
+
+    /// Sets the recorder that writes all the packets received and sent, and the update durations.
+    /// The capture can be replayed with [ServerReplay][crate::ServerReplay] when the recorder is set
+    /// before the first update.
+    pub fn set_recorder(&mut self, recorder: Option<PacketRecorder>) {
+        self.socket.recorder = recorder;
+        if let Some(recorder) = &mut self.socket.recorder {
+            recorder.record(&CaptureRecord::Start {
+                current_time: self.netcode_server.current_time(),
+            });
+            recorder.record(&CaptureRecord::ChallengeKey(*self.netcode_server.challenge_key()));
+        }
+    }
+
+    /// Removes the recorder, flushing the capture.
+    pub fn take_recorder(&mut self) -> Option<PacketRecorder> {
+        let mut recorder = self.socket.recorder.take();
+        if let Some(recorder) = &mut recorder {
+            if let Err(e) = recorder.flush() {
+                error!("Failed to flush the capture: {}", e);
+            }
+        }
+        recorder
+    }
+
+    pub(crate) fn set_replaying(&mut self) {
+        self.socket.replaying = true;
+    }
+
+    pub(crate) fn set_challenge_key(&mut self, challenge_key: [u8; NETCODE_KEY_BYTES]) {
+        self.netcode_server.set_challenge_key(challenge_key);
+    }
+
     pub fn get_event(&mut self) -> Option<ServerEvent> {
         self.events.pop_front()
     }
@@ -135,7 +232,24 @@ impl RenetServer {
             server_result,
             current_time,
             self.bandwidth_smoothing_factor,
-            &self.socket,
+            &mut self.socket,
+            &mut self.reliable_server,
+            &mut self.clients_packet_info,
+            &mut self.events,
+        ) {
+            error!("Failed to send disconnect packet to client {}: {}", client_id, e);
+        }
+    }
+
+    /// Disconnects a client, the reason is sent to the client in the disconnect packet.
+    pub fn disconnect_with_reason(&mut self, client_id: u64, reason: ApplicationReason) {
+        let current_time = self.netcode_server.current_time();
+        let server_result = self.netcode_server.disconnect_with_reason(client_id, reason);
+        if let Err(e) = handle_server_result(
+            server_result,
+            current_time,
+            self.bandwidth_smoothing_factor,
+            &mut self.socket,
             &mut self.reliable_server,
             &mut self.clients_packet_info,
             &mut self.events,
@@ -144,6 +258,116 @@ impl RenetServer {
         }
     }
 
+    /// Approves a connection request, the client will continue the connection handshake.
+    pub fn approve_connection(&mut self, client_id: u64) {
+        let current_time = self.netcode_server.current_time();
+        let server_result = self.netcode_server.approve_connection(client_id);
+        if let Err(e) = handle_server_result(
+            server_result,
+            current_time,
+            self.bandwidth_smoothing_factor,
+            &mut self.socket,
+            &mut self.reliable_server,
+            &mut self.clients_packet_info,
+            &mut self.events,
+        ) {
+            error!("Failed to send challenge packet to client {}: {}", client_id, e);
+        }
+    }
+
+    /// Returns the client ids of the connection requests waiting for approval.
+    pub fn pending_connection_requests(&self) -> Vec<u64> {
+        self.netcode_server.pending_connection_requests()
+    }
+
+    /// Returns the client ids of the clients in the connection handshake.
+    pub(crate) fn pending_clients_id(&self) -> Vec<u64> {
+        self.netcode_server.pending_clients_id()
+    }
+
+    /// Denies a connection request, the client will be notified that the connection was denied.
+    pub fn deny_connection(&mut self, client_id: u64) {
+        let current_time = self.netcode_server.current_time();
+        let server_result = self.netcode_server.deny_connection(client_id);
+        if let Err(e) = handle_server_result(
+            server_result,
+            current_time,
+            self.bandwidth_smoothing_factor,
+            &mut self.socket,
+            &mut self.reliable_server,
+            &mut self.clients_packet_info,
+            &mut self.events,
+        ) {
+            error!("Failed to send connection denied packet to client {}: {}", client_id, e);
+        }
+    }
+
+    /// Denies a pending connection request, the reason is sent to the client.
+    pub fn deny_connection_with_reason(&mut self, client_id: u64, reason: ApplicationReason) {
+        let current_time = self.netcode_server.current_time();
+        let server_result = self.netcode_server.deny_connection_with_reason(client_id, reason);
+        if let Err(e) = handle_server_result(
+            server_result,
+            current_time,
+            self.bandwidth_smoothing_factor,
+            &mut self.socket,
+            &mut self.reliable_server,
+            &mut self.clients_packet_info,
+            &mut self.events,
+        ) {
+            error!("Failed to send connection denied packet to client {}: {}", client_id, e);
+        }
+    }
+
+    /// Returns the number of connection packets dropped by the rate limits.
+    pub fn rate_limit_stats(&self) -> RateLimitStats {
+        self.netcode_server.rate_limit_stats()
+    }
+
+    /// Bans a client id, its connection requests are dropped. The ban expires after the duration,
+    /// or never if no duration is given. Use [RenetServer::disconnect] to kick a connected client.
+    pub fn ban_client_id(&mut self, client_id: u64, duration: Option<Duration>) {
+        self.netcode_server.ban_client_id(client_id, duration);
+    }
+
+    pub fn unban_client_id(&mut self, client_id: u64) {
+        self.netcode_server.unban_client_id(client_id);
+    }
+
+    /// Bans an IP address or range, its connection requests are dropped. The ban expires after the
+    /// duration, or never if no duration is given. Connected clients are not disconnected.
+    pub fn ban_ip<R: Into<IpRange>>(&mut self, ip_range: R, duration: Option<Duration>) {
+        self.netcode_server.ban_ip(ip_range, duration);
+    }
+
+    pub fn unban_ip<R: Into<IpRange>>(&mut self, ip_range: R) {
+        self.netcode_server.unban_ip(ip_range);
+    }
+
+    /// Revokes a connect token by its MAC and expire timestamp, see [ConnectToken::mac][crate::ConnectToken::mac].
+    pub fn revoke_connect_token(&mut self, mac: [u8; NETCODE_MAC_BYTES], expire_timestamp: u64) {
+        self.netcode_server.revoke_connect_token(mac, expire_timestamp);
+    }
+
+    /// Replaces the private key used to accept connect tokens, the current key becomes the only
+    /// previous key so the tokens already issued with it are still accepted.
+    pub fn rotate_private_key(&mut self, private_key: [u8; NETCODE_KEY_BYTES]) {
+        self.netcode_server.rotate_private_key(private_key);
+    }
+
+    /// Sets the previous private keys that are still accepted.
+    pub fn set_previous_private_keys(&mut self, private_keys: Vec<[u8; NETCODE_KEY_BYTES]>) {
+        self.netcode_server.set_previous_private_keys(private_keys);
+    }
+
+    pub fn is_client_id_banned(&self, client_id: u64) -> bool {
+        self.netcode_server.is_client_id_banned(client_id)
+    }
+
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        self.netcode_server.is_ip_banned(ip)
+    }
+
     /// Disconnects all connected clients.
     pub fn disconnect_clients(&mut self) {
         for client_id in self.netcode_server.clients_id() {
@@ -153,64 +377,98 @@ impl RenetServer {
 
     /// Returns the client's network info if the client exits.
     pub fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
-        let addr = match self.netcode_server.client_addr(client_id) {
-            Some(addr) => addr,
-            None => return None,
-        };
-
-        let client_packet_info = self.clients_packet_info.get(&addr).unwrap();
+        let addr = self.netcode_server.client_addr(client_id)?;
+        let client_packet_info = self.clients_packet_info.get(&addr)?;
+        let connection = self.reliable_server.connection(client_id)?;
 
-        let sent_kbps = client_packet_info.sent_kbps;
-        let received_kbps = client_packet_info.received_kbps;
-        let rtt = self.reliable_server.client_rtt(client_id);
-        let packet_loss = self.reliable_server.client_packet_loss(client_id);
+        Some(NetworkInfo::new(connection, client_packet_info))
+    }
 
-        Some(NetworkInfo {
-            received_kbps,
-            sent_kbps,
-            rtt,
-            packet_loss,
-        })
+    /// Returns a snapshot of the server metrics: handshakes, rate limits and the network
+    /// info and channel counters of the connected clients.
+    pub fn metrics(&self) -> ServerMetrics {
+        let clients = self
+            .clients_id()
+            .into_iter()
+            .filter_map(|client_id| {
+                let network_info = self.network_info(client_id)?;
+                Some(ClientMetrics { client_id, network_info })
+            })
+            .collect();
+
+        ServerMetrics {
+            connected_clients: self.connected_clients(),
+            max_clients: self.max_clients(),
+            handshake: self.netcode_server.handshake_stats(),
+            rate_limit: self.rate_limit_stats(),
+            clients,
+            per_client_metrics: self.per_client_metrics,
+        }
     }
 
     /// Advances the server by duration, and receive packets from the network.
     pub fn update(&mut self, duration: Duration) -> Result<(), io::Error> {
-        self.reliable_server.update_connections(duration);
-        self.netcode_server.update(duration);
+        self.advance_time(duration);
 
-        let current_time = self.netcode_server.current_time();
+        let mut buffer = std::mem::take(&mut self.buffer);
+        let result = self.receive_packets(&mut buffer);
+        self.buffer = buffer;
+        result?;
 
-        loop {
-            match self.socket.recv_from(&mut self.buffer) {
-                Ok((len, addr)) => {
-                    if let Some(info) = self.clients_packet_info.get_mut(&addr) {
-                        let packet_info = PacketInfo::new(current_time, len);
-                        info.add_packet_received(packet_info);
-                    }
+        self.update_connections()
+    }
 
-                    let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
-                    handle_server_result(
-                        server_result,
-                        current_time,
-                        self.bandwidth_smoothing_factor,
-                        &self.socket,
-                        &mut self.reliable_server,
-                        &mut self.clients_packet_info,
-                        &mut self.events,
-                    )?;
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+    fn receive_packets(&mut self, buffer: &mut [u8]) -> Result<(), io::Error> {
+        loop {
+            match self.socket.socket.recv_from(buffer) {
+                Ok((len, addr)) => self.process_packet_from(addr, &mut buffer[..len])?,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
                 Err(e) => return Err(e),
             };
         }
+    }
+
+    pub(crate) fn advance_time(&mut self, duration: Duration) {
+        if let Some(recorder) = &mut self.socket.recorder {
+            recorder.record(&CaptureRecord::Update { duration });
+        }
+        self.reliable_server.update_connections(duration);
+        self.netcode_server.update(duration);
+    }
+
+    /// Processes a packet received from addr, used when the socket is read outside of [RenetServer::update].
+    pub(crate) fn process_packet_from(&mut self, addr: SocketAddr, packet: &mut [u8]) -> Result<(), io::Error> {
+        let current_time = self.netcode_server.current_time();
+        if let Some(recorder) = &mut self.socket.recorder {
+            recorder.record_received(current_time, addr, packet);
+        }
+        if let Some(info) = self.clients_packet_info.get_mut(&addr) {
+            let packet_info = PacketInfo::new(current_time, packet.len());
+            info.add_packet_received(packet_info);
+        }
 
+        let server_result = self.netcode_server.process_packet(addr, packet);
+        handle_server_result(
+            server_result,
+            current_time,
+            self.bandwidth_smoothing_factor,
+            &mut self.socket,
+            &mut self.reliable_server,
+            &mut self.clients_packet_info,
+            &mut self.events,
+        )
+    }
+
+    /// Updates the connected clients, sending keep alive packets and handling timeouts and disconnections.
+    pub(crate) fn update_connections(&mut self) -> Result<(), io::Error> {
+        let current_time = self.netcode_server.current_time();
         for client_id in self.netcode_server.clients_id().into_iter() {
             let server_result = self.netcode_server.update_client(client_id);
             handle_server_result(
                 server_result,
                 current_time,
                 self.bandwidth_smoothing_factor,
-                &self.socket,
+                &mut self.socket,
                 &mut self.reliable_server,
                 &mut self.clients_packet_info,
                 &mut self.events,
@@ -218,16 +476,26 @@ impl RenetServer {
         }
 
         // Handle disconnected clients from Rechannel
-        while let Some((client_id, reason)) = self.reliable_server.disconnected_client() {
-            self.events.push_back(ServerEvent::ClientDisconnected(client_id));
-            if reason != DisconnectionReason::DisconnectedByClient {
+        while let Some((client_id, reason, connection)) = self.reliable_server.disconnected_connection() {
+            let network_info = self
+                .netcode_server
+                .client_addr(client_id)
+                .and_then(|addr| self.clients_packet_info.get(&addr))
+                .map(|packet_info| NetworkInfo::new(&connection, packet_info))
+                .unwrap_or_default();
+            self.events.push_back(ServerEvent::ClientDisconnected {
+                client_id,
+                reason: DisconnectionReason::Rechannel(reason),
+                network_info,
+            });
+            if reason != RechannelDisconnectionReason::DisconnectedByClient {
                 match disconnect_packet(reason) {
                     Err(e) => error!("Failed to serialize disconnect packet: {}", e),
                     Ok(packet) => match self.netcode_server.generate_payload_packet(client_id, &packet) {
                         Err(e) => error!("Failed to encrypt disconnect packet: {}", e),
                         Ok((addr, payload)) => {
                             for _ in 0..NUM_DISCONNECT_PACKETS_TO_SEND {
-                                self.socket.send_to(payload, addr)?;
+                                self.socket.send_to(current_time, payload, addr)?;
                             }
                         }
                     },
@@ -283,7 +551,7 @@ impl RenetServer {
             for packet in packets.iter() {
                 match self.netcode_server.generate_payload_packet(client_id, packet) {
                     Ok((addr, payload)) => {
-                        send_to(current_time, &self.socket, &mut self.clients_packet_info, payload, addr)?;
+                        send_to(current_time, &mut self.socket, &mut self.clients_packet_info, payload, addr)?;
                     }
                     Err(e) => error!("Failed to encrypt payload packet: {}", e),
                 }
@@ -303,6 +571,11 @@ impl RenetServer {
         self.netcode_server.user_data(client_id)
     }
 
+    /// Returns the user data from the connected client deserialized, see [to_user_data][crate::to_user_data].
+    pub fn deserialize_user_data<T: DeserializeOwned>(&self, client_id: u64) -> Option<Result<T, UserDataError>> {
+        self.user_data(client_id).map(|user_data| from_user_data(&user_data))
+    }
+
     pub fn is_client_connected(&self, client_id: u64) -> bool {
         self.netcode_server.is_client_connected(client_id)
     }
@@ -327,7 +600,7 @@ fn handle_server_result(
     server_result: ServerResult,
     current_time: Duration,
     bandwidth_smoothing_factor: f32,
-    socket: &UdpSocket,
+    socket: &mut ServerSocket,
     reliable_server: &mut RechannelServer<u64>,
     packet_infos: &mut HashMap<SocketAddr, ClientPacketInfo>,
     events: &mut VecDeque<ServerEvent>,
@@ -346,6 +619,17 @@ fn handle_server_result(
                 log::error!("Error while processing payload for {}: {}", client_id, e)
             }
         }
+        ServerResult::ConnectionRequested {
+            client_id,
+            addr,
+            user_data,
+        } => {
+            events.push_back(ServerEvent::ConnectionRequested {
+                client_id,
+                addr,
+                user_data,
+            });
+        }
         ServerResult::ClientConnected {
             client_id,
             user_data,
@@ -357,13 +641,26 @@ fn handle_server_result(
             events.push_back(ServerEvent::ClientConnected(client_id, user_data));
             send_to(current_time, socket, packet_infos, payload, addr)?;
         }
-        ServerResult::ClientDisconnected { client_id, addr, payload } => {
-            events.push_back(ServerEvent::ClientDisconnected(client_id));
+        ServerResult::ClientDisconnected {
+            client_id,
+            addr,
+            reason,
+            payload,
+        } => {
+            let network_info = match (reliable_server.connection(client_id), packet_infos.get(&addr)) {
+                (Some(connection), Some(packet_info)) => NetworkInfo::new(connection, packet_info),
+                _ => NetworkInfo::default(),
+            };
+            events.push_back(ServerEvent::ClientDisconnected {
+                client_id,
+                reason: DisconnectionReason::Netcode(reason),
+                network_info,
+            });
             reliable_server.remove_connection(&client_id);
             packet_infos.remove(&addr);
             if let Some(payload) = payload {
                 for _ in 0..NUM_DISCONNECT_PACKETS_TO_SEND {
-                    socket.send_to(payload, addr)?;
+                    socket.send_to(current_time, payload, addr)?;
                 }
             }
         }
@@ -374,14 +671,105 @@ fn handle_server_result(
 
 fn send_to(
     current_time: Duration,
-    socket: &UdpSocket,
+    socket: &mut ServerSocket,
     packet_infos: &mut HashMap<SocketAddr, ClientPacketInfo>,
     packet: &[u8],
     addr: SocketAddr,
-) -> Result<usize, std::io::Error> {
+) -> Result<(), std::io::Error> {
     if let Some(info) = packet_infos.get_mut(&addr) {
         let packet_info = PacketInfo::new(current_time, packet.len());
         info.add_packet_sent(packet_info);
     }
-    socket.send_to(packet, addr)
+    socket.send_to(current_time, packet, addr)
+}
+
+impl ServerSocket {
+    fn send_to(&mut self, current_time: Duration, packet: &[u8], addr: SocketAddr) -> Result<(), io::Error> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_sent(current_time, addr, packet);
+        }
+        if !self.replaying {
+            self.socket.send_to(packet, addr)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientAuthentication, NetcodeDisconnectReason, RenetClient};
+
+    fn connect_client(server: &mut RenetServer, client_id: u64) -> RenetClient {
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 7,
+            client_id,
+            server_addresses: vec![server.addr()],
+            user_data: None,
+            expire_seconds: 300,
+            timeout_seconds: 15,
+        };
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = RenetClient::new(Duration::ZERO, socket, RenetConnectionConfig::default(), authentication).unwrap();
+        for _ in 0..100 {
+            update(&mut client, server);
+            if client.is_connected() {
+                break;
+            }
+        }
+        assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected(id, _)) if id == client_id));
+        client
+    }
+
+    fn update(client: &mut RenetClient, server: &mut RenetServer) {
+        client.update(Duration::from_millis(10)).unwrap();
+        client.send_packets().unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        server.update(Duration::from_millis(10)).unwrap();
+        server.send_packets().unwrap();
+    }
+
+    fn disconnected_event(server: &mut RenetServer) -> (u64, DisconnectionReason) {
+        match server.get_event() {
+            Some(ServerEvent::ClientDisconnected { client_id, reason, .. }) => (client_id, reason),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn client_disconnected_reasons() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        let mut server = RenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap();
+
+        let mut client = connect_client(&mut server, 1);
+        client.disconnect();
+        for _ in 0..10 {
+            server.update(Duration::from_millis(10)).unwrap();
+            if server.connected_clients() == 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            disconnected_event(&mut server),
+            (1, DisconnectionReason::Netcode(NetcodeDisconnectReason::DisconnectedByClient))
+        );
+
+        let _client = connect_client(&mut server, 2);
+        server.disconnect(2);
+        assert_eq!(
+            disconnected_event(&mut server),
+            (2, DisconnectionReason::Netcode(NetcodeDisconnectReason::DisconnectedByServer))
+        );
+
+        let _client = connect_client(&mut server, 3);
+        server.update(Duration::from_secs(20)).unwrap();
+        assert_eq!(
+            disconnected_event(&mut server),
+            (3, DisconnectionReason::Netcode(NetcodeDisconnectReason::ConnectionTimedOut))
+        );
+    }
 }
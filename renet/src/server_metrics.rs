@@ -0,0 +1,392 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use renetcode::{HandshakeStats, RateLimitStats};
+
+use crate::{ChannelStats, NetworkInfo};
+
+/// Snapshot of the server metrics, from [RenetServer::metrics][crate::RenetServer::metrics].
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics {
+    pub connected_clients: usize,
+    pub max_clients: usize,
+    pub handshake: HandshakeStats,
+    pub rate_limit: RateLimitStats,
+    pub clients: Vec<ClientMetrics>,
+    /// Also export the round-trip time, packet loss and kbps of each client with a `client_id` label.
+    /// Disabled by default, the number of exported series grows with the number of clients.
+    pub per_client_metrics: bool,
+}
+
+/// Metrics of a connected client.
+#[derive(Debug, Clone, Default)]
+pub struct ClientMetrics {
+    pub client_id: u64,
+    pub network_info: NetworkInfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: MetricKind,
+    samples: Vec<Sample>,
+}
+
+struct Sample {
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+impl Metric {
+    fn new(name: &'static str, help: &'static str, kind: MetricKind) -> Self {
+        Self {
+            name,
+            help,
+            kind,
+            samples: vec![],
+        }
+    }
+
+    fn with_value(mut self, value: f64) -> Self {
+        self.samples.push(Sample { labels: vec![], value });
+        self
+    }
+
+    fn add(&mut self, labels: Vec<(&'static str, String)>, value: f64) {
+        self.samples.push(Sample { labels, value });
+    }
+}
+
+impl ServerMetrics {
+    fn metrics(&self) -> Vec<Metric> {
+        use MetricKind::*;
+
+        let handshake = &self.handshake;
+        let mut handshake_failures = Metric::new(
+            "renet_handshake_failures_total",
+            "Connection handshakes that failed, by reason.",
+            Counter,
+        );
+        for (reason, value) in [
+            ("invalid", handshake.invalid),
+            ("expired", handshake.expired),
+            ("banned", handshake.banned),
+            ("already_connected", handshake.already_connected),
+            ("too_many_pending", handshake.too_many_pending),
+            ("server_full", handshake.server_full),
+            ("denied", handshake.denied),
+            ("timed_out", handshake.timed_out),
+        ] {
+            handshake_failures.add(vec![("reason", reason.to_string())], value as f64);
+        }
+
+        let mut rate_limited = Metric::new(
+            "renet_rate_limited_packets_total",
+            "Connection packets dropped by the rate limits, by limit.",
+            Counter,
+        );
+        rate_limited.add(vec![("limit", "address".to_string())], self.rate_limit.dropped_by_address as f64);
+        rate_limited.add(vec![("limit", "subnet".to_string())], self.rate_limit.dropped_by_subnet as f64);
+
+        // Clients are aggregated to keep the number of series bounded, the counters of the clients
+        // are summed as gauges because they go down when a client disconnects.
+        let clients = &self.clients;
+        let clients_len = clients.len().max(1) as f64;
+        let client_sum = |value: fn(&NetworkInfo) -> f64| clients.iter().map(|client| value(&client.network_info)).sum::<f64>();
+        let max_rtt = clients.iter().map(|client| client.network_info.rtt as f64).fold(0.0, f64::max);
+
+        let mut channels: BTreeMap<u8, Vec<&ChannelStats>> = BTreeMap::new();
+        for client in clients.iter() {
+            for channel in client.network_info.channels.iter() {
+                channels.entry(channel.channel_id).or_default().push(channel);
+            }
+        }
+
+        let channel_metric = |name: &'static str, help: &'static str, value: fn(&ChannelStats) -> f64| {
+            let mut metric = Metric::new(name, help, Gauge);
+            for (channel_id, stats) in channels.iter() {
+                let value = stats.iter().map(|channel| value(channel)).sum();
+                metric.add(vec![("channel_id", channel_id.to_string())], value);
+            }
+            metric
+        };
+        let mut ack_time = Metric::new(
+            "renet_channel_ack_time_seconds",
+            "Average time between sending a message and its ack in the channel.",
+            Gauge,
+        );
+        for (channel_id, stats) in channels.iter() {
+            let acked: u64 = stats.iter().map(|channel| channel.messages_acked).sum();
+            let total_ack_time: f64 = stats
+                .iter()
+                .map(|channel| channel.average_ack_time.as_secs_f64() * channel.messages_acked as f64)
+                .sum();
+            ack_time.add(vec![("channel_id", channel_id.to_string())], total_ack_time / acked.max(1) as f64);
+        }
+
+        let mut metrics = vec![
+            Metric::new("renet_connected_clients", "Number of connected clients.", Gauge).with_value(self.connected_clients as f64),
+            Metric::new("renet_max_clients", "Maximum number of connected clients.", Gauge).with_value(self.max_clients as f64),
+            Metric::new("renet_handshake_requests_total", "Connection request packets received.", Counter)
+                .with_value(handshake.requests as f64),
+            Metric::new("renet_handshake_challenges_sent_total", "Challenges sent to clients.", Counter)
+                .with_value(handshake.challenges_sent as f64),
+            Metric::new("renet_handshake_connected_total", "Clients that completed the handshake.", Counter)
+                .with_value(handshake.connected as f64),
+            handshake_failures,
+            rate_limited,
+            Metric::new(
+                "renet_clients_rtt_milliseconds",
+                "Average round-trip time of the connected clients.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.rtt as f64) / clients_len),
+            Metric::new(
+                "renet_clients_max_rtt_milliseconds",
+                "Highest round-trip time of the connected clients.",
+                Gauge,
+            )
+            .with_value(max_rtt),
+            Metric::new(
+                "renet_clients_packet_loss_ratio",
+                "Average packet loss of the connected clients, from 0 to 1.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.packet_loss as f64) / clients_len),
+            Metric::new(
+                "renet_clients_sent_kbps",
+                "Kilobits per second sent to the connected clients.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.sent_kbps as f64)),
+            Metric::new(
+                "renet_clients_received_kbps",
+                "Kilobits per second received from the connected clients.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.received_kbps as f64)),
+            Metric::new(
+                "renet_clients_packets_sent",
+                "Packets with messages sent to the connected clients.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.packets.packets_sent as f64)),
+            Metric::new(
+                "renet_clients_packets_received",
+                "Packets with messages received from the connected clients.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.packets.packets_received as f64)),
+            Metric::new(
+                "renet_clients_packets_lost",
+                "Packets sent to the connected clients that were never acked.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.packets.packets_lost as f64)),
+            Metric::new(
+                "renet_clients_fragment_errors",
+                "Fragments from the connected clients that failed to be reassembled.",
+                Gauge,
+            )
+            .with_value(client_sum(|info| info.packets.fragments_dropped as f64)),
+            channel_metric(
+                "renet_channel_sent_bytes",
+                "Bytes of the messages sent in the channel to the connected clients.",
+                |channel| channel.bytes_sent as f64,
+            ),
+            channel_metric(
+                "renet_channel_received_bytes",
+                "Bytes of the messages received in the channel from the connected clients.",
+                |channel| channel.bytes_received as f64,
+            ),
+            channel_metric("renet_channel_queued_messages", "Messages queued in the channel.", |channel| {
+                channel.messages_queued as f64
+            }),
+            channel_metric("renet_channel_sent_messages", "Messages sent in the channel.", |channel| {
+                channel.messages_sent as f64
+            }),
+            channel_metric("renet_channel_resent_messages", "Messages resent in the channel.", |channel| {
+                channel.messages_resent as f64
+            }),
+            channel_metric("renet_channel_acked_messages", "Messages acked in the channel.", |channel| {
+                channel.messages_acked as f64
+            }),
+            channel_metric(
+                "renet_channel_dropped_messages",
+                "Messages dropped in the channel because of their size.",
+                |channel| channel.messages_dropped as f64,
+            ),
+            channel_metric(
+                "renet_channel_discarded_messages",
+                "Unreliable messages discarded in the channel because they did not fit in the packet budget.",
+                |channel| channel.messages_discarded as f64,
+            ),
+            channel_metric("renet_channel_received_messages", "Messages received in the channel.", |channel| {
+                channel.messages_received as f64
+            }),
+            channel_metric(
+                "renet_channel_queue_depth",
+                "Messages waiting to be sent or acked in the channel.",
+                |channel| channel.queue_depth as f64,
+            ),
+            ack_time,
+        ];
+
+        if self.per_client_metrics {
+            let client_metric = |name: &'static str, help: &'static str, value: fn(&NetworkInfo) -> f64| {
+                let mut metric = Metric::new(name, help, Gauge);
+                for client in clients.iter() {
+                    metric.add(vec![("client_id", client.client_id.to_string())], value(&client.network_info));
+                }
+                metric
+            };
+            metrics.extend([
+                client_metric("renet_client_rtt_milliseconds", "Round-trip time of the client.", |info| {
+                    info.rtt as f64
+                }),
+                client_metric(
+                    "renet_client_packet_loss_ratio",
+                    "Packet loss of the client, from 0 to 1.",
+                    |info| info.packet_loss as f64,
+                ),
+                client_metric("renet_client_sent_kbps", "Kilobits per second sent to the client.", |info| {
+                    info.sent_kbps as f64
+                }),
+                client_metric(
+                    "renet_client_received_kbps",
+                    "Kilobits per second received from the client.",
+                    |info| info.received_kbps as f64,
+                ),
+            ]);
+        }
+
+        metrics
+    }
+
+    /// Returns the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        for metric in self.metrics() {
+            let kind = match metric.kind {
+                MetricKind::Gauge => "gauge",
+                MetricKind::Counter => "counter",
+            };
+            writeln!(text, "# HELP {} {}", metric.name, metric.help).unwrap();
+            writeln!(text, "# TYPE {} {}", metric.name, kind).unwrap();
+            for sample in metric.samples {
+                text.push_str(metric.name);
+                if !sample.labels.is_empty() {
+                    let labels: Vec<String> = sample.labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, value)).collect();
+                    write!(text, "{{{}}}", labels.join(",")).unwrap();
+                }
+                writeln!(text, " {}", sample.value).unwrap();
+            }
+        }
+
+        text
+    }
+
+    /// Records the metrics in the recorder installed for the [metrics](https://docs.rs/metrics) crate.
+    /// Counters are set to their absolute values, so it can be called every update.
+    #[cfg(feature = "metrics")]
+    pub fn record(&self) {
+        for metric in self.metrics() {
+            for sample in metric.samples {
+                let labels: Vec<::metrics::Label> = sample
+                    .labels
+                    .into_iter()
+                    .map(|(key, value)| ::metrics::Label::new(key, value))
+                    .collect();
+                match metric.kind {
+                    MetricKind::Gauge => ::metrics::gauge!(metric.name, labels).set(sample.value),
+                    MetricKind::Counter => ::metrics::counter!(metric.name, labels).absolute(sample.value as u64),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rechannel::remote_connection::PacketStats;
+
+    use super::*;
+
+    #[test]
+    fn prometheus_text() {
+        let metrics = ServerMetrics {
+            connected_clients: 1,
+            max_clients: 8,
+            handshake: HandshakeStats {
+                requests: 3,
+                connected: 1,
+                server_full: 2,
+                ..Default::default()
+            },
+            clients: vec![
+                ClientMetrics {
+                    client_id: 4,
+                    network_info: NetworkInfo {
+                        rtt: 20.0,
+                        packets: PacketStats {
+                            packets_lost: 5,
+                            ..Default::default()
+                        },
+                        channels: vec![ChannelStats {
+                            channel_id: 0,
+                            bytes_sent: 100,
+                            bytes_received: 50,
+                            messages_resent: 2,
+                            messages_discarded: 4,
+                            queue_depth: 3,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                },
+                ClientMetrics {
+                    client_id: 5,
+                    network_info: NetworkInfo {
+                        rtt: 10.0,
+                        packets: PacketStats {
+                            packets_lost: 1,
+                            ..Default::default()
+                        },
+                        channels: vec![ChannelStats {
+                            channel_id: 0,
+                            bytes_sent: 20,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                },
+            ],
+            ..Default::default()
+        };
+        let response = metrics.to_prometheus();
+        assert!(response.contains("# TYPE renet_connected_clients gauge\nrenet_connected_clients 1\n"));
+        assert!(response.contains("renet_handshake_requests_total 3\n"));
+        assert!(response.contains("renet_handshake_failures_total{reason=\"server_full\"} 2\n"));
+        assert!(response.contains("renet_clients_rtt_milliseconds 15\n"));
+        assert!(response.contains("renet_clients_max_rtt_milliseconds 20\n"));
+        assert!(response.contains("renet_clients_packets_lost 6\n"));
+        assert!(response.contains("renet_channel_sent_bytes{channel_id=\"0\"} 120\n"));
+        assert!(response.contains("renet_channel_resent_messages{channel_id=\"0\"} 2\n"));
+        assert!(response.contains("renet_channel_discarded_messages{channel_id=\"0\"} 4\n"));
+        assert!(response.contains("renet_channel_queue_depth{channel_id=\"0\"} 3\n"));
+        assert!(!response.contains("client_id"));
+
+        let metrics = ServerMetrics {
+            per_client_metrics: true,
+            ..metrics
+        };
+        let response = metrics.to_prometheus();
+        assert!(response.contains("renet_client_rtt_milliseconds{client_id=\"4\"} 20\n"));
+        assert!(response.contains("renet_client_rtt_milliseconds{client_id=\"5\"} 10\n"));
+    }
+}
@@ -0,0 +1,742 @@
+use crate::{NetworkInfo, RenetConnectionConfig, RenetServer, ServerConfig, ServerEvent, NETCODE_USER_DATA_BYTES};
+
+use log::error;
+use rechannel::Bytes;
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+enum ShardCommand {
+    Update {
+        duration: Duration,
+        packets: Vec<(SocketAddr, Vec<u8>)>,
+    },
+    SendPackets,
+    SendMessage {
+        client_id: u64,
+        channel_id: u8,
+        message: Bytes,
+    },
+    BroadcastMessage {
+        channel_id: u8,
+        message: Bytes,
+    },
+    BroadcastMessageExcept {
+        client_id: u64,
+        channel_id: u8,
+        message: Bytes,
+    },
+    ApproveConnection(u64),
+    DenyConnection(u64),
+    Disconnect(u64),
+    Shutdown,
+}
+
+enum ShardReply {
+    Updated(ShardUpdate),
+    PacketsSent(Result<(), io::Error>),
+}
+
+struct ShardUpdate {
+    result: Result<(), io::Error>,
+    events: Vec<ServerEvent>,
+    messages: Vec<(u64, u8, Vec<u8>)>,
+    clients: Vec<(u64, SocketAddr, NetworkInfo)>,
+    connection_requests: Vec<u64>,
+    pending_clients: Vec<u64>,
+}
+
+struct Shard {
+    commands: Sender<ShardCommand>,
+    replies: Receiver<ShardReply>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ConnectionRequest {
+    shard: usize,
+    approved: bool,
+}
+
+#[derive(Debug)]
+struct ShardedClient {
+    shard: usize,
+    addr: SocketAddr,
+    user_data: Box<[u8; NETCODE_USER_DATA_BYTES]>,
+    network_info: NetworkInfo,
+}
+
+/// A server that splits its connections across worker threads, each one owning a [RenetServer].
+///
+/// Packets are read from the socket in [ShardedRenetServer::update] and routed to a shard by the
+/// client address, the shards then decrypt and process them in parallel. Sending packets is also
+/// done in parallel by the shards. This is useful when a single server can't keep up with the
+/// encryption and serialization work of all its clients.
+///
+/// Events, messages and the clients information are synchronized with the shards on every update,
+/// so disconnections requested with [ShardedRenetServer::disconnect] are only reported in the next update.
+pub struct ShardedRenetServer {
+    socket: UdpSocket,
+    public_addr: SocketAddr,
+    max_clients: usize,
+    require_connection_approval: bool,
+    shards: Vec<Shard>,
+    clients: HashMap<u64, ShardedClient>,
+    connection_requests: HashMap<u64, ConnectionRequest>,
+    messages: HashMap<(u64, u8), VecDeque<Vec<u8>>>,
+    events: VecDeque<ServerEvent>,
+    buffer: Box<[u8]>,
+}
+
+impl ShardedRenetServer {
+    /// Creates a new server with `num_shards` worker threads.
+    /// Each shard accepts up to `max_clients`, the limit for the whole server is enforced when
+    /// the connection requests are approved.
+    pub fn new(
+        current_time: Duration,
+        mut server_config: ServerConfig,
+        connection_config: RenetConnectionConfig,
+        socket: UdpSocket,
+        num_shards: usize,
+    ) -> Result<Self, io::Error> {
+        assert!(num_shards > 0, "The sharded server needs at least one shard.");
+
+        let buffer = vec![0u8; connection_config.max_packet_size as usize].into_boxed_slice();
+        let channels_id: Vec<u8> = connection_config
+            .receive_channels_config
+            .iter()
+            .map(|config| config.channel_id())
+            .collect();
+        let public_addr = server_config.public_addr;
+        let max_clients = server_config.max_clients;
+        // The shards always wait for the approval, so duplicated client ids and a full server
+        // are denied before the clients connect.
+        let require_connection_approval = server_config.require_connection_approval;
+        server_config.require_connection_approval = true;
+
+        socket.set_nonblocking(true)?;
+
+        let mut shards = Vec::with_capacity(num_shards);
+        for index in 0..num_shards {
+            // The shards only use the socket to send packets, all the reading is done in the update.
+            let server = RenetServer::new(current_time, server_config.clone(), connection_config.clone(), socket.try_clone()?)?;
+            let (commands, commands_receiver) = mpsc::channel();
+            let (replies_sender, replies) = mpsc::channel();
+
+            let worker = ShardWorker {
+                server,
+                channels_id: channels_id.clone(),
+                commands: commands_receiver,
+                replies: replies_sender,
+            };
+            let thread = thread::Builder::new()
+                .name(format!("renet server shard {}", index))
+                .spawn(move || worker.run())?;
+
+            shards.push(Shard {
+                commands,
+                replies,
+                thread: Some(thread),
+            });
+        }
+
+        Ok(Self {
+            socket,
+            public_addr,
+            max_clients,
+            require_connection_approval,
+            shards,
+            clients: HashMap::new(),
+            connection_requests: HashMap::new(),
+            messages: HashMap::new(),
+            events: VecDeque::new(),
+            buffer,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.public_addr
+    }
+
+    /// Returns the number of shards used by the server.
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn get_event(&mut self) -> Option<ServerEvent> {
+        self.events.pop_front()
+    }
+
+    /// Approves a connection request, the request is denied if the server is already full.
+    pub fn approve_connection(&mut self, client_id: u64) {
+        let request = match self.connection_requests.get(&client_id) {
+            Some(request) if !request.approved => *request,
+            _ => return,
+        };
+
+        if self.is_full() {
+            self.connection_requests.remove(&client_id);
+            send_command(&self.shards[request.shard], ShardCommand::DenyConnection(client_id));
+            return;
+        }
+
+        self.connection_requests
+            .insert(client_id, ConnectionRequest { approved: true, ..request });
+        send_command(&self.shards[request.shard], ShardCommand::ApproveConnection(client_id));
+    }
+
+    /// Denies a connection request.
+    pub fn deny_connection(&mut self, client_id: u64) {
+        if let Some(request) = self.connection_requests.get(&client_id) {
+            if !request.approved {
+                send_command(&self.shards[request.shard], ShardCommand::DenyConnection(client_id));
+                self.connection_requests.remove(&client_id);
+            }
+        }
+    }
+
+    /// Disconnects a client.
+    pub fn disconnect(&mut self, client_id: u64) {
+        if let Some(client) = self.clients.get(&client_id) {
+            send_command(&self.shards[client.shard], ShardCommand::Disconnect(client_id));
+        }
+    }
+
+    /// Disconnects all connected clients.
+    pub fn disconnect_clients(&mut self) {
+        for (client_id, client) in self.clients.iter() {
+            send_command(&self.shards[client.shard], ShardCommand::Disconnect(*client_id));
+        }
+    }
+
+    /// Returns the client's network info if the client exits.
+    pub fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
+        self.clients.get(&client_id).map(|client| client.network_info.clone())
+    }
+
+    /// Advances the server by duration, receive packets from the network and routes them to the shards.
+    pub fn update(&mut self, duration: Duration) -> Result<(), io::Error> {
+        let mut packets: Vec<Vec<(SocketAddr, Vec<u8>)>> = (0..self.shards.len()).map(|_| Vec::new()).collect();
+        let mut result = Ok(());
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, addr)) => {
+                    let shard = shard_index(addr, self.shards.len());
+                    packets[shard].push((addr, self.buffer[..len].to_vec()));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+        }
+
+        for (shard, packets) in self.shards.iter().zip(packets) {
+            send_command(shard, ShardCommand::Update { duration, packets });
+        }
+
+        for index in 0..self.shards.len() {
+            let update = match self.shards[index].replies.recv() {
+                Ok(ShardReply::Updated(update)) => update,
+                Ok(ShardReply::PacketsSent(_)) => unreachable!("Shards only reply to the command received"),
+                Err(_) => return Err(shard_stopped_error(index)),
+            };
+            let shard_result = self.sync_shard(index, update);
+            if result.is_ok() {
+                result = shard_result;
+            }
+        }
+
+        result
+    }
+
+    /// Receive a message from a client over a channel.
+    pub fn receive_message<I: Into<u8>>(&mut self, client_id: u64, channel_id: I) -> Option<Vec<u8>> {
+        self.messages.get_mut(&(client_id, channel_id.into()))?.pop_front()
+    }
+
+    /// Send a message to a client over a channel.
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) {
+        match self.clients.get(&client_id) {
+            Some(client) => send_command(
+                &self.shards[client.shard],
+                ShardCommand::SendMessage {
+                    client_id,
+                    channel_id: channel_id.into(),
+                    message: message.into(),
+                },
+            ),
+            None => error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Send a message to all client, except the specified one, over a channel.
+    pub fn broadcast_message_except<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for shard in self.shards.iter() {
+            send_command(
+                shard,
+                ShardCommand::BroadcastMessageExcept {
+                    client_id,
+                    channel_id,
+                    message: message.clone(),
+                },
+            );
+        }
+    }
+
+    /// Send a message to all client over a channel.
+    pub fn broadcast_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for shard in self.shards.iter() {
+            send_command(
+                shard,
+                ShardCommand::BroadcastMessage {
+                    channel_id,
+                    message: message.clone(),
+                },
+            );
+        }
+    }
+
+    /// Send packets to connected clients, each shard sends the packets of its clients in parallel.
+    pub fn send_packets(&mut self) -> Result<(), io::Error> {
+        for shard in self.shards.iter() {
+            send_command(shard, ShardCommand::SendPackets);
+        }
+
+        let mut result = Ok(());
+        for (index, shard) in self.shards.iter().enumerate() {
+            let shard_result = match shard.replies.recv() {
+                Ok(ShardReply::PacketsSent(shard_result)) => shard_result,
+                Ok(ShardReply::Updated(_)) => unreachable!("Shards only reply to the command received"),
+                Err(_) => Err(shard_stopped_error(index)),
+            };
+            if result.is_ok() {
+                result = shard_result;
+            }
+        }
+
+        result
+    }
+
+    /// Returns the client address if connected.
+    pub fn client_addr(&self, client_id: u64) -> Option<SocketAddr> {
+        self.clients.get(&client_id).map(|client| client.addr)
+    }
+
+    /// Returns the user data from the connected client.
+    pub fn user_data(&self, client_id: u64) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
+        self.clients.get(&client_id).map(|client| *client.user_data)
+    }
+
+    pub fn is_client_connected(&self, client_id: u64) -> bool {
+        self.clients.contains_key(&client_id)
+    }
+
+    /// Returns all the connected clients id.
+    pub fn clients_id(&self) -> Vec<u64> {
+        self.clients.keys().copied().collect()
+    }
+
+    /// Returns the maximum number of clients that can be connected.
+    pub fn max_clients(&self) -> usize {
+        self.max_clients
+    }
+
+    /// Returns the number of connected clients.
+    pub fn connected_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Returns true if the connected clients and the approved requests fill the server.
+    fn is_full(&self) -> bool {
+        let approved = self.connection_requests.values().filter(|request| request.approved).count();
+        self.clients.len() + approved >= self.max_clients
+    }
+
+    /// Applies the update result of a shard to the server state.
+    fn sync_shard(&mut self, shard: usize, update: ShardUpdate) -> Result<(), io::Error> {
+        for event in update.events {
+            match event {
+                ServerEvent::ClientConnected(client_id, ref user_data) => {
+                    // Only approved requests connect, this should not happen.
+                    if self.clients.contains_key(&client_id) || self.clients.len() >= self.max_clients {
+                        error!("Client {} connected in shard {} without a valid approval", client_id, shard);
+                        send_command(&self.shards[shard], ShardCommand::Disconnect(client_id));
+                        continue;
+                    }
+                    let addr = match update.clients.iter().find(|(id, _, _)| *id == client_id) {
+                        Some((_, addr, _)) => *addr,
+                        // Already disconnected in the shard, the disconnect event will follow.
+                        None => continue,
+                    };
+                    self.connection_requests.remove(&client_id);
+                    self.clients.insert(
+                        client_id,
+                        ShardedClient {
+                            shard,
+                            addr,
+                            user_data: user_data.clone(),
+                            network_info: NetworkInfo::default(),
+                        },
+                    );
+                }
+                ServerEvent::ClientDisconnected { client_id, .. } => match self.clients.get(&client_id) {
+                    Some(client) if client.shard == shard => {
+                        self.clients.remove(&client_id);
+                        self.messages.retain(|(id, _), _| *id != client_id);
+                    }
+                    // A client that was rejected by this server.
+                    _ => continue,
+                },
+                ServerEvent::ConnectionRequested { client_id, .. } => {
+                    // The shards don't know about each other, so the same client id could be connected or
+                    // requesting in different shards, or the server could go over the maximum number of clients.
+                    let requested_in_other_shard = matches!(self.connection_requests.get(&client_id), Some(other) if other.shard != shard);
+                    if self.clients.contains_key(&client_id) || requested_in_other_shard || self.is_full() {
+                        send_command(&self.shards[shard], ShardCommand::DenyConnection(client_id));
+                        continue;
+                    }
+                    self.connection_requests
+                        .insert(client_id, ConnectionRequest { shard, approved: false });
+                    if !self.require_connection_approval {
+                        self.approve_connection(client_id);
+                        continue;
+                    }
+                }
+            }
+            self.events.push_back(event);
+        }
+
+        for (client_id, channel_id, message) in update.messages {
+            if matches!(self.clients.get(&client_id), Some(client) if client.shard == shard) {
+                self.messages.entry((client_id, channel_id)).or_default().push_back(message);
+            }
+        }
+
+        // Requests that expired in the shard without being approved or denied, or approved
+        // requests that did not complete the handshake.
+        let pending_requests = update.connection_requests;
+        let pending_clients = update.pending_clients;
+        self.connection_requests.retain(|client_id, request| {
+            request.shard != shard || pending_requests.contains(client_id) || (request.approved && pending_clients.contains(client_id))
+        });
+
+        for (client_id, _, network_info) in update.clients {
+            if let Some(client) = self.clients.get_mut(&client_id) {
+                if client.shard == shard {
+                    client.network_info = network_info;
+                }
+            }
+        }
+
+        update.result
+    }
+
+    /// Disconnects all clients and stops the shards threads.
+    fn shutdown(&mut self) {
+        for shard in self.shards.iter_mut() {
+            if let Some(thread) = shard.thread.take() {
+                let _ = shard.commands.send(ShardCommand::Shutdown);
+                if thread.join().is_err() {
+                    error!("Renet server shard thread panicked");
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ShardedRenetServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn shard_index(addr: SocketAddr, num_shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    addr.hash(&mut hasher);
+    (hasher.finish() % num_shards as u64) as usize
+}
+
+fn send_command(shard: &Shard, command: ShardCommand) {
+    if shard.commands.send(command).is_err() {
+        error!("Tried to send a command to a stopped renet server shard");
+    }
+}
+
+fn shard_stopped_error(index: usize) -> io::Error {
+    io::Error::other(format!("renet server shard {} stopped", index))
+}
+
+struct ShardWorker {
+    server: RenetServer,
+    channels_id: Vec<u8>,
+    commands: Receiver<ShardCommand>,
+    replies: Sender<ShardReply>,
+}
+
+impl ShardWorker {
+    fn run(mut self) {
+        while let Ok(command) = self.commands.recv() {
+            let reply = match command {
+                ShardCommand::Update { duration, packets } => ShardReply::Updated(self.update(duration, packets)),
+                ShardCommand::SendPackets => ShardReply::PacketsSent(self.server.send_packets()),
+                ShardCommand::SendMessage {
+                    client_id,
+                    channel_id,
+                    message,
+                } => {
+                    if self.server.is_client_connected(client_id) {
+                        self.server.send_message(client_id, channel_id, message);
+                    }
+                    continue;
+                }
+                ShardCommand::BroadcastMessage { channel_id, message } => {
+                    self.server.broadcast_message(channel_id, message);
+                    continue;
+                }
+                ShardCommand::BroadcastMessageExcept {
+                    client_id,
+                    channel_id,
+                    message,
+                } => {
+                    self.server.broadcast_message_except(client_id, channel_id, message);
+                    continue;
+                }
+                ShardCommand::ApproveConnection(client_id) => {
+                    self.server.approve_connection(client_id);
+                    continue;
+                }
+                ShardCommand::DenyConnection(client_id) => {
+                    self.server.deny_connection(client_id);
+                    continue;
+                }
+                ShardCommand::Disconnect(client_id) => {
+                    self.server.disconnect(client_id);
+                    continue;
+                }
+                ShardCommand::Shutdown => break,
+            };
+
+            if self.replies.send(reply).is_err() {
+                break;
+            }
+        }
+
+        self.server.disconnect_clients();
+    }
+
+    fn update(&mut self, duration: Duration, packets: Vec<(SocketAddr, Vec<u8>)>) -> ShardUpdate {
+        self.server.advance_time(duration);
+        let result = self.process_packets(packets).and_then(|_| self.server.update_connections());
+
+        let mut events = Vec::new();
+        while let Some(event) = self.server.get_event() {
+            events.push(event);
+        }
+
+        let mut messages = Vec::new();
+        let mut clients = Vec::new();
+        for client_id in self.server.clients_id() {
+            for &channel_id in self.channels_id.iter() {
+                while let Some(message) = self.server.receive_message(client_id, channel_id) {
+                    messages.push((client_id, channel_id, message));
+                }
+            }
+
+            if let (Some(addr), Some(network_info)) = (self.server.client_addr(client_id), self.server.network_info(client_id)) {
+                clients.push((client_id, addr, network_info));
+            }
+        }
+
+        ShardUpdate {
+            result,
+            events,
+            messages,
+            clients,
+            connection_requests: self.server.pending_connection_requests(),
+            pending_clients: self.server.pending_clients_id(),
+        }
+    }
+
+    fn process_packets(&mut self, packets: Vec<(SocketAddr, Vec<u8>)>) -> Result<(), io::Error> {
+        for (addr, mut packet) in packets {
+            self.server.process_packet_from(addr, &mut packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientAuthentication, ClientEvent, DefaultChannel, NetcodeDisconnectReason, RenetClient, ServerAuthentication};
+
+    #[test]
+    fn sharded_server_exchange_messages() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        let mut server = ShardedRenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket, 2).unwrap();
+
+        let mut clients: Vec<RenetClient> = (0..4)
+            .map(|client_id| {
+                let authentication = ClientAuthentication::Unsecure {
+                    protocol_id: 7,
+                    client_id,
+                    server_addresses: vec![server_addr],
+                    user_data: None,
+                    expire_seconds: 300,
+                    timeout_seconds: 15,
+                };
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                RenetClient::new(Duration::ZERO, socket, RenetConnectionConfig::default(), authentication).unwrap()
+            })
+            .collect();
+
+        let mut received_broadcast = vec![false; clients.len()];
+        let mut server_messages = vec![false; clients.len()];
+        for _ in 0..400 {
+            for (i, client) in clients.iter_mut().enumerate() {
+                client.update(Duration::from_millis(5)).unwrap();
+                if client.is_connected() {
+                    client.send_message(DefaultChannel::Reliable, "ping".as_bytes().to_vec());
+                    if let Some(message) = client.receive_message(DefaultChannel::Reliable) {
+                        assert_eq!(message, b"pong");
+                        received_broadcast[i] = true;
+                    }
+                }
+                client.send_packets().unwrap();
+            }
+
+            server.update(Duration::from_millis(5)).unwrap();
+            while server.get_event().is_some() {}
+            for client_id in server.clients_id() {
+                if let Some(message) = server.receive_message(client_id, DefaultChannel::Reliable) {
+                    assert_eq!(message, b"ping");
+                    server_messages[client_id as usize] = true;
+                }
+            }
+            if server.connected_clients() == clients.len() {
+                server.broadcast_message(DefaultChannel::Reliable, "pong".as_bytes().to_vec());
+            }
+            server.send_packets().unwrap();
+
+            if received_broadcast.iter().all(|r| *r) && server_messages.iter().all(|r| *r) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(server.connected_clients(), clients.len());
+        assert!(received_broadcast.iter().all(|r| *r));
+        assert!(server_messages.iter().all(|r| *r));
+        for client_id in 0..clients.len() as u64 {
+            assert!(server.client_addr(client_id).is_some());
+        }
+    }
+
+    #[test]
+    fn expired_connection_request() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let mut server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        server_config.require_connection_approval = true;
+        let mut server = ShardedRenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket, 2).unwrap();
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 7,
+            client_id: 0,
+            server_addresses: vec![server_addr],
+            user_data: None,
+            expire_seconds: 300,
+            timeout_seconds: 15,
+        };
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = RenetClient::new(Duration::ZERO, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+
+        let mut requested = false;
+        for _ in 0..400 {
+            client.update(Duration::from_millis(5)).unwrap();
+            client.send_packets().unwrap();
+            server.update(Duration::from_millis(5)).unwrap();
+            while let Some(event) = server.get_event() {
+                requested |= matches!(event, ServerEvent::ConnectionRequested { client_id: 0, .. });
+            }
+            if requested {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(requested);
+        assert!(server.connection_requests.contains_key(&0));
+
+        // The connect token expires and the shard drops the request
+        server.update(Duration::from_secs(3600)).unwrap();
+        assert!(server.connection_requests.is_empty());
+    }
+
+    #[test]
+    fn deny_requests_when_full() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig::new(1, 7, server_addr, ServerAuthentication::Unsecure);
+        let mut server = ShardedRenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket, 2).unwrap();
+
+        let mut clients: Vec<RenetClient> = (0..2)
+            .map(|client_id| {
+                let authentication = ClientAuthentication::Unsecure {
+                    protocol_id: 7,
+                    client_id,
+                    server_addresses: vec![server_addr],
+                    user_data: None,
+                    expire_seconds: 300,
+                    timeout_seconds: 15,
+                };
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                RenetClient::new(Duration::ZERO, socket, RenetConnectionConfig::default(), authentication).unwrap()
+            })
+            .collect();
+
+        let mut client_events = vec![vec![]; clients.len()];
+        for _ in 0..400 {
+            for (i, client) in clients.iter_mut().enumerate() {
+                let _ = client.update(Duration::from_millis(5));
+                while let Some(event) = client.get_event() {
+                    client_events[i].push(event);
+                }
+                let _ = client.send_packets();
+            }
+
+            server.update(Duration::from_millis(5)).unwrap();
+            while let Some(event) = server.get_event() {
+                assert!(!matches!(event, ServerEvent::ClientDisconnected { .. }));
+            }
+            server.send_packets().unwrap();
+
+            if clients.iter().any(|client| client.is_connected()) && clients.iter().any(|client| client.disconnected().is_some()) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(server.connected_clients(), 1);
+        let denied = ClientEvent::ConnectionFailed(NetcodeDisconnectReason::ConnectionDenied);
+        let (connected, denied): (Vec<_>, Vec<_>) = client_events.iter().partition(|events| !events.contains(&denied));
+        assert_eq!(connected.len(), 1);
+        assert_eq!(denied.len(), 1);
+        assert!(!denied[0].iter().any(|event| matches!(event, ClientEvent::Connected { .. })));
+    }
+}
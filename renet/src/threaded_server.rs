@@ -0,0 +1,415 @@
+use crate::{RenetConnectionConfig, RenetServer, ServerConfig, ServerEvent};
+
+use log::error;
+use rechannel::Bytes;
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    net::UdpSocket,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+enum Command {
+    SendMessage { client_id: u64, channel_id: u8, message: Bytes },
+    BroadcastMessage { channel_id: u8, message: Bytes },
+    BroadcastMessageExcept { client_id: u64, channel_id: u8, message: Bytes },
+    ApproveConnection(u64),
+    DenyConnection(u64),
+    Disconnect(u64),
+    DisconnectClients,
+    Shutdown,
+}
+
+struct ReceivedMessage {
+    client_id: u64,
+    channel_id: u8,
+    message: Vec<u8>,
+}
+
+/// Events and messages from the network thread, sent through the same queue so a message is
+/// never seen before the connection event of its client.
+enum NetworkUpdate {
+    Event(ServerEvent),
+    Message(ReceivedMessage),
+}
+
+/// A [RenetServer] that runs in a dedicated network thread.
+///
+/// The network thread updates the server and sends packets on its own cadence, so acks and
+/// heartbeats keep going out even when the game loop stalls. Messages and events are exchanged
+/// with the game thread through queues.
+pub struct ThreadedRenetServer {
+    commands: Sender<Command>,
+    updates_receiver: Receiver<NetworkUpdate>,
+    errors_receiver: Receiver<io::Error>,
+    events: VecDeque<ServerEvent>,
+    messages: HashMap<(u64, u8), VecDeque<Vec<u8>>>,
+    clients: HashSet<u64>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThreadedRenetServer {
+    /// Creates a new server and spawns the network thread that runs it.
+    /// The server is updated and its packets are sent every `update_interval`.
+    pub fn new(
+        current_time: Duration,
+        server_config: ServerConfig,
+        connection_config: RenetConnectionConfig,
+        socket: UdpSocket,
+        update_interval: Duration,
+    ) -> Result<Self, io::Error> {
+        let channels_id: Vec<u8> = connection_config
+            .receive_channels_config
+            .iter()
+            .map(|config| config.channel_id())
+            .collect();
+        let server = RenetServer::new(current_time, server_config, connection_config, socket)?;
+
+        let (commands, commands_receiver) = mpsc::channel();
+        let (updates_sender, updates_receiver) = mpsc::channel();
+        let (errors_sender, errors_receiver) = mpsc::channel();
+
+        let network_thread = NetworkThread {
+            server,
+            channels_id,
+            update_interval,
+            commands: commands_receiver,
+            updates: updates_sender,
+            errors: errors_sender,
+        };
+        let thread = thread::Builder::new()
+            .name("renet server".to_string())
+            .spawn(move || network_thread.run())?;
+
+        Ok(Self {
+            commands,
+            updates_receiver,
+            errors_receiver,
+            events: VecDeque::new(),
+            messages: HashMap::new(),
+            clients: HashSet::new(),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn get_event(&mut self) -> Option<ServerEvent> {
+        self.sync();
+        self.events.pop_front()
+    }
+
+    /// Returns an IO error that occurred in the network thread.
+    pub fn get_error(&mut self) -> Option<io::Error> {
+        self.errors_receiver.try_recv().ok()
+    }
+
+    /// Receive a message from a client over a channel.
+    pub fn receive_message<I: Into<u8>>(&mut self, client_id: u64, channel_id: I) -> Option<Vec<u8>> {
+        self.sync();
+        self.messages.get_mut(&(client_id, channel_id.into()))?.pop_front()
+    }
+
+    /// Send a message to a client over a channel.
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) {
+        self.send_command(Command::SendMessage {
+            client_id,
+            channel_id: channel_id.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Send a message to all client, except the specified one, over a channel.
+    pub fn broadcast_message_except<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) {
+        self.send_command(Command::BroadcastMessageExcept {
+            client_id,
+            channel_id: channel_id.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Send a message to all client over a channel.
+    pub fn broadcast_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        self.send_command(Command::BroadcastMessage {
+            channel_id: channel_id.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Approves a connection request.
+    pub fn approve_connection(&mut self, client_id: u64) {
+        self.send_command(Command::ApproveConnection(client_id));
+    }
+
+    /// Denies a connection request.
+    pub fn deny_connection(&mut self, client_id: u64) {
+        self.send_command(Command::DenyConnection(client_id));
+    }
+
+    /// Disconnects a client.
+    pub fn disconnect(&mut self, client_id: u64) {
+        self.send_command(Command::Disconnect(client_id));
+    }
+
+    /// Disconnects all connected clients.
+    pub fn disconnect_clients(&mut self) {
+        self.send_command(Command::DisconnectClients);
+    }
+
+    /// Returns all the connected clients id, as seen by the received events.
+    pub fn clients_id(&mut self) -> Vec<u64> {
+        self.sync();
+        self.clients.iter().copied().collect()
+    }
+
+    /// Returns whether the network thread is still running.
+    pub fn is_running(&self) -> bool {
+        matches!(&self.thread, Some(thread) if !thread.is_finished())
+    }
+
+    /// Disconnects all clients and stops the network thread.
+    pub fn shutdown(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = self.commands.send(Command::Shutdown);
+            if thread.join().is_err() {
+                error!("Renet server network thread panicked");
+            }
+        }
+    }
+
+    fn send_command(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            error!("Tried to send a command to a stopped renet server network thread");
+        }
+    }
+
+    /// Moves events and messages from the network thread queues to the local ones.
+    fn sync(&mut self) {
+        while let Ok(update) = self.updates_receiver.try_recv() {
+            match update {
+                NetworkUpdate::Event(event) => {
+                    match event {
+                        ServerEvent::ClientConnected(client_id, _) => {
+                            self.clients.insert(client_id);
+                        }
+                        ServerEvent::ClientDisconnected { client_id, .. } => {
+                            self.clients.remove(&client_id);
+                            self.messages.retain(|(id, _), _| *id != client_id);
+                        }
+                        ServerEvent::ConnectionRequested { .. } => {}
+                    }
+                    self.events.push_back(event);
+                }
+                NetworkUpdate::Message(received) => {
+                    if !self.clients.contains(&received.client_id) {
+                        continue;
+                    }
+                    self.messages
+                        .entry((received.client_id, received.channel_id))
+                        .or_default()
+                        .push_back(received.message);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ThreadedRenetServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+struct NetworkThread {
+    server: RenetServer,
+    channels_id: Vec<u8>,
+    update_interval: Duration,
+    commands: Receiver<Command>,
+    updates: Sender<NetworkUpdate>,
+    errors: Sender<io::Error>,
+}
+
+impl NetworkThread {
+    fn run(mut self) {
+        let mut last_updated = Instant::now();
+        loop {
+            let now = Instant::now();
+            if let Err(e) = self.server.update(now - last_updated) {
+                let _ = self.errors.send(e);
+            }
+            last_updated = now;
+
+            while let Some(event) = self.server.get_event() {
+                let _ = self.updates.send(NetworkUpdate::Event(event));
+            }
+
+            for client_id in self.server.clients_id() {
+                for &channel_id in self.channels_id.iter() {
+                    while let Some(message) = self.server.receive_message(client_id, channel_id) {
+                        let _ = self.updates.send(NetworkUpdate::Message(ReceivedMessage {
+                            client_id,
+                            channel_id,
+                            message,
+                        }));
+                    }
+                }
+            }
+
+            let shutdown = self.process_commands();
+
+            if let Err(e) = self.server.send_packets() {
+                let _ = self.errors.send(e);
+            }
+
+            if shutdown {
+                return;
+            }
+
+            thread::sleep(self.update_interval.saturating_sub(now.elapsed()));
+        }
+    }
+
+    /// Applies the commands sent from the game thread, returns true if the thread should stop.
+    fn process_commands(&mut self) -> bool {
+        loop {
+            let command = match self.commands.try_recv() {
+                Ok(command) => command,
+                Err(TryRecvError::Empty) => return false,
+                // The ThreadedRenetServer was dropped without a shutdown.
+                Err(TryRecvError::Disconnected) => Command::Shutdown,
+            };
+
+            match command {
+                Command::SendMessage {
+                    client_id,
+                    channel_id,
+                    message,
+                } => {
+                    if self.server.is_client_connected(client_id) {
+                        self.server.send_message(client_id, channel_id, message);
+                    }
+                }
+                Command::BroadcastMessage { channel_id, message } => self.server.broadcast_message(channel_id, message),
+                Command::BroadcastMessageExcept {
+                    client_id,
+                    channel_id,
+                    message,
+                } => self.server.broadcast_message_except(client_id, channel_id, message),
+                Command::ApproveConnection(client_id) => self.server.approve_connection(client_id),
+                Command::DenyConnection(client_id) => self.server.deny_connection(client_id),
+                Command::Disconnect(client_id) => self.server.disconnect(client_id),
+                Command::DisconnectClients => self.server.disconnect_clients(),
+                Command::Shutdown => {
+                    self.server.disconnect_clients();
+                    while let Some(event) = self.server.get_event() {
+                        let _ = self.updates.send(NetworkUpdate::Event(event));
+                    }
+                    return true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientAuthentication, DefaultChannel, RenetClient, ServerAuthentication};
+
+    #[test]
+    fn threaded_server_exchange_messages() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig::new(8, 7, server_addr, ServerAuthentication::Unsecure);
+        let mut server = ThreadedRenetServer::new(
+            Duration::ZERO,
+            server_config,
+            RenetConnectionConfig::default(),
+            socket,
+            Duration::from_millis(5),
+        )
+        .unwrap();
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 7,
+            client_id: 1,
+            server_addresses: vec![server_addr],
+            user_data: None,
+            expire_seconds: 300,
+            timeout_seconds: 15,
+        };
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = RenetClient::new(Duration::ZERO, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+
+        let mut client_message = None;
+        let mut server_message = None;
+        for _ in 0..400 {
+            client.update(Duration::from_millis(5)).unwrap();
+            if client.is_connected() {
+                client.send_message(DefaultChannel::Reliable, "ping".as_bytes().to_vec());
+                if let Some(message) = client.receive_message(DefaultChannel::Reliable) {
+                    client_message = Some(message);
+                }
+            }
+            client.send_packets().unwrap();
+
+            while let Some(event) = server.get_event() {
+                if let ServerEvent::ClientConnected(client_id, _) = event {
+                    server.send_message(client_id, DefaultChannel::Reliable, "pong".as_bytes().to_vec());
+                }
+            }
+            if let Some(message) = server.receive_message(1, DefaultChannel::Reliable) {
+                server_message = Some(message);
+            }
+
+            if client_message.is_some() && server_message.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(server.clients_id(), vec![1]);
+        assert_eq!(client_message.unwrap(), b"pong");
+        assert_eq!(server_message.unwrap(), b"ping");
+
+        server.shutdown();
+        assert!(!server.is_running());
+    }
+
+    #[test]
+    fn message_in_the_same_tick_as_the_connection() {
+        let (commands, _commands_receiver) = mpsc::channel();
+        let (updates, updates_receiver) = mpsc::channel();
+        let (_errors, errors_receiver) = mpsc::channel();
+        let mut server = ThreadedRenetServer {
+            commands,
+            updates_receiver,
+            errors_receiver,
+            events: VecDeque::new(),
+            messages: HashMap::new(),
+            clients: HashSet::new(),
+            thread: None,
+        };
+
+        // The first sync happens before the network thread sends anything
+        assert!(server.get_event().is_none());
+
+        updates
+            .send(NetworkUpdate::Event(ServerEvent::ClientConnected(
+                1,
+                Box::new([0; crate::NETCODE_USER_DATA_BYTES]),
+            )))
+            .unwrap();
+        updates
+            .send(NetworkUpdate::Message(ReceivedMessage {
+                client_id: 1,
+                channel_id: 0,
+                message: b"hello".to_vec(),
+            }))
+            .unwrap();
+
+        assert_eq!(server.receive_message(1, 0).unwrap(), b"hello");
+        assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected(1, _))));
+    }
+}
@@ -0,0 +1,92 @@
+use std::{error::Error, fmt};
+
+use renetcode::NETCODE_USER_DATA_BYTES;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Possible errors when writing or reading typed user data.
+#[derive(Debug)]
+pub enum UserDataError {
+    /// The serialized value does not fit in the user data.
+    TooLarge { size: usize },
+    /// The value could not be serialized or deserialized.
+    Bincode(bincode::Error),
+}
+
+impl Error for UserDataError {}
+
+impl fmt::Display for UserDataError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use UserDataError::*;
+
+        match *self {
+            TooLarge { size } => write!(
+                fmt,
+                "serialized user data has {} bytes, the maximum is {} bytes",
+                size, NETCODE_USER_DATA_BYTES
+            ),
+            Bincode(ref err) => write!(fmt, "failed to serialize or deserialize user data: {}", err),
+        }
+    }
+}
+
+impl From<bincode::Error> for UserDataError {
+    fn from(inner: bincode::Error) -> Self {
+        UserDataError::Bincode(inner)
+    }
+}
+
+/// Serializes a value into the user data of a connect token, the unused bytes are zeroed.
+///
+/// # Example
+/// ```
+/// # use renet::{from_user_data, to_user_data};
+/// let user_data = to_user_data(&("player".to_string(), 42u32)).unwrap();
+/// let (username, level): (String, u32) = from_user_data(&user_data).unwrap();
+/// assert_eq!(username, "player");
+/// assert_eq!(level, 42);
+/// ```
+pub fn to_user_data<T: Serialize>(value: &T) -> Result<[u8; NETCODE_USER_DATA_BYTES], UserDataError> {
+    let size = bincode::serialized_size(value)? as usize;
+    if size > NETCODE_USER_DATA_BYTES {
+        return Err(UserDataError::TooLarge { size });
+    }
+
+    let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+    bincode::serialize_into(&mut user_data[..], value)?;
+
+    Ok(user_data)
+}
+
+/// Deserializes a value written with [to_user_data].
+pub fn from_user_data<T: DeserializeOwned>(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Result<T, UserDataError> {
+    Ok(bincode::deserialize(user_data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Player {
+        username: String,
+        skin: u8,
+    }
+
+    #[test]
+    fn user_data_serialization() {
+        let player = Player {
+            username: "renet".to_string(),
+            skin: 3,
+        };
+        let user_data = to_user_data(&player).unwrap();
+        let result: Player = from_user_data(&user_data).unwrap();
+        assert_eq!(player, result);
+
+        let too_large = Player {
+            username: "a".repeat(NETCODE_USER_DATA_BYTES),
+            skin: 0,
+        };
+        assert!(matches!(to_user_data(&too_large), Err(UserDataError::TooLarge { .. })));
+    }
+}
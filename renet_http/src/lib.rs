@@ -0,0 +1,275 @@
+//! Minimal HTTP/1.1 server shared by the renet services.
+//!
+//! Each connection is handled in its own thread, up to [MAX_CONNECTIONS] at the same time. The
+//! request line and each header are limited to [MAX_LINE_BYTES], all the headers to
+//! [MAX_HEADERS_BYTES], and the whole request must be received in [REQUEST_DEADLINE].
+//! Only one request is handled per connection.
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::error;
+
+/// The maximum number of bytes of the request line and of each header.
+pub const MAX_LINE_BYTES: usize = 8192;
+/// The maximum number of bytes of all the headers.
+pub const MAX_HEADERS_BYTES: usize = 16 * 1024;
+/// The maximum time to receive the whole request.
+pub const REQUEST_DEADLINE: Duration = Duration::from_secs(5);
+/// The maximum number of connections handled at the same time, new connections are closed.
+pub const MAX_CONNECTIONS: usize = 64;
+
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(10);
+
+/// Request with the method, path and body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+/// Response with the status code, the content type and the body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Serves the requests from the listener with the handler, malformed requests or requests with a
+/// body bigger than `max_body_bytes` get a 400 response without calling the handler.
+/// Blocks until the listener fails with an error it can't recover from, or until the next
+/// connection after `shutdown` is set. Other errors accepting connections are logged.
+pub fn serve<F>(listener: TcpListener, max_body_bytes: usize, shutdown: &AtomicBool, handler: F) -> io::Result<()>
+where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) if is_fatal_accept_error(&e) => return Err(e),
+            Err(e) => {
+                // Errors like running out of file descriptors can last, wait before accepting again
+                error!("Failed to accept HTTP connection: {}", e);
+                thread::sleep(ACCEPT_ERROR_DELAY);
+                continue;
+            }
+        };
+        if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            continue;
+        }
+
+        let handler = handler.clone();
+        let thread_connections = connections.clone();
+        let spawned = thread::Builder::new().name("renet http connection".to_string()).spawn(move || {
+            if let Err(e) = handle_connection(stream, max_body_bytes, handler.as_ref()) {
+                error!("Failed to handle HTTP request: {}", e);
+            }
+            thread_connections.fetch_sub(1, Ordering::Relaxed);
+        });
+        if let Err(e) = spawned {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            error!("Failed to spawn HTTP connection thread: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+// Errors from a listener that is not valid anymore, the others are from a single connection or transient
+fn is_fatal_accept_error(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported)
+}
+
+fn handle_connection(mut stream: TcpStream, max_body_bytes: usize, handler: &dyn Fn(Request) -> Response) -> io::Result<()> {
+    stream.set_write_timeout(Some(REQUEST_DEADLINE))?;
+    let deadline = Instant::now() + REQUEST_DEADLINE;
+    let response = match read_request(&stream, deadline, max_body_bytes)? {
+        Some(request) => handler(request),
+        None => Response::new(400, "text/plain", "invalid request"),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// Reads from the stream until the deadline, each read waits at most the remaining time.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request deadline exceeded"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Reads the request, returns None if the request is malformed or too big.
+fn read_request(stream: &TcpStream, deadline: Instant, max_body_bytes: usize) -> io::Result<Option<Request>> {
+    let mut reader = BufReader::new(DeadlineReader { stream, deadline });
+    let request_line = match read_line(&mut reader)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Ok(None),
+    };
+
+    let mut headers_bytes = 0;
+    let mut content_length = 0;
+    loop {
+        let header = match read_line(&mut reader)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        if header.is_empty() {
+            break;
+        }
+        headers_bytes += header.len();
+        if headers_bytes > MAX_HEADERS_BYTES {
+            return Ok(None);
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(length) => content_length = length,
+                    Err(_) => return Ok(None),
+                }
+            }
+        }
+    }
+
+    if content_length > max_body_bytes {
+        return Ok(None);
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Some(Request { method, path, body }))
+}
+
+/// Reads a line without the line ending, returns None if it has more than [MAX_LINE_BYTES]
+/// or the stream ended before the line ending.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    reader.by_ref().take(MAX_LINE_BYTES as u64).read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Ok(None);
+    }
+
+    Ok(Some(line.trim_end().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    fn start_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let shutdown = AtomicBool::new(false);
+            serve(listener, 16, &shutdown, |request| {
+                let body = format!("{} {} {}", request.method, request.path, String::from_utf8(request.body).unwrap());
+                Response::new(200, "text/plain", body)
+            })
+        });
+
+        addr
+    }
+
+    fn send(addr: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn request_limits() {
+        let addr = start_server();
+
+        let response = send(addr, b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("POST /echo hello"));
+
+        let response = send(addr, b"POST /echo HTTP/1.1\r\nContent-Length: 17\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+
+        // The requests are only what the server reads before rejecting them, unread data would reset the connection
+        let long_line = format!("GET /{}", "a".repeat(MAX_LINE_BYTES - 5));
+        let response = send(addr, long_line.as_bytes());
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+
+        let header = format!("X-Header: {}\r\n", "a".repeat(MAX_LINE_BYTES / 2));
+        let many_headers = format!("GET / HTTP/1.1\r\n{}", header.repeat(4));
+        let response = send(addr, many_headers.as_bytes());
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn slow_request_does_not_block_others() {
+        let addr = start_server();
+
+        let mut slow_stream = TcpStream::connect(addr).unwrap();
+        slow_stream.write_all(b"GET /slow HTTP/1.1\r\n").unwrap();
+
+        let response = send(addr, b"GET /fast HTTP/1.1\r\n\r\n");
+        assert!(response.ends_with("GET /fast "));
+    }
+}
@@ -0,0 +1,347 @@
+//! Decodes renet packets from captures written by a [PacketRecorder][renet::PacketRecorder] or from hex dumps.
+//!
+//! The netcode packet type and sequence are read from the prefix, the packets are decrypted with the
+//! given keys, and the payloads are decoded as rechannel packets with the channels configuration.
+//! With the server private key, the packet keys are read from the connect tokens in the connection requests.
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read, Write},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use rechannel::inspect::{self as rechannel_inspect, InspectedChannel, InspectedMessage, InspectedPacket};
+use renet::{CaptureReader, CaptureRecord, ChannelConfig, DefaultChannel, NETCODE_KEY_BYTES};
+use renetcode::inspect::{self as netcode_inspect, Packet, PacketType};
+
+/// Configuration to decode the packets.
+#[derive(Debug, Clone)]
+pub struct InspectConfig {
+    /// Keys tried to decrypt the packets, client to server or server to client keys.
+    pub keys: Vec<[u8; NETCODE_KEY_BYTES]>,
+    /// Server private key, decrypts the connection requests to find the keys of the connections.
+    pub private_key: Option<[u8; NETCODE_KEY_BYTES]>,
+    /// Protocol id of the packets, when not set it's read from the first connection request.
+    pub protocol_id: Option<u64>,
+    /// Channels used to decode the messages.
+    pub channels_config: Vec<ChannelConfig>,
+}
+
+/// Where a captured packet came from.
+#[derive(Debug, Clone, Copy)]
+pub struct PacketOrigin {
+    pub current_time: Duration,
+    pub addr: SocketAddr,
+    /// True if the packet was received by the recorder, false if it was sent.
+    pub received: bool,
+}
+
+// Fragments are grouped by address and direction, hex dumps have no origin.
+type FragmentsKey = (Option<(SocketAddr, bool)>, u16);
+
+/// Decodes packets and writes a description of them to the output.
+pub struct Inspector<W> {
+    config: InspectConfig,
+    out: W,
+    keys: Vec<[u8; NETCODE_KEY_BYTES]>,
+    protocol_id: Option<u64>,
+    fragments: HashMap<FragmentsKey, Vec<Option<Vec<u8>>>>,
+}
+
+impl Default for InspectConfig {
+    fn default() -> Self {
+        Self {
+            keys: vec![],
+            private_key: None,
+            protocol_id: None,
+            channels_config: DefaultChannel::config(),
+        }
+    }
+}
+
+impl<W: Write> Inspector<W> {
+    pub fn new(config: InspectConfig, out: W) -> Self {
+        Self {
+            keys: config.keys.clone(),
+            protocol_id: config.protocol_id,
+            config,
+            out,
+            fragments: HashMap::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Decodes all the packets of a capture.
+    pub fn inspect_capture<R: Read>(&mut self, capture: R) -> Result<(), io::Error> {
+        for record in CaptureReader::new(capture)? {
+            match record? {
+                CaptureRecord::Start { current_time } => writeln!(self.out, "capture started at {:.3}s", current_time.as_secs_f64())?,
+                CaptureRecord::ChallengeKey(_) => writeln!(self.out, "server challenge key")?,
+                CaptureRecord::Update { .. } => {}
+                CaptureRecord::Received {
+                    current_time,
+                    addr,
+                    payload,
+                } => {
+                    let origin = PacketOrigin {
+                        current_time,
+                        addr,
+                        received: true,
+                    };
+                    self.inspect_packet(&payload, Some(origin))?;
+                }
+                CaptureRecord::Sent {
+                    current_time,
+                    addr,
+                    payload,
+                } => {
+                    let origin = PacketOrigin {
+                        current_time,
+                        addr,
+                        received: false,
+                    };
+                    self.inspect_packet(&payload, Some(origin))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes a hex dump with one packet per line, whitespace between the bytes is ignored
+    /// and lines starting with `#` are skipped.
+    pub fn inspect_hex_dump<R: BufRead>(&mut self, dump: R) -> Result<(), io::Error> {
+        for (i, line) in dump.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_hex(line) {
+                Some(packet) => self.inspect_packet(&packet, None)?,
+                None => writeln!(self.out, "line {}: invalid hex", i + 1)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes a netcode packet and its payload.
+    pub fn inspect_packet(&mut self, packet: &[u8], origin: Option<PacketOrigin>) -> Result<(), io::Error> {
+        if let Some(origin) = origin {
+            let direction = if origin.received { "recv from" } else { "sent to" };
+            write!(self.out, "{:.3}s {} {} ", origin.current_time.as_secs_f64(), direction, origin.addr)?;
+        }
+
+        let header = match netcode_inspect::read_header(packet) {
+            Ok(header) => header,
+            Err(e) => return writeln!(self.out, "invalid netcode packet ({} bytes): {}", packet.len(), e),
+        };
+        let unencrypted = matches!(
+            header.packet_type,
+            PacketType::ConnectionRequest | PacketType::Ping | PacketType::Pong
+        );
+        write!(self.out, "{:?} prefix=0x{:02x}", header.packet_type, header.prefix)?;
+        if !unencrypted {
+            write!(self.out, " seq={}", header.sequence)?;
+        }
+        write!(self.out, " ({} bytes)", packet.len())?;
+
+        if matches!(header.packet_type, PacketType::ConnectionRequest) {
+            return self.inspect_connection_request(packet);
+        }
+        if unencrypted {
+            return self.inspect_ping(packet);
+        }
+
+        let protocol_id = match self.protocol_id {
+            Some(protocol_id) => protocol_id,
+            None => return writeln!(self.out, " [unknown protocol id]"),
+        };
+        let mut buffer = packet.to_vec();
+        let decrypted = self.keys.iter().find_map(|key| {
+            buffer.copy_from_slice(packet);
+            netcode_inspect::decode_packet(&mut buffer, protocol_id, key).ok().map(|_| *key)
+        });
+        let key = match decrypted {
+            Some(key) => key,
+            None => return writeln!(self.out, " [not decrypted]"),
+        };
+        buffer.copy_from_slice(packet);
+        let packet = netcode_inspect::decode_packet(&mut buffer, protocol_id, &key).expect("packet was already decrypted with the key");
+
+        match packet {
+            Packet::ConnectionDenied(reason) | Packet::Disconnect(reason) => match reason {
+                Some(reason) => writeln!(self.out, " reason: {}", reason),
+                None => writeln!(self.out),
+            },
+            Packet::Challenge { token_sequence, .. } | Packet::Response { token_sequence, .. } => {
+                writeln!(self.out, " token_seq={}", token_sequence)
+            }
+            Packet::KeepAlive { client_index, max_clients } => {
+                writeln!(self.out, " client_index={} max_clients={}", client_index, max_clients)
+            }
+            Packet::Payload(payload) => {
+                writeln!(self.out)?;
+                let fragments_key = origin.map(|origin| (origin.addr, origin.received));
+                self.inspect_payload(payload, fragments_key)
+            }
+            Packet::ConnectionRequest { .. } | Packet::Ping { .. } | Packet::Pong { .. } => unreachable!(),
+        }
+    }
+
+    fn inspect_ping(&mut self, packet: &[u8]) -> Result<(), io::Error> {
+        let mut buffer = packet.to_vec();
+        // Pings are not encrypted, the key and protocol id are not used.
+        match netcode_inspect::decode_packet(&mut buffer, 0, &[0; NETCODE_KEY_BYTES]) {
+            Ok(Packet::Ping { protocol_id, sequence, .. } | Packet::Pong { protocol_id, sequence, .. }) => {
+                writeln!(self.out, " protocol_id={} ping_seq={}", protocol_id, sequence)
+            }
+            Ok(_) => unreachable!(),
+            Err(e) => writeln!(self.out, " invalid: {}", e),
+        }
+    }
+
+    fn inspect_connection_request(&mut self, packet: &[u8]) -> Result<(), io::Error> {
+        let mut buffer = packet.to_vec();
+        // Connection requests are not encrypted, the key and protocol id are not used.
+        let request = match netcode_inspect::decode_packet(&mut buffer, 0, &[0; NETCODE_KEY_BYTES]) {
+            Ok(request) => request,
+            Err(e) => return writeln!(self.out, " invalid: {}", e),
+        };
+        let (protocol_id, expire_timestamp) = match request {
+            Packet::ConnectionRequest {
+                protocol_id,
+                expire_timestamp,
+                ..
+            } => (protocol_id, expire_timestamp),
+            _ => unreachable!(),
+        };
+        write!(self.out, " protocol_id={} expire_timestamp={}", protocol_id, expire_timestamp)?;
+        if self.protocol_id.is_none() {
+            self.protocol_id = Some(protocol_id);
+        }
+
+        if let Some(private_key) = self.config.private_key {
+            match netcode_inspect::decrypt_connection_request(&request, &private_key) {
+                Ok(keys) => {
+                    write!(self.out, " client_id={}", keys.client_id)?;
+                    for key in [keys.client_to_server_key, keys.server_to_client_key] {
+                        if !self.keys.contains(&key) {
+                            self.keys.push(key);
+                        }
+                    }
+                }
+                Err(e) => write!(self.out, " [connect token not decrypted: {}]", e)?,
+            }
+        }
+
+        writeln!(self.out)
+    }
+
+    fn inspect_payload(&mut self, payload: &[u8], fragments_origin: Option<(SocketAddr, bool)>) -> Result<(), io::Error> {
+        let packet = match rechannel_inspect::decode_packet(payload, &self.config.channels_config) {
+            Ok(packet) => packet,
+            Err(e) => return writeln!(self.out, "    invalid rechannel packet: {}", e),
+        };
+
+        match packet {
+            InspectedPacket::Normal {
+                sequence,
+                ack,
+                ack_bits,
+                channels,
+            } => {
+                writeln!(self.out, "    Normal seq={} ack={} ack_bits={:032b}", sequence, ack, ack_bits)?;
+                self.write_channels(&channels)
+            }
+            InspectedPacket::Fragment {
+                sequence,
+                ack,
+                ack_bits,
+                fragment_id,
+                num_fragments,
+                payload,
+            } => {
+                writeln!(
+                    self.out,
+                    "    Fragment seq={} ack={} ack_bits={:032b} fragment={}/{} ({} bytes)",
+                    sequence,
+                    ack,
+                    ack_bits,
+                    fragment_id,
+                    num_fragments,
+                    payload.len()
+                )?;
+                self.add_fragment((fragments_origin, sequence), fragment_id, num_fragments, payload)
+            }
+            InspectedPacket::Heartbeat { ack, ack_bits } => writeln!(self.out, "    Heartbeat ack={} ack_bits={:032b}", ack, ack_bits),
+            InspectedPacket::Disconnect { reason } => writeln!(self.out, "    Disconnect reason: {}", reason),
+        }
+    }
+
+    fn add_fragment(&mut self, key: FragmentsKey, fragment_id: u8, num_fragments: u8, payload: Vec<u8>) -> Result<(), io::Error> {
+        let fragments = self.fragments.entry(key).or_insert_with(|| vec![None; num_fragments as usize]);
+        if fragments.len() != num_fragments as usize || fragment_id >= num_fragments {
+            return writeln!(self.out, "    invalid fragment");
+        }
+        fragments[fragment_id as usize] = Some(payload);
+        if fragments.iter().any(|fragment| fragment.is_none()) {
+            return Ok(());
+        }
+
+        let fragments = self.fragments.remove(&key).unwrap();
+        let reassembled: Vec<u8> = fragments.into_iter().flatten().flatten().collect();
+        match rechannel_inspect::decode_reassembled(&reassembled, &self.config.channels_config) {
+            Ok(channels) => {
+                writeln!(self.out, "    Reassembled seq={} ({} bytes)", key.1, reassembled.len())?;
+                self.write_channels(&channels)
+            }
+            Err(e) => writeln!(self.out, "    invalid reassembled packet seq={}: {}", key.1, e),
+        }
+    }
+
+    fn write_channels(&mut self, channels: &[InspectedChannel]) -> Result<(), io::Error> {
+        for channel in channels {
+            write!(self.out, "      channel {}:", channel.channel_id)?;
+            for message in channel.messages.iter() {
+                match message {
+                    InspectedMessage::Reliable { message_id, len } => write!(self.out, " [reliable id={} {}B]", message_id, len)?,
+                    InspectedMessage::Unreliable { len } => write!(self.out, " [unreliable {}B]", len)?,
+                    InspectedMessage::Slice {
+                        chunk_id,
+                        slice_id,
+                        num_slices,
+                        len,
+                    } => write!(self.out, " [slice chunk={} slice={}/{} {}B]", chunk_id, slice_id, num_slices, len)?,
+                    InspectedMessage::Unknown { len } => write!(self.out, " [unknown {}B]", len)?,
+                }
+            }
+            writeln!(self.out)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a hex string, whitespace between the bytes is ignored.
+pub fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let pairs = digits.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+
+    pairs
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Parses a key from a hex string.
+pub fn parse_key(hex: &str) -> Option<[u8; NETCODE_KEY_BYTES]> {
+    parse_hex(hex)?.try_into().ok()
+}
@@ -0,0 +1,104 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use renet::{BlockChannelConfig, ChannelConfig, ReliableChannelConfig, UnreliableChannelConfig};
+use renet_inspect::{parse_key, InspectConfig, Inspector};
+
+const USAGE: &str = "usage: renet-inspect [options] <capture or hex dump file, - for stdin>
+
+options:
+    --key <hex>           key to decrypt the packets, can be repeated
+    --private-key <hex>   server private key, finds the keys in the connection requests
+    --protocol-id <id>    protocol id, read from the connection requests when not set
+    --channels <types>    channel types by id, separated by commas (default: reliable,unreliable,block)";
+
+const CAPTURE_PREFIX: &[u8] = b"RENETCAP";
+
+fn main() {
+    let mut config = InspectConfig::default();
+    let mut input = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => config.keys.push(parse_arg(args.next(), parse_key)),
+            "--private-key" => config.private_key = Some(parse_arg(args.next(), parse_key)),
+            "--protocol-id" => config.protocol_id = Some(parse_arg(args.next(), |id| id.parse().ok())),
+            "--channels" => config.channels_config = parse_arg(args.next(), parse_channels),
+            "-h" | "--help" => exit_with_usage(),
+            _ if input.is_none() && !arg.starts_with("--") => input = Some(arg),
+            _ => exit_with_usage(),
+        }
+    }
+
+    let input = input.unwrap_or_else(|| exit_with_usage());
+    let reader: Box<dyn BufRead> = if input == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        match File::open(&input) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("failed to open {}: {}", input, e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let mut inspector = Inspector::new(config, io::stdout().lock());
+    if let Err(e) = inspect(&mut inspector, reader) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn inspect(inspector: &mut Inspector<io::StdoutLock>, mut reader: Box<dyn BufRead>) -> Result<(), io::Error> {
+    // Captures start with the prefix, other inputs are read as hex dumps
+    if reader.fill_buf()?.starts_with(CAPTURE_PREFIX) {
+        inspector.inspect_capture(reader)
+    } else {
+        inspector.inspect_hex_dump(reader)
+    }
+}
+
+fn parse_channels(types: &str) -> Option<Vec<ChannelConfig>> {
+    types
+        .split(',')
+        .enumerate()
+        .map(|(channel_id, channel_type)| {
+            let channel_id = channel_id as u8;
+            let config = match channel_type.trim() {
+                "reliable" => ReliableChannelConfig {
+                    channel_id,
+                    ..Default::default()
+                }
+                .into(),
+                "unreliable" => UnreliableChannelConfig {
+                    channel_id,
+                    ..Default::default()
+                }
+                .into(),
+                "block" => BlockChannelConfig {
+                    channel_id,
+                    ..Default::default()
+                }
+                .into(),
+                _ => return None,
+            };
+            Some(config)
+        })
+        .collect()
+}
+
+fn parse_arg<T>(value: Option<String>, parse: impl Fn(&str) -> Option<T>) -> T {
+    match value.as_deref().and_then(parse) {
+        Some(value) => value,
+        None => exit_with_usage(),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
@@ -0,0 +1,116 @@
+use std::{
+    io::{self, Write},
+    net::UdpSocket,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use renet::{
+    ClientAuthentication, ConnectToken, DefaultChannel, PacketRecorder, RenetClient, RenetConnectionConfig, RenetServer,
+    ServerAuthentication, ServerConfig,
+};
+use renet_inspect::{parse_hex, parse_key, InspectConfig, Inspector};
+
+const PROTOCOL_ID: u64 = 7;
+const PRIVATE_KEY: &[u8; 32] = b"an example very very secret key.";
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn record_server_capture() -> Vec<u8> {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let authentication = ServerAuthentication::Secure {
+        private_key: *PRIVATE_KEY,
+        previous_private_keys: vec![],
+    };
+    let server_config = ServerConfig::new(8, PROTOCOL_ID, server_addr, authentication);
+    let mut server = RenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap();
+    let capture = SharedBuffer::default();
+    server.set_recorder(Some(PacketRecorder::new(capture.clone()).unwrap()));
+
+    let connect_token = ConnectToken::builder()
+        .protocol_id(PROTOCOL_ID)
+        .client_id(3)
+        .server_address(server_addr)
+        .generate(Duration::ZERO, PRIVATE_KEY)
+        .unwrap();
+    let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let authentication = ClientAuthentication::Secure { connect_token };
+    let mut client = RenetClient::new(Duration::ZERO, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+
+    let mut sent_message = false;
+    for _ in 0..100 {
+        client.update(Duration::from_millis(10)).unwrap();
+        server.update(Duration::from_millis(10)).unwrap();
+        if client.is_connected() && !sent_message {
+            client.send_message(DefaultChannel::Reliable, vec![1, 2, 3]);
+            sent_message = true;
+        }
+        client.send_packets().unwrap();
+        server.send_packets().unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(sent_message);
+
+    let capture = capture.0.lock().unwrap().clone();
+    capture
+}
+
+#[test]
+fn inspect_capture_with_private_key() {
+    let capture = record_server_capture();
+
+    let config = InspectConfig {
+        private_key: Some(*PRIVATE_KEY),
+        ..Default::default()
+    };
+    let mut inspector = Inspector::new(config, vec![]);
+    inspector.inspect_capture(&capture[..]).unwrap();
+    let output = String::from_utf8(inspector.into_inner()).unwrap();
+
+    assert!(output.contains("ConnectionRequest prefix=0x00"));
+    assert!(output.contains("protocol_id=7"));
+    assert!(output.contains("client_id=3"));
+    assert!(output.contains("Challenge"));
+    assert!(output.contains("KeepAlive"));
+    assert!(output.contains("Payload"));
+    assert!(output.contains("channel 0: [reliable id=0 3B]"));
+    assert!(!output.contains("[not decrypted]"));
+
+    let mut inspector = Inspector::new(InspectConfig::default(), vec![]);
+    inspector.inspect_capture(&capture[..]).unwrap();
+    let output = String::from_utf8(inspector.into_inner()).unwrap();
+    assert!(output.contains("[not decrypted]"));
+    assert!(!output.contains("reliable id="));
+}
+
+#[test]
+fn inspect_hex_dump() {
+    let mut inspector = Inspector::new(InspectConfig::default(), vec![]);
+    let dump = "# comment\n15 01 000102030405060708090a0b0c0d0e0f10\nzz\n";
+    inspector.inspect_hex_dump(dump.as_bytes()).unwrap();
+    let output = String::from_utf8(inspector.into_inner()).unwrap();
+
+    assert!(output.contains("Payload prefix=0x15 seq=1 (19 bytes) [unknown protocol id]"));
+    assert!(output.contains("line 3: invalid hex"));
+}
+
+#[test]
+fn parse_hex_and_keys() {
+    assert_eq!(parse_hex("00 ff 1a"), Some(vec![0x00, 0xff, 0x1a]));
+    assert_eq!(parse_hex("0"), None);
+    assert_eq!(parse_hex("zz"), None);
+    assert_eq!(parse_key(&"ab".repeat(32)), Some([0xab; 32]));
+    assert_eq!(parse_key("ab"), None);
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crate::{packet::RelayPacket, SessionToken, RELAY_MAX_PACKET_BYTES};
+
+const RELAY_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const RELAY_REGISTER_INTERVAL: Duration = Duration::from_millis(250);
+const RELAY_CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct ClientProxy {
+    socket: UdpSocket,
+    last_received_time: Duration,
+}
+
+/// Connects a game server behind NAT to a relay.
+///
+/// Each client forwarded by the relay gets a local socket that exchanges its packets with the game
+/// server, so the server sees each client as a different local address. The server should use
+/// [RelayHost::public_addr] as its public address, that is the address in the connect tokens.
+pub struct RelayHost {
+    socket: UdpSocket,
+    relay_addr: SocketAddr,
+    server_addr: SocketAddr,
+    public_addr: SocketAddr,
+    session_id: u64,
+    current_time: Duration,
+    last_keep_alive_time: Duration,
+    clients: HashMap<SocketAddr, ClientProxy>,
+    buffer: Box<[u8; RELAY_MAX_PACKET_BYTES]>,
+    out: Box<[u8; RELAY_MAX_PACKET_BYTES]>,
+}
+
+impl RelayHost {
+    /// Opens a session in the relay for the game server in `server_addr` with the session token.
+    /// Blocks until the relay responds with the public address, or the timeout.
+    /// The relay doesn't respond when the token is invalid or the session is open for another host.
+    pub fn connect(relay_addr: SocketAddr, server_addr: SocketAddr, token: SessionToken, timeout: Duration) -> Result<Self, io::Error> {
+        let socket = UdpSocket::bind(unspecified_addr(relay_addr))?;
+        let mut buffer = Box::new([0u8; RELAY_MAX_PACKET_BYTES]);
+        let mut out = Box::new([0u8; RELAY_MAX_PACKET_BYTES]);
+        let len = RelayPacket::Register(token)
+            .encode(&mut out[..])
+            .expect("register packet fits in the buffer");
+
+        let deadline = Instant::now() + timeout;
+        let public_addr = 'register: loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "relay did not respond to the register"));
+            }
+            socket.send_to(&out[..len], relay_addr)?;
+            socket.set_read_timeout(Some(remaining.min(RELAY_REGISTER_INTERVAL)))?;
+
+            loop {
+                let (len, addr) = match socket.recv_from(&mut buffer[..]) {
+                    Ok(result) => result,
+                    Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                    Err(e) => return Err(e),
+                };
+                if addr != relay_addr {
+                    continue;
+                }
+                if let Some(RelayPacket::Registered(mut public_addr)) = RelayPacket::decode(&buffer[..len]) {
+                    if public_addr.ip().is_unspecified() {
+                        public_addr.set_ip(relay_addr.ip());
+                    }
+                    break 'register public_addr;
+                }
+            }
+        };
+        socket.set_read_timeout(None)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            relay_addr,
+            server_addr,
+            public_addr,
+            session_id: token.session_id(),
+            current_time: Duration::ZERO,
+            last_keep_alive_time: Duration::ZERO,
+            clients: HashMap::new(),
+            buffer,
+            out,
+        })
+    }
+
+    /// Returns the address in the relay that clients connect to.
+    pub fn public_addr(&self) -> SocketAddr {
+        self.public_addr
+    }
+
+    /// Returns the number of clients being forwarded.
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Advances the host by the duration, and forwards the packets between the relay and the game server.
+    pub fn update(&mut self, duration: Duration) -> Result<(), io::Error> {
+        self.current_time += duration;
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut self.buffer[..]) {
+                Ok(result) => result,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+            if addr != self.relay_addr {
+                continue;
+            }
+
+            if let Some(RelayPacket::Forward { session_id, addr, payload }) = RelayPacket::decode(&self.buffer[..len]) {
+                if session_id != self.session_id {
+                    continue;
+                }
+                let client = match self.clients.get_mut(&addr) {
+                    Some(client) => client,
+                    None => {
+                        let socket = UdpSocket::bind(unspecified_addr(self.server_addr))?;
+                        socket.set_nonblocking(true)?;
+                        self.clients.entry(addr).or_insert(ClientProxy {
+                            socket,
+                            last_received_time: self.current_time,
+                        })
+                    }
+                };
+                client.last_received_time = self.current_time;
+                client.socket.send_to(payload, self.server_addr)?;
+            }
+        }
+
+        for (client_addr, client) in self.clients.iter() {
+            loop {
+                let (len, addr) = match client.socket.recv_from(&mut self.buffer[..]) {
+                    Ok(result) => result,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e),
+                };
+                if addr != self.server_addr {
+                    continue;
+                }
+
+                let packet = RelayPacket::Forward {
+                    session_id: self.session_id,
+                    addr: *client_addr,
+                    payload: &self.buffer[..len],
+                };
+                if let Some(len) = packet.encode(&mut self.out[..]) {
+                    self.socket.send_to(&self.out[..len], self.relay_addr)?;
+                }
+            }
+        }
+
+        if self.current_time - self.last_keep_alive_time >= RELAY_KEEP_ALIVE_INTERVAL {
+            self.last_keep_alive_time = self.current_time;
+            let len = RelayPacket::KeepAlive {
+                session_id: self.session_id,
+            }
+            .encode(&mut self.out[..])
+            .expect("keep alive packet fits in the buffer");
+            self.socket.send_to(&self.out[..len], self.relay_addr)?;
+        }
+
+        let current_time = self.current_time;
+        self.clients
+            .retain(|_, client| current_time - client.last_received_time < RELAY_CLIENT_TIMEOUT);
+
+        Ok(())
+    }
+}
+
+// Address to bind a socket that can reach the address
+fn unspecified_addr(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V4(addr) if addr.ip().is_loopback() => (Ipv4Addr::LOCALHOST, 0).into(),
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    }
+}
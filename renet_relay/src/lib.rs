@@ -0,0 +1,21 @@
+//! Relay that forwards netcode packets between clients and a game server behind NAT.
+//!
+//! The game server, usually hosted by one of the players, opens a session in a public relay with
+//! [RelayHost]. Clients connect to the session address in the relay, and the [RelayServer] forwards
+//! the packets to the host without decrypting them.
+//!
+//! Hosts need a [SessionToken] signed with the relay private key to open a session, so only the
+//! hosts authorized by the game backend can use the relay.
+mod host;
+mod packet;
+mod relay;
+mod token;
+
+pub use host::RelayHost;
+pub use relay::{RelayConfig, RelayServer};
+pub use token::{SessionToken, SESSION_TOKEN_BYTES};
+
+/// The number of bytes of the relay private key, used to sign the session tokens.
+pub const RELAY_KEY_BYTES: usize = 32;
+/// The maximum number of bytes of a relay packet, netcode packets are smaller than this.
+pub const RELAY_MAX_PACKET_BYTES: usize = 1500;
@@ -0,0 +1,58 @@
+use std::{
+    net::{IpAddr, SocketAddr, UdpSocket},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use renet_relay::{RelayConfig, RelayServer, RELAY_KEY_BYTES};
+
+const USAGE: &str = "usage: RENET_RELAY_KEY=<base64 private key> renet_relay <bind address> [public ip]";
+
+fn main() {
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let bind_addr: SocketAddr = match args.next().map(|arg| arg.parse()) {
+        Some(Ok(addr)) => addr,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let public_ip: Option<IpAddr> = match args.next().map(|arg| arg.parse()) {
+        None => None,
+        Some(Ok(ip)) => Some(ip),
+        Some(Err(_)) => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let private_key: [u8; RELAY_KEY_BYTES] = match std::env::var("RENET_RELAY_KEY").ok().and_then(|key| base64::decode(key).ok()) {
+        Some(key) if key.len() == RELAY_KEY_BYTES => key.try_into().unwrap(),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let socket = UdpSocket::bind(bind_addr).expect("failed to bind the relay socket");
+    let config = RelayConfig {
+        public_ip,
+        ..Default::default()
+    };
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let mut relay = RelayServer::new(current_time, socket, &private_key, config).unwrap();
+    // Printed so the address can be read when binding to port 0
+    println!("Relay listening on {}", relay.addr().unwrap());
+
+    let mut last_updated = Instant::now();
+    loop {
+        let now = Instant::now();
+        if let Err(e) = relay.update(now - last_updated) {
+            log::error!("relay error: {}", e);
+        }
+        last_updated = now;
+        thread::sleep(Duration::from_millis(1));
+    }
+}
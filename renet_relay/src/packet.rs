@@ -0,0 +1,181 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::{token::SESSION_TOKEN_BYTES, SessionToken, RELAY_MAX_PACKET_BYTES};
+
+const RELAY_PREFIX: &[u8; 4] = b"RRLY";
+const RELAY_ADDRESS_IPV4: u8 = 4;
+const RELAY_ADDRESS_IPV6: u8 = 6;
+// Prefix, packet type, session id and the largest address
+const RELAY_HEADER_BYTES: usize = 4 + 1 + 8 + 1 + 16 + 2;
+
+/// Packets exchanged between the relay and the hosts in the control socket.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum RelayPacket<'a> {
+    /// Sent by the host to open a session.
+    Register(SessionToken),
+    /// Sent by the relay with the public address of the session.
+    Registered(SocketAddr),
+    /// Sent by the host to keep the session open.
+    KeepAlive { session_id: u64 },
+    /// A netcode packet from or to a client of the session.
+    Forward {
+        session_id: u64,
+        addr: SocketAddr,
+        payload: &'a [u8],
+    },
+}
+
+impl<'a> RelayPacket<'a> {
+    const REGISTER: u8 = 0;
+    const REGISTERED: u8 = 1;
+    const KEEP_ALIVE: u8 = 2;
+    const FORWARD: u8 = 3;
+
+    pub fn encode(&self, buffer: &mut [u8]) -> Option<usize> {
+        let mut len = RELAY_PREFIX.len() + 1;
+        if buffer.len() < RELAY_HEADER_BYTES.max(len + SESSION_TOKEN_BYTES) {
+            return None;
+        }
+        buffer[..RELAY_PREFIX.len()].copy_from_slice(RELAY_PREFIX);
+
+        match self {
+            RelayPacket::Register(token) => {
+                buffer[4] = Self::REGISTER;
+                buffer[len..len + SESSION_TOKEN_BYTES].copy_from_slice(&token.to_bytes());
+                len += SESSION_TOKEN_BYTES;
+            }
+            RelayPacket::KeepAlive { session_id } => {
+                buffer[4] = Self::KEEP_ALIVE;
+                buffer[len..len + 8].copy_from_slice(&session_id.to_le_bytes());
+                len += 8;
+            }
+            RelayPacket::Registered(addr) => {
+                buffer[4] = Self::REGISTERED;
+                len += write_addr(&mut buffer[len..], *addr);
+            }
+            RelayPacket::Forward { session_id, addr, payload } => {
+                buffer[4] = Self::FORWARD;
+                buffer[len..len + 8].copy_from_slice(&session_id.to_le_bytes());
+                len += 8;
+                len += write_addr(&mut buffer[len..], *addr);
+                if len + payload.len() > buffer.len().min(RELAY_MAX_PACKET_BYTES) {
+                    return None;
+                }
+                buffer[len..len + payload.len()].copy_from_slice(payload);
+                len += payload.len();
+            }
+        }
+
+        Some(len)
+    }
+
+    pub fn decode(buffer: &'a [u8]) -> Option<Self> {
+        let buffer = buffer.strip_prefix(RELAY_PREFIX)?;
+        let (packet_type, buffer) = buffer.split_first()?;
+        let packet = match *packet_type {
+            Self::REGISTER => {
+                let token = buffer.get(..SESSION_TOKEN_BYTES)?.try_into().ok()?;
+                RelayPacket::Register(SessionToken::from_bytes(token))
+            }
+            Self::KEEP_ALIVE => RelayPacket::KeepAlive {
+                session_id: read_session_id(buffer)?.0,
+            },
+            Self::REGISTERED => RelayPacket::Registered(read_addr(buffer)?.0),
+            Self::FORWARD => {
+                let (session_id, buffer) = read_session_id(buffer)?;
+                let (addr, payload) = read_addr(buffer)?;
+                RelayPacket::Forward { session_id, addr, payload }
+            }
+            _ => return None,
+        };
+
+        Some(packet)
+    }
+}
+
+fn read_session_id(buffer: &[u8]) -> Option<(u64, &[u8])> {
+    let session_id = u64::from_le_bytes(buffer.get(..8)?.try_into().ok()?);
+
+    Some((session_id, &buffer[8..]))
+}
+
+fn write_addr(buffer: &mut [u8], addr: SocketAddr) -> usize {
+    let len = match addr.ip() {
+        IpAddr::V4(ip) => {
+            buffer[0] = RELAY_ADDRESS_IPV4;
+            buffer[1..5].copy_from_slice(&ip.octets());
+            5
+        }
+        IpAddr::V6(ip) => {
+            buffer[0] = RELAY_ADDRESS_IPV6;
+            buffer[1..17].copy_from_slice(&ip.octets());
+            17
+        }
+    };
+    buffer[len..len + 2].copy_from_slice(&addr.port().to_le_bytes());
+
+    len + 2
+}
+
+fn read_addr(buffer: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    let (address_type, buffer) = buffer.split_first()?;
+    let (ip, buffer): (IpAddr, &[u8]) = match *address_type {
+        RELAY_ADDRESS_IPV4 if buffer.len() >= 4 => {
+            let octets: [u8; 4] = buffer[..4].try_into().ok()?;
+            (Ipv4Addr::from(octets).into(), &buffer[4..])
+        }
+        RELAY_ADDRESS_IPV6 if buffer.len() >= 16 => {
+            let octets: [u8; 16] = buffer[..16].try_into().ok()?;
+            (Ipv6Addr::from(octets).into(), &buffer[16..])
+        }
+        _ => return None,
+    };
+    if buffer.len() < 2 {
+        return None;
+    }
+    let port = u16::from_le_bytes([buffer[0], buffer[1]]);
+
+    Some((SocketAddr::new(ip, port), &buffer[2..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::RELAY_KEY_BYTES;
+
+    #[test]
+    fn encode_decode_packets() {
+        let mut buffer = [0u8; RELAY_MAX_PACKET_BYTES];
+        let payload = [7u8; 300];
+        let token = SessionToken::generate(Duration::ZERO, 3, 30, &[1; RELAY_KEY_BYTES]);
+        let packets = [
+            RelayPacket::Register(token),
+            RelayPacket::KeepAlive { session_id: 3 },
+            RelayPacket::Registered("127.0.0.1:5000".parse().unwrap()),
+            RelayPacket::Forward {
+                session_id: 3,
+                addr: "[::1]:6000".parse().unwrap(),
+                payload: &payload,
+            },
+        ];
+
+        for packet in packets {
+            let len = packet.encode(&mut buffer).unwrap();
+            assert_eq!(RelayPacket::decode(&buffer[..len]), Some(packet));
+        }
+
+        assert_eq!(RelayPacket::decode(b"RRLY"), None);
+        assert_eq!(RelayPacket::decode(b"RRLY\x03\x03\0\0\0\0\0\0\0\x04\x7f"), None);
+        assert_eq!(RelayPacket::decode(b"RRLY\x00\x03\0\0\0"), None);
+
+        let too_large = [0u8; RELAY_MAX_PACKET_BYTES];
+        let packet = RelayPacket::Forward {
+            session_id: 3,
+            addr: "127.0.0.1:5000".parse().unwrap(),
+            payload: &too_large,
+        };
+        assert_eq!(packet.encode(&mut buffer), None);
+    }
+}
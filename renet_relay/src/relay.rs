@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use crate::{packet::RelayPacket, RELAY_KEY_BYTES, RELAY_MAX_PACKET_BYTES};
+
+// Prefix byte, version info, protocol id, expire timestamp, nonce and the encrypted private connect token
+const NETCODE_CONNECTION_REQUEST_BYTES: usize = 1 + 13 + 8 + 8 + 24 + 1024;
+
+/// Configuration options for the relay server.
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Maximum number of hosts with an open session.
+    pub max_sessions: usize,
+    /// Maximum number of clients forwarded to each host.
+    pub max_clients_per_session: usize,
+    /// Maximum number of clients with the same IP forwarded to each host.
+    pub max_clients_per_ip: usize,
+    /// Sessions are closed when the host doesn't send packets for this duration.
+    pub session_timeout: Duration,
+    /// Clients are forgotten when they don't send packets for this duration.
+    pub client_timeout: Duration,
+    /// Public IP sent to the hosts. When not set, the IP of the control socket is used.
+    /// Should be set when the relay is bound to an unspecified address (`0.0.0.0`).
+    pub public_ip: Option<IpAddr>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            max_sessions: 64,
+            max_clients_per_session: 64,
+            max_clients_per_ip: 4,
+            session_timeout: Duration::from_secs(10),
+            client_timeout: Duration::from_secs(30),
+            public_ip: None,
+        }
+    }
+}
+
+struct Session {
+    host_addr: SocketAddr,
+    socket: UdpSocket,
+    public_addr: SocketAddr,
+    last_received_time: Duration,
+    clients: HashMap<SocketAddr, Duration>,
+}
+
+impl Session {
+    fn can_add_client(&self, client_addr: SocketAddr, packet: &[u8], config: &RelayConfig) -> bool {
+        let is_connection_request = packet.len() == NETCODE_CONNECTION_REQUEST_BYTES && packet[0] == 0;
+        if !is_connection_request || self.clients.len() >= config.max_clients_per_session {
+            return false;
+        }
+
+        let clients_with_ip = self.clients.keys().filter(|addr| addr.ip() == client_addr.ip()).count();
+        clients_with_ip < config.max_clients_per_ip
+    }
+}
+
+/// Forwards netcode packets between clients and hosts without decrypting them.
+///
+/// Hosts open a session through the control socket with a [SessionToken] and receive a public
+/// address, allocated for the session. The packets that clients send to that address are forwarded
+/// to the host with the client address, and the host sends back the packets for each client
+/// through the relay. The packets of the host must have the session id of the token and come from
+/// the address that opened the session. The relay only sends packets to clients that sent packets
+/// to the session. New clients are only added with a packet that looks like a netcode connection
+/// request, the other packets from unknown addresses are dropped.
+pub struct RelayServer {
+    socket: UdpSocket,
+    config: RelayConfig,
+    private_key: [u8; RELAY_KEY_BYTES],
+    current_time: Duration,
+    sessions: HashMap<u64, Session>,
+    out: Box<[u8; RELAY_MAX_PACKET_BYTES]>,
+}
+
+impl RelayServer {
+    /// Creates a relay with the control socket, where the hosts register.
+    /// The session tokens of the hosts must be signed with the private key.
+    pub fn new(
+        current_time: Duration,
+        socket: UdpSocket,
+        private_key: &[u8; RELAY_KEY_BYTES],
+        config: RelayConfig,
+    ) -> Result<Self, io::Error> {
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            config,
+            private_key: *private_key,
+            current_time,
+            sessions: HashMap::new(),
+            out: Box::new([0; RELAY_MAX_PACKET_BYTES]),
+        })
+    }
+
+    /// Returns the address of the control socket.
+    pub fn addr(&self) -> Result<SocketAddr, io::Error> {
+        self.socket.local_addr()
+    }
+
+    /// Returns the number of open sessions.
+    pub fn sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Advances the relay by the duration, and forwards the packets received.
+    pub fn update(&mut self, duration: Duration) -> Result<(), io::Error> {
+        self.current_time += duration;
+        let mut buffer = [0u8; RELAY_MAX_PACKET_BYTES];
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buffer) {
+                Ok(result) => result,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::error!("failed to receive packet in the control socket: {}", e);
+                    break;
+                }
+            };
+
+            if let Some(packet) = RelayPacket::decode(&buffer[..len]) {
+                if let Err(e) = self.process_host_packet(addr, packet) {
+                    log::error!("failed to process packet from host {}: {}", addr, e);
+                }
+            }
+        }
+
+        for (session_id, session) in self.sessions.iter_mut() {
+            loop {
+                let (len, client_addr) = match session.socket.recv_from(&mut buffer) {
+                    Ok(result) => result,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        log::error!("failed to receive packet in session {}: {}", session.public_addr, e);
+                        break;
+                    }
+                };
+
+                if !session.clients.contains_key(&client_addr) && !session.can_add_client(client_addr, &buffer[..len], &self.config) {
+                    continue;
+                }
+                session.clients.insert(client_addr, self.current_time);
+
+                let packet = RelayPacket::Forward {
+                    session_id: *session_id,
+                    addr: client_addr,
+                    payload: &buffer[..len],
+                };
+                if let Some(len) = packet.encode(&mut self.out[..]) {
+                    if let Err(e) = self.socket.send_to(&self.out[..len], session.host_addr) {
+                        log::error!("failed to forward packet to host {}: {}", session.host_addr, e);
+                    }
+                }
+            }
+        }
+
+        let current_time = self.current_time;
+        let config = &self.config;
+        self.sessions.retain(|_, session| {
+            session
+                .clients
+                .retain(|_, last_received_time| current_time - *last_received_time < config.client_timeout);
+            let timed_out = current_time - session.last_received_time >= config.session_timeout;
+            if timed_out {
+                log::info!("Session {} from host {} timed out", session.public_addr, session.host_addr);
+            }
+            !timed_out
+        });
+
+        Ok(())
+    }
+
+    fn process_host_packet(&mut self, host_addr: SocketAddr, packet: RelayPacket) -> Result<(), io::Error> {
+        match packet {
+            RelayPacket::Register(token) => {
+                if !token.is_valid(self.current_time, &self.private_key) {
+                    log::warn!("Ignored register from {}, invalid or expired session token", host_addr);
+                    return Ok(());
+                }
+
+                let session_id = token.session_id();
+                match self.sessions.get(&session_id) {
+                    Some(session) if session.host_addr != host_addr => {
+                        log::warn!(
+                            "Ignored register from {}, session {} is open for host {}",
+                            host_addr,
+                            session_id,
+                            session.host_addr
+                        );
+                        return Ok(());
+                    }
+                    Some(_) => {}
+                    None => {
+                        if self.sessions.len() >= self.config.max_sessions {
+                            log::warn!("Ignored register from {}, max sessions reached", host_addr);
+                            return Ok(());
+                        }
+                        let session = self.open_session(host_addr)?;
+                        log::info!("Opened session {} for host {}", session.public_addr, host_addr);
+                        self.sessions.insert(session_id, session);
+                    }
+                }
+
+                let session = self.sessions.get_mut(&session_id).unwrap();
+                session.last_received_time = self.current_time;
+                let len = RelayPacket::Registered(session.public_addr)
+                    .encode(&mut self.out[..])
+                    .expect("registered packet fits in the buffer");
+                self.socket.send_to(&self.out[..len], host_addr)?;
+            }
+            RelayPacket::KeepAlive { session_id } => {
+                let current_time = self.current_time;
+                if let Some(session) = self.host_session(session_id, host_addr) {
+                    session.last_received_time = current_time;
+                }
+            }
+            RelayPacket::Forward { session_id, addr, payload } => {
+                let current_time = self.current_time;
+                if let Some(session) = self.host_session(session_id, host_addr) {
+                    session.last_received_time = current_time;
+                    if session.clients.contains_key(&addr) {
+                        session.socket.send_to(payload, addr)?;
+                    }
+                }
+            }
+            RelayPacket::Registered(_) => {}
+        }
+
+        Ok(())
+    }
+
+    // The session, only when the host opened it
+    fn host_session(&mut self, session_id: u64, host_addr: SocketAddr) -> Option<&mut Session> {
+        self.sessions.get_mut(&session_id).filter(|session| session.host_addr == host_addr)
+    }
+
+    fn open_session(&self, host_addr: SocketAddr) -> Result<Session, io::Error> {
+        let local_addr = self.socket.local_addr()?;
+        let socket = UdpSocket::bind((local_addr.ip(), 0))?;
+        socket.set_nonblocking(true)?;
+        let port = socket.local_addr()?.port();
+        let public_ip = self.config.public_ip.unwrap_or_else(|| local_addr.ip());
+
+        Ok(Session {
+            host_addr,
+            socket,
+            public_addr: SocketAddr::new(public_ip, port),
+            last_received_time: self.current_time,
+            clients: HashMap::new(),
+        })
+    }
+}
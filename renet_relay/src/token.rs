@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+use renetcode::generate_random_bytes;
+
+use crate::RELAY_KEY_BYTES;
+
+const SESSION_TOKEN_PREFIX: &[u8] = b"renet relay session";
+const SESSION_TOKEN_NONCE_BYTES: usize = 24;
+const SESSION_TOKEN_MAC_BYTES: usize = 16;
+/// The number of bytes of a serialized [SessionToken].
+pub const SESSION_TOKEN_BYTES: usize = 8 + 8 + SESSION_TOKEN_NONCE_BYTES + SESSION_TOKEN_MAC_BYTES;
+
+/// Token that allows a host to open a session in the relay, signed with the relay private key.
+///
+/// Should be generated by the same backend that generates the connect tokens for the game, and sent
+/// to the host over a secure channel. The session id identifies the session in the relay, only one
+/// host can hold a session at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionToken {
+    session_id: u64,
+    expire_timestamp: u64,
+    nonce: [u8; SESSION_TOKEN_NONCE_BYTES],
+    mac: [u8; SESSION_TOKEN_MAC_BYTES],
+}
+
+impl SessionToken {
+    /// Generates a token for the session that expires `expire_seconds` after the current time.
+    pub fn generate(current_time: Duration, session_id: u64, expire_seconds: u64, private_key: &[u8; RELAY_KEY_BYTES]) -> Self {
+        let expire_timestamp = current_time.as_secs().saturating_add(expire_seconds);
+        let nonce = generate_random_bytes();
+        let mac = compute_mac(session_id, expire_timestamp, &nonce, private_key)
+            .try_into()
+            .expect("mac of the session token has the tag size");
+
+        Self {
+            session_id,
+            expire_timestamp,
+            nonce,
+            mac,
+        }
+    }
+
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    /// Seconds since the UNIX epoch when the token expires.
+    pub fn expire_timestamp(&self) -> u64 {
+        self.expire_timestamp
+    }
+
+    /// Returns true if the token was signed with the private key and has not expired.
+    pub(crate) fn is_valid(&self, current_time: Duration, private_key: &[u8; RELAY_KEY_BYTES]) -> bool {
+        if current_time.as_secs() >= self.expire_timestamp {
+            return false;
+        }
+
+        let cipher = XChaCha20Poly1305::new(private_key.into());
+        let aad = token_aad(self.session_id, self.expire_timestamp);
+        let payload = Payload { msg: &self.mac, aad: &aad };
+        cipher.decrypt(XNonce::from_slice(&self.nonce), payload).is_ok()
+    }
+
+    pub fn to_bytes(&self) -> [u8; SESSION_TOKEN_BYTES] {
+        let mut bytes = [0u8; SESSION_TOKEN_BYTES];
+        bytes[..8].copy_from_slice(&self.session_id.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.expire_timestamp.to_le_bytes());
+        bytes[16..16 + SESSION_TOKEN_NONCE_BYTES].copy_from_slice(&self.nonce);
+        bytes[16 + SESSION_TOKEN_NONCE_BYTES..].copy_from_slice(&self.mac);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; SESSION_TOKEN_BYTES]) -> Self {
+        Self {
+            session_id: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            expire_timestamp: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            nonce: bytes[16..16 + SESSION_TOKEN_NONCE_BYTES].try_into().unwrap(),
+            mac: bytes[16 + SESSION_TOKEN_NONCE_BYTES..].try_into().unwrap(),
+        }
+    }
+}
+
+fn token_aad(session_id: u64, expire_timestamp: u64) -> Vec<u8> {
+    let mut aad = SESSION_TOKEN_PREFIX.to_vec();
+    aad.extend_from_slice(&session_id.to_le_bytes());
+    aad.extend_from_slice(&expire_timestamp.to_le_bytes());
+
+    aad
+}
+
+// The tag of an empty message authenticates the session id and expire timestamp
+fn compute_mac(
+    session_id: u64,
+    expire_timestamp: u64,
+    nonce: &[u8; SESSION_TOKEN_NONCE_BYTES],
+    private_key: &[u8; RELAY_KEY_BYTES],
+) -> Vec<u8> {
+    let cipher = XChaCha20Poly1305::new(private_key.into());
+    let aad = token_aad(session_id, expire_timestamp);
+    let payload = Payload { msg: &[], aad: &aad };
+    cipher
+        .encrypt(XNonce::from_slice(nonce), payload)
+        .expect("encryption should not fail with a valid key and nonce")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_session_token() {
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let current_time = Duration::from_secs(100);
+        let token = SessionToken::generate(current_time, 7, 30, private_key);
+        assert!(token.is_valid(current_time, private_key));
+        assert_eq!(SessionToken::from_bytes(&token.to_bytes()), token);
+
+        assert!(!token.is_valid(current_time, &[0; RELAY_KEY_BYTES]));
+        assert!(!token.is_valid(Duration::from_secs(130), private_key));
+
+        let forged = SessionToken { session_id: 8, ..token };
+        assert!(!forged.is_valid(current_time, private_key));
+        let forged = SessionToken {
+            expire_timestamp: u64::MAX,
+            ..token
+        };
+        assert!(!forged.is_valid(current_time, private_key));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    io::{BufRead, BufReader},
+    net::{SocketAddr, UdpSocket},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use renet::{ClientAuthentication, DefaultChannel, RenetClient, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig};
+use renet_relay::{RelayHost, SessionToken};
+
+const PROTOCOL_ID: u64 = 7;
+const RELAY_KEY: &[u8; 32] = b"an example very very secret key.";
+
+struct RelayProcess(Child);
+
+impl Drop for RelayProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+fn start_relay() -> (RelayProcess, SocketAddr) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_renet_relay"))
+        .arg("127.0.0.1:0")
+        .env("RENET_RELAY_KEY", base64::encode(RELAY_KEY))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let addr = line.trim().rsplit(' ').next().unwrap().parse().unwrap();
+
+    (RelayProcess(child), addr)
+}
+
+fn session_token(session_id: u64, private_key: &[u8; 32]) -> SessionToken {
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    SessionToken::generate(current_time, session_id, 300, private_key)
+}
+
+#[test]
+fn client_connects_through_relay() {
+    let (_relay, relay_addr) = start_relay();
+
+    let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let token = session_token(1, RELAY_KEY);
+    let mut host = RelayHost::connect(relay_addr, server_socket.local_addr().unwrap(), token, Duration::from_secs(5)).unwrap();
+    let public_addr = host.public_addr();
+    assert_eq!(public_addr.ip(), relay_addr.ip());
+    assert_ne!(public_addr, relay_addr);
+
+    let server_config = ServerConfig::new(8, PROTOCOL_ID, public_addr, ServerAuthentication::Unsecure);
+    let mut server = RenetServer::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), server_socket).unwrap();
+
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: PROTOCOL_ID,
+        client_id: 1,
+        server_addresses: vec![public_addr],
+        user_data: None,
+        expire_seconds: 300,
+        timeout_seconds: 15,
+    };
+    let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client = RenetClient::new(Duration::ZERO, client_socket, RenetConnectionConfig::default(), authentication).unwrap();
+
+    let delta = Duration::from_millis(10);
+    let mut server_message = None;
+    let mut client_message = None;
+    for _ in 0..500 {
+        client.update(delta).unwrap();
+        host.update(delta).unwrap();
+        server.update(delta).unwrap();
+
+        if client.is_connected() {
+            client.send_message(DefaultChannel::Reliable, b"hello host".to_vec());
+            if server.is_client_connected(1) {
+                server.send_message(1, DefaultChannel::Reliable, b"hello client".to_vec());
+            }
+        }
+        if let Some(message) = server.receive_message(1, DefaultChannel::Reliable) {
+            server_message = Some(message);
+        }
+        if let Some(message) = client.receive_message(DefaultChannel::Reliable) {
+            client_message = Some(message);
+        }
+        if server_message.is_some() && client_message.is_some() {
+            break;
+        }
+
+        client.send_packets().unwrap();
+        server.send_packets().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+
+    assert_eq!(server_message.as_deref(), Some(&b"hello host"[..]));
+    assert_eq!(client_message.as_deref(), Some(&b"hello client"[..]));
+    assert_eq!(host.clients(), 1);
+}
+
+#[test]
+fn register_requires_session() {
+    let (_relay, relay_addr) = start_relay();
+    let server_addr = "127.0.0.1:5000".parse().unwrap();
+    let timeout = Duration::from_millis(500);
+
+    let invalid_token = session_token(1, &[0; 32]);
+    assert!(RelayHost::connect(relay_addr, server_addr, invalid_token, timeout).is_err());
+
+    // The session can only be held by one host
+    let token = session_token(1, RELAY_KEY);
+    let host = RelayHost::connect(relay_addr, server_addr, token, timeout).unwrap();
+    assert!(RelayHost::connect(relay_addr, server_addr, token, timeout).is_err());
+
+    let other_host = RelayHost::connect(relay_addr, server_addr, session_token(2, RELAY_KEY), timeout).unwrap();
+    assert_ne!(host.public_addr(), other_host.public_addr());
+}
+
+#[test]
+fn clients_added_with_connection_requests() {
+    let (_relay, relay_addr) = start_relay();
+    let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let token = session_token(1, RELAY_KEY);
+    let mut host = RelayHost::connect(relay_addr, server_socket.local_addr().unwrap(), token, Duration::from_secs(5)).unwrap();
+
+    let send_from_new_sockets = |host: &mut RelayHost, packet: &[u8], count: usize| {
+        let sockets: Vec<UdpSocket> = (0..count).map(|_| UdpSocket::bind("127.0.0.1:0").unwrap()).collect();
+        for socket in sockets.iter() {
+            socket.send_to(packet, host.public_addr()).unwrap();
+        }
+        for _ in 0..50 {
+            host.update(Duration::from_millis(10)).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+    };
+
+    // Packets that are not connection requests don't add clients
+    send_from_new_sockets(&mut host, &[1u8; 100], 3);
+    assert_eq!(host.clients(), 0);
+
+    // Only 4 clients are added from the same IP
+    let connection_request = [0u8; 1078];
+    send_from_new_sockets(&mut host, &connection_request, 6);
+    assert_eq!(host.clients(), 4);
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, fmt};
+
+use renetcode::TokenGenerationError;
+
+/// Errors from the token issuer.
+#[derive(Debug)]
+pub enum IssuerError {
+    /// The registration could not be decrypted with the registration key.
+    InvalidSignature,
+    /// The registration is too old, or from the future.
+    RegistrationExpired,
+    /// The registration is not newer than the last registration from the same address.
+    RegistrationReplayed,
+    /// No server is registered with the given id.
+    ServerNotFound,
+    /// The user data is not valid base64 or is above the connect token limit.
+    InvalidUserData,
+    /// An error occured while generating the connect token.
+    TokenGenerationError(TokenGenerationError),
+}
+
+impl Error for IssuerError {}
+
+impl fmt::Display for IssuerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use IssuerError::*;
+
+        match *self {
+            InvalidSignature => write!(fmt, "invalid registration signature"),
+            RegistrationExpired => write!(fmt, "registration has expired"),
+            RegistrationReplayed => write!(fmt, "registration is older than the current one"),
+            ServerNotFound => write!(fmt, "server not found"),
+            InvalidUserData => write!(fmt, "invalid user data"),
+            TokenGenerationError(ref err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl From<TokenGenerationError> for IssuerError {
+    fn from(inner: TokenGenerationError) -> Self {
+        IssuerError::TokenGenerationError(inner)
+    }
+}
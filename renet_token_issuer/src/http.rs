@@ -0,0 +1,124 @@
+use std::{
+    io,
+    net::TcpListener,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use renet_http::Response;
+use renetcode::{ConnectToken, NETCODE_USER_DATA_BYTES};
+use serde::{Deserialize, Serialize};
+
+use crate::{IssuerError, SignedRegistration, TokenIssuer};
+
+const MAX_BODY_BYTES: usize = 16 * 1024;
+
+/// Response to a server registration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisterResponse {
+    pub server_id: u64,
+}
+
+/// Request for a connect token, the user data is encoded in URL-safe base64.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenRequest {
+    pub client_id: u64,
+    #[serde(default)]
+    pub user_data: Option<String>,
+}
+
+/// Response with the connect token, encoded in URL-safe base64.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub connect_token: ConnectToken,
+}
+
+fn json<T: Serialize>(value: &T) -> Response {
+    Response::new(200, "application/json", serde_json::to_string(value).unwrap())
+}
+
+fn error(status: u16, message: impl Into<String>) -> Response {
+    Response::new(
+        status,
+        "application/json",
+        serde_json::json!({ "error": message.into() }).to_string(),
+    )
+}
+
+impl From<IssuerError> for Response {
+    fn from(issuer_error: IssuerError) -> Self {
+        let status = match issuer_error {
+            IssuerError::InvalidSignature | IssuerError::RegistrationExpired | IssuerError::RegistrationReplayed => 401,
+            IssuerError::ServerNotFound => 404,
+            IssuerError::InvalidUserData => 400,
+            IssuerError::TokenGenerationError(_) => 500,
+        };
+        error(status, issuer_error.to_string())
+    }
+}
+
+/// Serves a minimal HTTP API for the token issuer, each connection is handled in its own thread.
+/// Blocks until the listener fails, or until the next connection after `shutdown` is set.
+///
+/// Routes:
+/// - `POST /servers` with a [SignedRegistration], returns a [RegisterResponse].
+/// - `GET /servers` returns the list of [ServerInfo][crate::ServerInfo].
+/// - `POST /servers/{server_id}/tokens` with a [TokenRequest], returns a [TokenResponse].
+///
+/// The token route issues tokens for any client id, it should only be reachable by the service
+/// that authenticates the players.
+pub fn serve(listener: TcpListener, issuer: Arc<Mutex<TokenIssuer>>, shutdown: &AtomicBool) -> io::Result<()> {
+    renet_http::serve(listener, MAX_BODY_BYTES, shutdown, move |request| {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let mut issuer = issuer.lock().unwrap();
+        issuer.update(current_time);
+        route(&mut issuer, current_time, &request.method, &request.path, &request.body)
+    })
+}
+
+fn route(issuer: &mut TokenIssuer, current_time: Duration, method: &str, path: &str, body: &[u8]) -> Response {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        ("GET", ["servers"]) => json(&issuer.servers()),
+        ("POST", ["servers"]) => {
+            let signed_registration: SignedRegistration = match serde_json::from_slice(body) {
+                Ok(registration) => registration,
+                Err(e) => return error(400, e.to_string()),
+            };
+            match issuer.register(current_time, &signed_registration) {
+                Ok(server_id) => json(&RegisterResponse { server_id }),
+                Err(e) => e.into(),
+            }
+        }
+        ("POST", ["servers", server_id, "tokens"]) => {
+            let server_id: u64 = match server_id.parse() {
+                Ok(server_id) => server_id,
+                Err(_) => return error(404, "server not found"),
+            };
+            let request: TokenRequest = match serde_json::from_slice(body) {
+                Ok(request) => request,
+                Err(e) => return error(400, e.to_string()),
+            };
+            let user_data = match request.user_data.as_deref().map(decode_user_data).transpose() {
+                Ok(user_data) => user_data,
+                Err(e) => return e.into(),
+            };
+            match issuer.issue_token(current_time, server_id, request.client_id, user_data) {
+                Ok(connect_token) => json(&TokenResponse { connect_token }),
+                Err(e) => e.into(),
+            }
+        }
+        _ => error(404, "not found"),
+    }
+}
+
+fn decode_user_data(encoded: &str) -> Result<[u8; NETCODE_USER_DATA_BYTES], IssuerError> {
+    let decoded = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).map_err(|_| IssuerError::InvalidUserData)?;
+    if decoded.len() > NETCODE_USER_DATA_BYTES {
+        return Err(IssuerError::InvalidUserData);
+    }
+
+    let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+    user_data[..decoded.len()].copy_from_slice(&decoded);
+    Ok(user_data)
+}
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use renetcode::{ConnectToken, NETCODE_USER_DATA_BYTES};
+use serde::{Deserialize, Serialize};
+
+use crate::{IssuerError, ServerRegistration, SignedRegistration, REGISTRATION_KEY_BYTES};
+
+/// Configuration options for the token issuer.
+#[derive(Debug, Clone)]
+pub struct TokenIssuerConfig {
+    /// Key shared with the game servers to sign their registrations.
+    pub registration_key: [u8; REGISTRATION_KEY_BYTES],
+    /// Seconds until the issued connect tokens expire.
+    pub token_expire_seconds: u64,
+    /// Timeout in seconds of the issued connect tokens, negative values disable the timeout.
+    pub token_timeout_seconds: i32,
+    /// Maximum age of a registration, older registrations are rejected.
+    pub registration_max_age: Duration,
+    /// Servers are removed when they don't register again within this time.
+    pub server_timeout: Duration,
+}
+
+impl TokenIssuerConfig {
+    pub fn new(registration_key: [u8; REGISTRATION_KEY_BYTES]) -> Self {
+        Self {
+            registration_key,
+            token_expire_seconds: 300,
+            token_timeout_seconds: 15,
+            registration_max_age: Duration::from_secs(30),
+            server_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Public information of a registered server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub server_id: u64,
+    pub name: String,
+    pub protocol_id: u64,
+    pub address: SocketAddr,
+}
+
+#[derive(Debug)]
+struct RegisteredServer {
+    registration: ServerRegistration,
+    last_registered: Duration,
+}
+
+/// Keeps the registered game servers and issues connect tokens for them.
+///
+/// Servers register again periodically to stay listed, a registration from an already
+/// registered address keeps the same server id.
+#[derive(Debug)]
+pub struct TokenIssuer {
+    config: TokenIssuerConfig,
+    servers: HashMap<u64, RegisteredServer>,
+    servers_by_addr: HashMap<SocketAddr, u64>,
+    next_server_id: u64,
+}
+
+impl TokenIssuer {
+    pub fn new(config: TokenIssuerConfig) -> Self {
+        Self {
+            config,
+            servers: HashMap::new(),
+            servers_by_addr: HashMap::new(),
+            next_server_id: 0,
+        }
+    }
+
+    /// Verifies the signed registration and registers the server, returns its id.
+    pub fn register(&mut self, current_time: Duration, signed_registration: &SignedRegistration) -> Result<u64, IssuerError> {
+        let registration = signed_registration.open(&self.config.registration_key)?;
+
+        let registration_time = Duration::from_millis(registration.timestamp);
+        let max_age = self.config.registration_max_age;
+        if current_time.saturating_sub(registration_time) > max_age || registration_time.saturating_sub(current_time) > max_age {
+            return Err(IssuerError::RegistrationExpired);
+        }
+
+        if let Some(&server_id) = self.servers_by_addr.get(&registration.address) {
+            let server = self.servers.get_mut(&server_id).unwrap();
+            if registration.timestamp <= server.registration.timestamp {
+                return Err(IssuerError::RegistrationReplayed);
+            }
+            server.registration = registration;
+            server.last_registered = current_time;
+            return Ok(server_id);
+        }
+
+        let server_id = self.next_server_id;
+        self.next_server_id += 1;
+        self.servers_by_addr.insert(registration.address, server_id);
+        self.servers.insert(
+            server_id,
+            RegisteredServer {
+                registration,
+                last_registered: current_time,
+            },
+        );
+
+        Ok(server_id)
+    }
+
+    /// Removes a server, returns false if it was not registered.
+    pub fn remove_server(&mut self, server_id: u64) -> bool {
+        match self.servers.remove(&server_id) {
+            Some(server) => {
+                self.servers_by_addr.remove(&server.registration.address);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the servers that have not registered again within the server timeout.
+    pub fn update(&mut self, current_time: Duration) {
+        let server_timeout = self.config.server_timeout;
+        let servers_by_addr = &mut self.servers_by_addr;
+        self.servers.retain(|_, server| {
+            let timed_out = current_time.saturating_sub(server.last_registered) >= server_timeout;
+            if timed_out {
+                servers_by_addr.remove(&server.registration.address);
+            }
+            !timed_out
+        });
+    }
+
+    pub fn server(&self, server_id: u64) -> Option<ServerInfo> {
+        self.servers.get(&server_id).map(|server| server_info(server_id, server))
+    }
+
+    /// Returns the registered servers, ordered by id.
+    pub fn servers(&self) -> Vec<ServerInfo> {
+        let mut servers: Vec<ServerInfo> = self.servers.iter().map(|(id, server)| server_info(*id, server)).collect();
+        servers.sort_by_key(|server| server.server_id);
+        servers
+    }
+
+    /// Generates a connect token for the client to connect to the server.
+    pub fn issue_token(
+        &self,
+        current_time: Duration,
+        server_id: u64,
+        client_id: u64,
+        user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>,
+    ) -> Result<ConnectToken, IssuerError> {
+        let server = self.servers.get(&server_id).ok_or(IssuerError::ServerNotFound)?;
+        let registration = &server.registration;
+
+        let mut builder = ConnectToken::builder()
+            .protocol_id(registration.protocol_id)
+            .client_id(client_id)
+            .expire_seconds(self.config.token_expire_seconds)
+            .timeout_seconds(self.config.token_timeout_seconds)
+            .server_address(registration.address);
+        if let Some(user_data) = user_data {
+            builder = builder.user_data(user_data);
+        }
+
+        Ok(builder.generate(current_time, &registration.private_key)?)
+    }
+}
+
+fn server_info(server_id: u64, server: &RegisteredServer) -> ServerInfo {
+    ServerInfo {
+        server_id,
+        name: server.registration.name.clone(),
+        protocol_id: server.registration.protocol_id,
+        address: server.registration.address,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTRATION_KEY: &[u8; REGISTRATION_KEY_BYTES] = b"an example very very secret key."; // 32-bytes
+
+    fn registration(current_time: Duration) -> SignedRegistration {
+        ServerRegistration::new("server", 7, "127.0.0.1:5000".parse().unwrap(), [3; 32], current_time).sign(REGISTRATION_KEY)
+    }
+
+    #[test]
+    fn register_servers() {
+        let mut issuer = TokenIssuer::new(TokenIssuerConfig::new(*REGISTRATION_KEY));
+        let current_time = Duration::from_secs(100);
+
+        let first_registration = registration(current_time);
+        let server_id = issuer.register(current_time, &first_registration).unwrap();
+        assert_eq!(issuer.servers().len(), 1);
+
+        // Replayed registrations are rejected
+        assert!(matches!(
+            issuer.register(current_time, &first_registration),
+            Err(IssuerError::RegistrationReplayed)
+        ));
+
+        // Registering again keeps the id
+        let current_time = current_time + Duration::from_secs(20);
+        assert_eq!(issuer.register(current_time, &registration(current_time)).unwrap(), server_id);
+
+        // Old registrations are rejected
+        let old_registration = registration(current_time - Duration::from_secs(31));
+        assert!(matches!(
+            issuer.register(current_time, &old_registration),
+            Err(IssuerError::RegistrationExpired)
+        ));
+
+        let token = issuer.issue_token(current_time, server_id, 42, None).unwrap();
+        assert_eq!(token.client_id(), 42);
+        assert!(matches!(
+            issuer.issue_token(current_time, server_id + 1, 42, None),
+            Err(IssuerError::ServerNotFound)
+        ));
+
+        issuer.update(current_time + Duration::from_secs(60));
+        assert!(issuer.servers().is_empty());
+    }
+}
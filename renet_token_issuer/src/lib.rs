@@ -0,0 +1,20 @@
+//! Service to issue connect tokens for game servers.
+//!
+//! Game servers register themselves with their protocol id, address and private key. The
+//! registration is encrypted and signed with a registration key shared between the servers and
+//! the issuer, so the private key is never sent in plain text. Clients then request connect
+//! tokens for a registered server.
+//!
+//! The [TokenIssuer] is agnostic from the transport, [serve] exposes it in a minimal HTTP API.
+mod error;
+mod http;
+mod issuer;
+mod registration;
+
+pub use error::IssuerError;
+pub use http::{serve, RegisterResponse, TokenRequest, TokenResponse};
+pub use issuer::{ServerInfo, TokenIssuer, TokenIssuerConfig};
+pub use registration::{ServerRegistration, SignedRegistration};
+
+/// The number of bytes of the registration key.
+pub const REGISTRATION_KEY_BYTES: usize = 32;
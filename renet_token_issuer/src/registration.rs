@@ -0,0 +1,117 @@
+use std::{net::SocketAddr, time::Duration};
+
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+use renetcode::{generate_random_bytes, NETCODE_KEY_BYTES};
+use serde::{Deserialize, Serialize};
+
+use crate::{IssuerError, REGISTRATION_KEY_BYTES};
+
+const REGISTRATION_AAD: &[u8] = b"renet server registration";
+const REGISTRATION_NONCE_BYTES: usize = 24;
+
+/// Information a game server sends to be able to receive clients.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerRegistration {
+    pub name: String,
+    pub protocol_id: u64,
+    pub address: SocketAddr,
+    /// Private key used by the server, the connect tokens are generated with it.
+    pub private_key: [u8; NETCODE_KEY_BYTES],
+    /// Milliseconds since the UNIX epoch when the registration was created.
+    pub timestamp: u64,
+}
+
+/// A [ServerRegistration] encrypted and signed with the registration key, the fields are encoded
+/// in URL-safe base64 so it can be sent as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedRegistration {
+    nonce: String,
+    payload: String,
+}
+
+impl ServerRegistration {
+    pub fn new(
+        name: impl Into<String>,
+        protocol_id: u64,
+        address: SocketAddr,
+        private_key: [u8; NETCODE_KEY_BYTES],
+        current_time: Duration,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            protocol_id,
+            address,
+            private_key,
+            timestamp: current_time.as_millis() as u64,
+        }
+    }
+
+    /// Encrypts and signs the registration with the registration key shared with the issuer.
+    pub fn sign(&self, registration_key: &[u8; REGISTRATION_KEY_BYTES]) -> SignedRegistration {
+        let nonce: [u8; REGISTRATION_NONCE_BYTES] = generate_random_bytes();
+        let message = serde_json::to_vec(self).expect("registration should serialize to json");
+        let cipher = XChaCha20Poly1305::new(registration_key.into());
+        let payload = Payload {
+            msg: &message,
+            aad: REGISTRATION_AAD,
+        };
+        let payload = cipher
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .expect("encryption should not fail with a valid key and nonce");
+
+        SignedRegistration {
+            nonce: base64::encode_config(nonce, base64::URL_SAFE_NO_PAD),
+            payload: base64::encode_config(payload, base64::URL_SAFE_NO_PAD),
+        }
+    }
+}
+
+impl SignedRegistration {
+    /// Verifies and decrypts the registration with the registration key.
+    pub fn open(&self, registration_key: &[u8; REGISTRATION_KEY_BYTES]) -> Result<ServerRegistration, IssuerError> {
+        let nonce = base64::decode_config(&self.nonce, base64::URL_SAFE_NO_PAD).map_err(|_| IssuerError::InvalidSignature)?;
+        if nonce.len() != REGISTRATION_NONCE_BYTES {
+            return Err(IssuerError::InvalidSignature);
+        }
+        let payload = base64::decode_config(&self.payload, base64::URL_SAFE_NO_PAD).map_err(|_| IssuerError::InvalidSignature)?;
+
+        let cipher = XChaCha20Poly1305::new(registration_key.into());
+        let payload = Payload {
+            msg: &payload,
+            aad: REGISTRATION_AAD,
+        };
+        let message = cipher
+            .decrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| IssuerError::InvalidSignature)?;
+
+        serde_json::from_slice(&message).map_err(|_| IssuerError::InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_open_registration() {
+        let registration_key = b"an example very very secret key."; // 32-bytes
+        let registration = ServerRegistration::new("server", 7, "127.0.0.1:5000".parse().unwrap(), [3; 32], Duration::from_secs(10));
+        let signed = registration.sign(registration_key);
+        assert!(!signed.payload.contains("127.0.0.1"));
+
+        let result = signed.open(registration_key).unwrap();
+        assert_eq!(registration, result);
+
+        let wrong_key = [0u8; REGISTRATION_KEY_BYTES];
+        assert!(matches!(signed.open(&wrong_key), Err(IssuerError::InvalidSignature)));
+
+        let mut tampered = signed;
+        tampered
+            .payload
+            .replace_range(0..1, if tampered.payload.starts_with('A') { "B" } else { "A" });
+        assert!(matches!(tampered.open(registration_key), Err(IssuerError::InvalidSignature)));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use renet_token_issuer::{
+    serve, RegisterResponse, ServerInfo, ServerRegistration, TokenIssuer, TokenIssuerConfig, TokenRequest, TokenResponse,
+    REGISTRATION_KEY_BYTES,
+};
+use renetcode::{NetcodeClient, NetcodeServer, ServerResult};
+
+const REGISTRATION_KEY: &[u8; REGISTRATION_KEY_BYTES] = b"an example very very secret key."; // 32-bytes
+const PROTOCOL_ID: u64 = 7;
+
+fn start_issuer() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let issuer = Arc::new(Mutex::new(TokenIssuer::new(TokenIssuerConfig::new(*REGISTRATION_KEY))));
+    thread::spawn(move || serve(listener, issuer, &AtomicBool::new(false)));
+
+    addr
+}
+
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+    let body = response.split("\r\n\r\n").nth(1).unwrap().to_string();
+
+    (status, body)
+}
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap()
+}
+
+#[test]
+fn issue_token_for_registered_server() {
+    let issuer_addr = start_issuer();
+    let private_key = [5u8; 32];
+    let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+
+    let registration = ServerRegistration::new("lobby", PROTOCOL_ID, server_addr, private_key, now()).sign(REGISTRATION_KEY);
+    let (status, body) = request(issuer_addr, "POST", "/servers", &serde_json::to_string(&registration).unwrap());
+    assert_eq!(status, 200);
+    let RegisterResponse { server_id } = serde_json::from_str(&body).unwrap();
+
+    let (status, body) = request(issuer_addr, "GET", "/servers", "");
+    assert_eq!(status, 200);
+    let servers: Vec<ServerInfo> = serde_json::from_str(&body).unwrap();
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0].address, server_addr);
+    assert!(!body.contains("private_key"));
+
+    let token_request = TokenRequest {
+        client_id: 42,
+        user_data: Some(base64::encode_config(b"username", base64::URL_SAFE_NO_PAD)),
+    };
+    let (status, body) = request(
+        issuer_addr,
+        "POST",
+        &format!("/servers/{}/tokens", server_id),
+        &serde_json::to_string(&token_request).unwrap(),
+    );
+    assert_eq!(status, 200);
+    let TokenResponse { connect_token } = serde_json::from_str(&body).unwrap();
+
+    // The issued token connects to a server using the registered private key
+    let mut server = NetcodeServer::new(now(), 16, PROTOCOL_ID, server_addr, private_key);
+    let mut client = NetcodeClient::new(now(), connect_token);
+    let client_addr: SocketAddr = "127.0.0.1:6000".parse().unwrap();
+
+    let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+    match server.process_packet(client_addr, client_packet) {
+        ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+        _ => unreachable!(),
+    };
+    let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+    match server.process_packet(client_addr, client_packet) {
+        ServerResult::ClientConnected { client_id, user_data, .. } => {
+            assert_eq!(client_id, 42);
+            assert_eq!(&user_data[..8], b"username");
+        }
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn reject_invalid_requests() {
+    let issuer_addr = start_issuer();
+    let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+
+    let wrong_key = [1u8; REGISTRATION_KEY_BYTES];
+    let registration = ServerRegistration::new("lobby", PROTOCOL_ID, server_addr, [5; 32], now()).sign(&wrong_key);
+    let (status, _) = request(issuer_addr, "POST", "/servers", &serde_json::to_string(&registration).unwrap());
+    assert_eq!(status, 401);
+
+    let old_registration =
+        ServerRegistration::new("lobby", PROTOCOL_ID, server_addr, [5; 32], now() - Duration::from_secs(60)).sign(REGISTRATION_KEY);
+    let (status, _) = request(issuer_addr, "POST", "/servers", &serde_json::to_string(&old_registration).unwrap());
+    assert_eq!(status, 401);
+
+    let (status, _) = request(issuer_addr, "POST", "/servers", "not json");
+    assert_eq!(status, 400);
+
+    let token_request = serde_json::to_string(&TokenRequest {
+        client_id: 1,
+        user_data: None,
+    })
+    .unwrap();
+    let (status, _) = request(issuer_addr, "POST", "/servers/0/tokens", &token_request);
+    assert_eq!(status, 404);
+
+    let registration = ServerRegistration::new("lobby", PROTOCOL_ID, server_addr, [5; 32], now()).sign(REGISTRATION_KEY);
+    let (status, _) = request(issuer_addr, "POST", "/servers", &serde_json::to_string(&registration).unwrap());
+    assert_eq!(status, 200);
+
+    let too_large = serde_json::to_string(&TokenRequest {
+        client_id: 1,
+        user_data: Some(base64::encode_config([0u8; 300], base64::URL_SAFE_NO_PAD)),
+    })
+    .unwrap();
+    let (status, _) = request(issuer_addr, "POST", "/servers/0/tokens", &too_large);
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn shutdown() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let issuer = Arc::new(Mutex::new(TokenIssuer::new(TokenIssuerConfig::new(*REGISTRATION_KEY))));
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let thread_shutdown = shutdown.clone();
+    let handle = thread::spawn(move || serve(listener, issuer, &thread_shutdown));
+
+    let (status, _) = request(addr, "GET", "/servers", "");
+    assert_eq!(status, 200);
+
+    // The flag is checked when the next connection arrives
+    shutdown.store(true, Ordering::Relaxed);
+    TcpStream::connect(addr).unwrap();
+    handle.join().unwrap().unwrap();
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, fmt, net::IpAddr, time::Duration};
+
+use crate::{ClientID, NETCODE_MAC_BYTES};
+
+/// A range of IP addresses, an address with the number of leading bits that must match.
+/// A range with the full prefix length (32 for IPv4, 128 for IPv6) contains only its address.
+/// The bits of the address after the prefix are cleared, so ranges can be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Creates a new range, the prefix length is capped to the address size.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Self {
+        match addr {
+            IpAddr::V4(addr) => {
+                let prefix_len = prefix_len.min(32);
+                let addr = u32::from(addr) & ipv4_mask(prefix_len);
+                Self {
+                    addr: IpAddr::V4(addr.into()),
+                    prefix_len,
+                }
+            }
+            IpAddr::V6(addr) => {
+                let prefix_len = prefix_len.min(128);
+                let addr = u128::from(addr) & ipv6_mask(prefix_len);
+                Self {
+                    addr: IpAddr::V6(addr.into()),
+                    prefix_len,
+                }
+            }
+        }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns true if the address is inside the range.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(range), IpAddr::V4(addr)) => u32::from(range) == u32::from(addr) & ipv4_mask(self.prefix_len),
+            (IpAddr::V6(range), IpAddr::V6(addr)) => u128::from(range) == u128::from(addr) & ipv6_mask(self.prefix_len),
+            _ => false,
+        }
+    }
+}
+
+fn ipv4_mask(prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+fn ipv6_mask(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> Self {
+        Self::new(addr, u8::MAX)
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Banned client ids and IP ranges with their optional expire time, and revoked connect tokens
+/// with the expire timestamp of the token.
+#[derive(Debug, Default)]
+pub(crate) struct BanList {
+    client_ids: HashMap<ClientID, Option<Duration>>,
+    ip_ranges: HashMap<IpRange, Option<Duration>>,
+    revoked_tokens: HashMap<[u8; NETCODE_MAC_BYTES], u64>,
+}
+
+impl BanList {
+    pub fn ban_client_id(&mut self, client_id: ClientID, expire_time: Option<Duration>) {
+        self.client_ids.insert(client_id, expire_time);
+    }
+
+    pub fn unban_client_id(&mut self, client_id: ClientID) {
+        self.client_ids.remove(&client_id);
+    }
+
+    pub fn ban_ip_range(&mut self, ip_range: IpRange, expire_time: Option<Duration>) {
+        self.ip_ranges.insert(ip_range, expire_time);
+    }
+
+    pub fn unban_ip_range(&mut self, ip_range: IpRange) {
+        self.ip_ranges.remove(&ip_range);
+    }
+
+    pub fn revoke_token(&mut self, mac: [u8; NETCODE_MAC_BYTES], expire_timestamp: u64) {
+        self.revoked_tokens.insert(mac, expire_timestamp);
+    }
+
+    pub fn is_client_id_banned(&self, client_id: ClientID) -> bool {
+        self.client_ids.contains_key(&client_id)
+    }
+
+    pub fn is_ip_banned(&self, addr: IpAddr) -> bool {
+        self.ip_ranges.keys().any(|range| range.contains(addr))
+    }
+
+    pub fn is_token_revoked(&self, mac: &[u8; NETCODE_MAC_BYTES]) -> bool {
+        self.revoked_tokens.contains_key(mac)
+    }
+
+    /// Removes the bans that have expired and the revoked tokens that can no longer be used.
+    pub fn update(&mut self, current_time: Duration) {
+        let not_expired = |expire_time: &Option<Duration>| match expire_time {
+            Some(expire_time) => current_time < *expire_time,
+            None => true,
+        };
+        self.client_ids.retain(|_, expire_time| not_expired(expire_time));
+        self.ip_ranges.retain(|_, expire_time| not_expired(expire_time));
+        self.revoked_tokens
+            .retain(|_, expire_timestamp| current_time.as_secs() < *expire_timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_range_contains() {
+        let range = IpRange::new("192.168.1.0".parse().unwrap(), 24);
+        assert!(range.contains("192.168.1.42".parse().unwrap()));
+        assert!(!range.contains("192.168.2.42".parse().unwrap()));
+        assert!(!range.contains("::1".parse().unwrap()));
+
+        let single: IpRange = "10.0.0.1".parse::<IpAddr>().unwrap().into();
+        assert_eq!(single.prefix_len(), 32);
+        assert!(single.contains("10.0.0.1".parse().unwrap()));
+        assert!(!single.contains("10.0.0.2".parse().unwrap()));
+
+        let all = IpRange::new("0.0.0.0".parse().unwrap(), 0);
+        assert!(all.contains("8.8.8.8".parse().unwrap()));
+
+        let range = IpRange::new("192.168.1.42".parse().unwrap(), 24);
+        assert_eq!(range, IpRange::new("192.168.1.0".parse().unwrap(), 24));
+
+        let range = IpRange::new("2001:db8::".parse().unwrap(), 32);
+        assert!(range.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!range.contains("2001:db9::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn ban_expire() {
+        let mut ban_list = BanList::default();
+        ban_list.ban_client_id(1, Some(Duration::from_secs(10)));
+        ban_list.ban_client_id(2, None);
+        ban_list.ban_ip_range(IpRange::new("10.0.0.0".parse().unwrap(), 8), Some(Duration::from_secs(5)));
+
+        ban_list.update(Duration::from_secs(5));
+        assert!(ban_list.is_client_id_banned(1));
+        assert!(!ban_list.is_ip_banned("10.1.2.3".parse().unwrap()));
+
+        ban_list.update(Duration::from_secs(10));
+        assert!(!ban_list.is_client_id_banned(1));
+        assert!(ban_list.is_client_id_banned(2));
+    }
+
+    #[test]
+    fn revoked_token_expire() {
+        let mut ban_list = BanList::default();
+        let mac = [7u8; NETCODE_MAC_BYTES];
+        ban_list.revoke_token(mac, 30);
+
+        ban_list.update(Duration::from_secs(29));
+        assert!(ban_list.is_token_revoked(&mac));
+
+        ban_list.update(Duration::from_secs(30));
+        assert!(!ban_list.is_token_revoked(&mac));
+        assert!(ban_list.revoked_tokens.is_empty());
+    }
+}
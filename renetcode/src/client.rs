@@ -1,12 +1,13 @@
 use std::{fmt, net::SocketAddr, time::Duration};
 
 use crate::{
-    packet::Packet, replay_protection::ReplayProtection, token::ConnectToken, ClientID, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES,
-    NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_SEND_RATE,
+    crypto::generate_random_bytes, packet::Packet, replay_protection::ReplayProtection, token::ConnectToken, ClientID, NetcodeError,
+    NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_REASON_MESSAGE_BYTES,
+    NETCODE_PROBE_WINDOW, NETCODE_SEND_RATE, NETCODE_VERSION_INFO,
 };
 
 /// The reason why a client is in error state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DisconnectReason {
     ConnectTokenExpired,
     ConnectionTimedOut,
@@ -15,16 +16,71 @@ pub enum DisconnectReason {
     ConnectionDenied,
     DisconnectedByClient,
     DisconnectedByServer,
+    /// The server denied the connection with a reason from the application.
+    ConnectionDeniedWithReason(ApplicationReason),
+    /// The server terminated the connection with a reason from the application.
+    DisconnectedByServerWithReason(ApplicationReason),
+}
+
+/// A reason defined by the application that the server can send when denying or terminating a
+/// connection, like "server full" or "kicked: AFK".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplicationReason {
+    code: u16,
+    message: String,
+}
+
+impl ApplicationReason {
+    /// Creates a new reason, the message is truncated to [NETCODE_MAX_REASON_MESSAGE_BYTES] bytes.
+    pub fn new(code: u16, message: impl Into<String>) -> Self {
+        let mut message = message.into();
+        if message.len() > NETCODE_MAX_REASON_MESSAGE_BYTES {
+            let mut len = NETCODE_MAX_REASON_MESSAGE_BYTES;
+            while !message.is_char_boundary(len) {
+                len -= 1;
+            }
+            message.truncate(len);
+        }
+
+        Self { code, message }
+    }
+
+    /// Application defined code for the reason.
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// Short message for the reason, up to [NETCODE_MAX_REASON_MESSAGE_BYTES] bytes.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ApplicationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum ClientState {
     Disconnected(DisconnectReason),
+    ProbingServers,
     SendingConnectionRequest,
     SendingConnectionResponse,
     Connected,
 }
 
+/// A server address being probed with pings. The pings have consecutive sequences from a random
+/// start, the round trip time is measured from the send time of the ping answered by the pong.
+#[derive(Debug)]
+struct ServerProbe {
+    addr: SocketAddr,
+    first_sequence: u64,
+    ping_send_times: Vec<Duration>,
+    rtt: Option<Duration>,
+}
+
 /// A client that can generate encrypted packets that be sent to the connected server, or consume
 /// encrypted packets from the server.
 /// The client is agnostic from the transport layer, only consuming and generating bytes
@@ -40,6 +96,10 @@ pub struct NetcodeClient {
     sequence: u64,
     server_addr: SocketAddr,
     server_addr_index: usize,
+    server_addresses: Vec<SocketAddr>,
+    failed_server_addresses: Vec<(SocketAddr, DisconnectReason)>,
+    probes: Vec<ServerProbe>,
+    first_probe_response_time: Option<Duration>,
     connect_token: ConnectToken,
     challenge_token_sequence: u64,
     challenge_token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES],
@@ -62,19 +122,28 @@ impl fmt::Display for DisconnectReason {
             ConnectionDenied => write!(f, "server denied connection"),
             DisconnectedByClient => write!(f, "connection terminated by client"),
             DisconnectedByServer => write!(f, "connection terminated by server"),
+            ConnectionDeniedWithReason(ref reason) => write!(f, "server denied connection: {}", reason),
+            DisconnectedByServerWithReason(ref reason) => write!(f, "connection terminated by server: {}", reason),
         }
     }
 }
 
 impl NetcodeClient {
     pub fn new(current_time: Duration, connect_token: ConnectToken) -> Self {
-        let server_addr = connect_token.server_addresses[0].expect("cannot create or deserialize a ConnectToken without a server address");
+        let server_addresses: Vec<SocketAddr> = connect_token.server_addresses.iter().flatten().copied().collect();
+        let server_addr = *server_addresses
+            .first()
+            .expect("cannot create or deserialize a ConnectToken without a server address");
 
         Self {
             sequence: 0,
             client_id: connect_token.client_id,
             server_addr,
             server_addr_index: 0,
+            server_addresses,
+            failed_server_addresses: Vec::new(),
+            probes: Vec::new(),
+            first_probe_response_time: None,
             challenge_token_sequence: 0,
             state: ClientState::SendingConnectionRequest,
             connect_start_time: current_time,
@@ -91,6 +160,28 @@ impl NetcodeClient {
         }
     }
 
+    /// Creates a client that probes all the server addresses in the connect token at the same
+    /// time, and connects to the one with the lowest round trip time.
+    /// The probes are pings, the servers that respond are sorted by their round trip time and the
+    /// others are only tried if the faster ones fail. Pings are not part of the netcode standard,
+    /// when no server responds before the connection timeout the servers are tried in the connect token order.
+    pub fn new_with_server_probing(current_time: Duration, connect_token: ConnectToken) -> Self {
+        let mut client = Self::new(current_time, connect_token);
+        client.state = ClientState::ProbingServers;
+        client.probes = client
+            .server_addresses
+            .iter()
+            .map(|&addr| ServerProbe {
+                addr,
+                first_sequence: u64::from_le_bytes(generate_random_bytes()),
+                ping_send_times: Vec::new(),
+                rtt: None,
+            })
+            .collect();
+
+        client
+    }
+
     pub fn connected(&self) -> bool {
         self.state == ClientState::Connected
     }
@@ -103,10 +194,15 @@ impl NetcodeClient {
         self.client_id
     }
 
+    /// Returns the index of the client slot in the server, only valid when connected.
+    pub fn client_index(&self) -> u32 {
+        self.client_index
+    }
+
     /// Returns the reason that the client was disconnected for.
     pub fn disconnected(&self) -> Option<DisconnectReason> {
         if let ClientState::Disconnected(reason) = &self.state {
-            return Some(*reason);
+            return Some(reason.clone());
         }
         None
     }
@@ -116,11 +212,26 @@ impl NetcodeClient {
         self.server_addr
     }
 
+    /// Returns the server addresses from the connect token that the client failed to connect to,
+    /// in order, with the reason of the failure.
+    pub fn failed_server_addresses(&self) -> &[(SocketAddr, DisconnectReason)] {
+        &self.failed_server_addresses
+    }
+
+    /// Returns the round trip times measured while probing the servers.
+    /// Empty if the client was not created with [NetcodeClient::new_with_server_probing].
+    pub fn server_rtts(&self) -> Vec<(SocketAddr, Duration)> {
+        self.probes
+            .iter()
+            .filter_map(|probe| probe.rtt.map(|rtt| (probe.addr, rtt)))
+            .collect()
+    }
+
     /// Disconnect the client from the server.
     /// Returns a disconnect packet that should be sent to the server.
     pub fn disconnect(&mut self) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
-        self.state = ClientState::Disconnected(DisconnectReason::DisconnectedByClient);
-        let packet = Packet::Disconnect;
+        self.set_state(ClientState::Disconnected(DisconnectReason::DisconnectedByClient));
+        let packet = Packet::Disconnect(None);
         let len = packet.encode(
             &mut self.out,
             self.connect_token.protocol_id,
@@ -134,17 +245,45 @@ impl NetcodeClient {
     /// server. If nothing is returned, it was a packet used for the internal protocol or an
     /// invalid packet.
     pub fn process_packet<'a>(&mut self, buffer: &'a mut [u8]) -> Option<&'a [u8]> {
-        let (_, packet) = Packet::decode(
+        self.process_packet_from(self.server_addr, buffer)
+    }
+
+    /// Process a packet received from the address. Packets that are not from the current server are
+    /// discarded, except while probing the servers.
+    pub fn process_packet_from<'a>(&mut self, addr: SocketAddr, buffer: &'a mut [u8]) -> Option<&'a [u8]> {
+        trace_span!(trace, "netcode_client_packet", client_id = self.client_id, addr = addr);
+        if self.state == ClientState::ProbingServers {
+            self.process_probe_packet(addr, buffer);
+            return None;
+        }
+
+        if addr != self.server_addr {
+            trace_event!(trace, "discarded packet from unknown address", len = buffer.len());
+            return None;
+        }
+
+        let len = buffer.len();
+        let packet = match Packet::decode(
             buffer,
             self.connect_token.protocol_id,
             Some(&self.connect_token.server_to_client_key),
             Some(&mut self.replay_protection),
-        )
-        .ok()?;
+        ) {
+            Ok((_, packet)) => packet,
+            Err(e) => {
+                trace_event!(debug, "discarded invalid packet", len = len, error = e);
+                return None;
+            }
+        };
+        trace_event!(trace, "received packet", packet_type = packet.packet_type(), len = len);
         match (packet, &self.state) {
-            (Packet::ConnectionDenied, ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse) => {
-                self.state = ClientState::Disconnected(DisconnectReason::ConnectionDenied);
-                self.last_packet_received_time = self.current_time;
+            (Packet::ConnectionDenied(reason), ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse) => {
+                let reason = match reason {
+                    Some(reason) => DisconnectReason::ConnectionDeniedWithReason(reason),
+                    None => DisconnectReason::ConnectionDenied,
+                };
+                // The error only means that there are no more servers, the client stays disconnected.
+                let _ = self.connect_to_next_server(reason);
             }
             (
                 Packet::Challenge {
@@ -157,7 +296,7 @@ impl NetcodeClient {
                 self.last_packet_received_time = self.current_time;
                 self.last_packet_send_time = None;
                 self.challenge_token_data = token_data;
-                self.state = ClientState::SendingConnectionResponse;
+                self.set_state(ClientState::SendingConnectionResponse);
             }
             (Packet::KeepAlive { .. }, ClientState::Connected) => {
                 self.last_packet_received_time = self.current_time;
@@ -166,14 +305,18 @@ impl NetcodeClient {
                 self.last_packet_received_time = self.current_time;
                 self.max_clients = max_clients;
                 self.client_index = client_index;
-                self.state = ClientState::Connected;
+                self.set_state(ClientState::Connected);
             }
             (Packet::Payload(p), ClientState::Connected) => {
                 self.last_packet_received_time = self.current_time;
                 return Some(p);
             }
-            (Packet::Disconnect, ClientState::Connected) => {
-                self.state = ClientState::Disconnected(DisconnectReason::DisconnectedByServer);
+            (Packet::Disconnect(reason), ClientState::Connected) => {
+                let reason = match reason {
+                    Some(reason) => DisconnectReason::DisconnectedByServerWithReason(reason),
+                    None => DisconnectReason::DisconnectedByServer,
+                };
+                self.set_state(ClientState::Disconnected(reason));
                 self.last_packet_received_time = self.current_time;
             }
             _ => {}
@@ -182,6 +325,70 @@ impl NetcodeClient {
         None
     }
 
+    fn process_probe_packet(&mut self, addr: SocketAddr, buffer: &mut [u8]) {
+        let (version_info, protocol_id, sequence) = match Packet::decode(buffer, self.connect_token.protocol_id, None, None) {
+            Ok((
+                _,
+                Packet::Pong {
+                    version_info,
+                    protocol_id,
+                    sequence,
+                },
+            )) => (version_info, protocol_id, sequence),
+            Ok(_) => return,
+            Err(e) => {
+                trace_event!(debug, "discarded invalid probe response", error = e);
+                return;
+            }
+        };
+        if version_info != *NETCODE_VERSION_INFO || protocol_id != self.connect_token.protocol_id {
+            return;
+        }
+
+        let current_time = self.current_time;
+        let probe = match self.probes.iter_mut().find(|probe| probe.addr == addr && probe.rtt.is_none()) {
+            Some(probe) => probe,
+            None => return,
+        };
+        let index = sequence.wrapping_sub(probe.first_sequence);
+        let send_time = match probe.ping_send_times.get(index as usize) {
+            Some(send_time) => *send_time,
+            None => return,
+        };
+        probe.rtt = Some(current_time - send_time);
+        trace_event!(
+            debug,
+            "server probe responded",
+            client_id = self.client_id,
+            server_addr = addr,
+            rtt = probe.rtt
+        );
+        self.first_probe_response_time.get_or_insert(current_time);
+
+        if self.probes.iter().all(|probe| probe.rtt.is_some()) {
+            self.select_probed_server();
+        }
+    }
+
+    /// Connects to the probed server with the lowest round trip time. The servers are ordered by it,
+    /// the ones that didn't respond stay at the end in the connect token order.
+    fn select_probed_server(&mut self) {
+        self.probes.sort_by_key(|probe| probe.rtt.unwrap_or(Duration::MAX));
+        self.server_addresses = self.probes.iter().map(|probe| probe.addr).collect();
+        self.server_addr_index = 0;
+        self.server_addr = self.server_addresses[0];
+        trace_event!(
+            debug,
+            "selected probed server",
+            client_id = self.client_id,
+            server_addr = self.server_addr,
+            rtt = self.probes[0].rtt
+        );
+        self.last_packet_received_time = self.current_time;
+        self.last_packet_send_time = None;
+        self.set_state(ClientState::SendingConnectionRequest);
+    }
+
     /// Returns the server address and an encrypted payload packet that can be sent to the server.
     pub fn generate_payload_packet(&mut self, payload: &[u8]) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
         if payload.len() > NETCODE_MAX_PAYLOAD_BYTES {
@@ -199,6 +406,7 @@ impl NetcodeClient {
             Some((self.sequence, &self.connect_token.client_to_server_key)),
         )?;
         self.sequence += 1;
+        trace_event!(trace, "sending payload packet", client_id = self.client_id, len = len);
 
         Ok((self.server_addr, &mut self.out[..len]))
     }
@@ -206,8 +414,13 @@ impl NetcodeClient {
     /// Update the internal state of the client, receives the duration since last updated.
     /// Might return the serve address and a protocol packet to be sent to the server.
     pub fn update(&mut self, duration: Duration) -> Option<(&mut [u8], SocketAddr)> {
-        if let Err(_e) = self.update_internal_state(duration) {
-            // TODO(log): error
+        trace_span!(trace, "netcode_client_update", client_id = self.client_id);
+        let was_disconnected = matches!(self.state, ClientState::Disconnected(_));
+        if let Err(e) = self.update_internal_state(duration) {
+            // Disconnected clients return an error in every update, only the first one is reported.
+            if !was_disconnected {
+                trace_event!(debug, "netcode client update failed", client_id = self.client_id, error = e);
+            }
             return None;
         }
 
@@ -221,54 +434,114 @@ impl NetcodeClient {
             && (self.last_packet_received_time + Duration::from_secs(self.connect_token.timeout_seconds as u64) < self.current_time);
 
         match self.state {
-            ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse => {
+            ClientState::ProbingServers | ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse => {
                 let expire_seconds = self.connect_token.expire_timestamp - self.connect_token.create_timestamp;
                 let connection_expired = (self.current_time - self.connect_start_time).as_secs() >= expire_seconds;
                 if connection_expired {
-                    self.state = ClientState::Disconnected(DisconnectReason::ConnectTokenExpired);
+                    self.set_state(ClientState::Disconnected(DisconnectReason::ConnectTokenExpired));
                     return Err(NetcodeError::Expired);
                 }
+                if self.state == ClientState::ProbingServers {
+                    return self.update_probes(connection_timed_out);
+                }
                 if connection_timed_out {
                     let reason = if self.state == ClientState::SendingConnectionResponse {
                         DisconnectReason::ConnectionResponseTimedOut
                     } else {
                         DisconnectReason::ConnectionRequestTimedOut
                     };
-                    self.state = ClientState::Disconnected(reason);
-                    // Try to connect to the next server address
-                    self.server_addr_index += 1;
-                    if self.server_addr_index >= 32 {
-                        return Err(NetcodeError::NoMoreServers);
-                    }
-                    match self.connect_token.server_addresses[self.server_addr_index] {
-                        None => return Err(NetcodeError::NoMoreServers),
-                        Some(server_address) => {
-                            self.state = ClientState::SendingConnectionRequest;
-                            self.server_addr = server_address;
-                            self.connect_start_time = self.current_time;
-                            self.last_packet_send_time = None;
-                            self.last_packet_received_time = self.current_time;
-                            self.challenge_token_sequence = 0;
-
-                            return Ok(());
-                        }
-                    }
+                    return self.connect_to_next_server(reason);
                 }
                 Ok(())
             }
             ClientState::Connected => {
                 if connection_timed_out {
-                    self.state = ClientState::Disconnected(DisconnectReason::ConnectionTimedOut);
+                    self.set_state(ClientState::Disconnected(DisconnectReason::ConnectionTimedOut));
                     return Err(NetcodeError::Disconnected(DisconnectReason::ConnectionTimedOut));
                 }
 
                 Ok(())
             }
-            ClientState::Disconnected(reason) => Err(NetcodeError::Disconnected(reason)),
+            ClientState::Disconnected(ref reason) => Err(NetcodeError::Disconnected(reason.clone())),
         }
     }
 
+    fn update_probes(&mut self, connection_timed_out: bool) -> Result<(), NetcodeError> {
+        if let Some(first_response_time) = self.first_probe_response_time {
+            // Give some time for the other servers to respond after the first one
+            if self.current_time - first_response_time >= NETCODE_PROBE_WINDOW {
+                self.select_probed_server();
+            }
+            return Ok(());
+        }
+
+        // Servers that don't support pings never respond, connect to the servers in the connect token order
+        if connection_timed_out {
+            trace_event!(debug, "no server probe responded", client_id = self.client_id);
+            self.connect_to_server(0);
+        }
+
+        Ok(())
+    }
+
+    /// Records the failure with the current server and starts connecting to the next server address
+    /// in the connect token. When there are no more addresses the client is disconnected with the reason.
+    fn connect_to_next_server(&mut self, reason: DisconnectReason) -> Result<(), NetcodeError> {
+        self.failed_server_addresses.push((self.server_addr, reason.clone()));
+        if self.server_addr_index + 1 >= self.server_addresses.len() {
+            self.set_state(ClientState::Disconnected(reason));
+            return Err(NetcodeError::NoMoreServers);
+        }
+
+        trace_event!(
+            debug,
+            "connecting to next server",
+            client_id = self.client_id,
+            server_addr = self.server_addresses[self.server_addr_index + 1],
+            reason = reason
+        );
+        self.connect_to_server(self.server_addr_index + 1);
+        Ok(())
+    }
+
+    /// Starts sending connection requests to the server address with the index in the connect token.
+    fn connect_to_server(&mut self, server_addr_index: usize) {
+        self.server_addr_index = server_addr_index;
+        self.server_addr = self.server_addresses[server_addr_index];
+        self.set_state(ClientState::SendingConnectionRequest);
+        self.connect_start_time = self.current_time;
+        self.last_packet_send_time = None;
+        self.last_packet_received_time = self.current_time;
+        self.challenge_token_sequence = 0;
+    }
+
+    fn set_state(&mut self, state: ClientState) {
+        match &state {
+            ClientState::Disconnected(reason) => {
+                trace_event!(
+                    info,
+                    "netcode client disconnected",
+                    client_id = self.client_id,
+                    server_addr = self.server_addr,
+                    reason = reason
+                )
+            }
+            _ => trace_event!(
+                debug,
+                "netcode client state changed",
+                client_id = self.client_id,
+                server_addr = self.server_addr,
+                state = state
+            ),
+        }
+        self.state = state;
+    }
+
     fn generate_packet(&mut self) -> Option<(&mut [u8], SocketAddr)> {
+        if self.state == ClientState::ProbingServers {
+            return self.generate_probe_packet();
+        }
+
         if let Some(last_packet_send_time) = self.last_packet_send_time {
             if self.current_time - last_packet_send_time < self.send_rate {
                 return None;
@@ -300,13 +573,47 @@ impl NetcodeClient {
             Some((self.sequence, &self.connect_token.client_to_server_key)),
         );
         match result {
-            Err(_) => None,
+            Err(e) => {
+                trace_event!(
+                    error,
+                    "failed to encode netcode client packet",
+                    client_id = self.client_id,
+                    error = e
+                );
+                None
+            }
             Ok(encoded) => {
+                trace_event!(
+                    trace,
+                    "sending packet",
+                    client_id = self.client_id,
+                    packet_type = packet.packet_type(),
+                    len = encoded
+                );
                 self.sequence += 1;
                 Some((&mut self.out[..encoded], self.server_addr))
             }
         }
     }
+
+    /// Returns a ping for the next server that is waiting for a probe,
+    /// only one packet is generated for each update.
+    fn generate_probe_packet(&mut self) -> Option<(&mut [u8], SocketAddr)> {
+        let current_time = self.current_time;
+        let send_rate = self.send_rate;
+        let probe = self.probes.iter_mut().find(|probe| {
+            let waiting_resend = matches!(probe.ping_send_times.last(), Some(&last_send_time) if current_time - last_send_time < send_rate);
+            probe.rtt.is_none() && !waiting_resend
+        })?;
+        let sequence = probe.first_sequence.wrapping_add(probe.ping_send_times.len() as u64);
+        probe.ping_send_times.push(current_time);
+        let addr = probe.addr;
+
+        let packet = Packet::ping(self.connect_token.protocol_id, sequence);
+        let len = packet.encode(&mut self.out, self.connect_token.protocol_id, None).ok()?;
+
+        Some((&mut self.out[..len], addr))
+    }
 }
 
 #[cfg(test)]
@@ -380,4 +687,128 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn connect_token_timeout() {
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let builder = ConnectToken::builder()
+            .expire_seconds(60)
+            .server_address("127.0.0.1:8080".parse().unwrap());
+
+        let connect_token = builder.clone().timeout_seconds(2).generate(Duration::ZERO, private_key).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        client.update(Duration::from_secs(1));
+        assert!(client.disconnected().is_none());
+        client.update(Duration::from_secs(2));
+        assert_eq!(client.disconnected(), Some(DisconnectReason::ConnectionRequestTimedOut));
+
+        // Negative timeouts disable it
+        let connect_token = builder.timeout_seconds(-1).generate(Duration::ZERO, private_key).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        client.update(Duration::from_secs(30));
+        assert!(client.disconnected().is_none());
+    }
+
+    #[test]
+    fn server_address_failover() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let first_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let second_addr: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let connect_token = ConnectToken::builder()
+            .timeout_seconds(1)
+            .server_addresses(vec![first_addr, second_addr])
+            .generate(Duration::ZERO, private_key)
+            .unwrap();
+        let server_key = connect_token.server_to_client_key;
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        assert_eq!(client.server_addr(), first_addr);
+
+        let (_, addr) = client.update(Duration::from_secs(2)).unwrap();
+        assert_eq!(addr, second_addr);
+        assert_eq!(client.server_addr(), second_addr);
+        assert_eq!(
+            client.failed_server_addresses(),
+            &[(first_addr, DisconnectReason::ConnectionRequestTimedOut)]
+        );
+
+        let len = Packet::ConnectionDenied(None)
+            .encode(&mut buffer, 0, Some((0, &server_key)))
+            .unwrap();
+        client.process_packet(&mut buffer[..len]);
+        assert_eq!(client.disconnected(), Some(DisconnectReason::ConnectionDenied));
+        assert_eq!(client.failed_server_addresses().len(), 2);
+    }
+
+    #[test]
+    fn server_probing() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let far_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let near_addr: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let down_addr: SocketAddr = "127.0.0.1:8082".parse().unwrap();
+        let connect_token = ConnectToken::builder()
+            .server_addresses(vec![far_addr, near_addr, down_addr])
+            .generate(Duration::ZERO, private_key)
+            .unwrap();
+        let server_key = connect_token.server_to_client_key;
+        let mut client = NetcodeClient::new_with_server_probing(Duration::ZERO, connect_token);
+
+        // One ping is sent to each server
+        let mut pings = vec![];
+        for _ in 0..4 {
+            if let Some((packet, addr)) = client.update(Duration::ZERO) {
+                match Packet::decode(packet, 0, None, None).unwrap() {
+                    (_, Packet::Ping { sequence, .. }) => pings.push((addr, sequence)),
+                    _ => unreachable!(),
+                }
+            }
+        }
+        assert_eq!(
+            pings.iter().map(|(addr, _)| *addr).collect::<Vec<_>>(),
+            vec![far_addr, near_addr, down_addr]
+        );
+        let pong = |buffer: &mut [u8], sequence: u64| {
+            let packet = Packet::Pong {
+                version_info: *NETCODE_VERSION_INFO,
+                protocol_id: 0,
+                sequence,
+            };
+            let len = packet.encode(buffer, 0, None).unwrap();
+            buffer[..len].to_vec()
+        };
+
+        // The far server answers the resent ping, the time is measured from it
+        client.update(Duration::from_millis(20));
+        client.process_packet_from(near_addr, &mut pong(&mut buffer, pings[1].1));
+        let (packet, addr) = client.update(NETCODE_SEND_RATE).unwrap();
+        assert_eq!(addr, far_addr);
+        let resent_sequence = match Packet::decode(packet, 0, None, None).unwrap() {
+            (_, Packet::Ping { sequence, .. }) => sequence,
+            _ => unreachable!(),
+        };
+        assert_eq!(resent_sequence, pings[0].1.wrapping_add(1));
+        client.update(Duration::from_millis(100));
+        client.process_packet_from(far_addr, &mut pong(&mut buffer, resent_sequence));
+        assert_eq!(client.state, ClientState::ProbingServers);
+        assert_eq!(
+            client.server_rtts(),
+            vec![(far_addr, Duration::from_millis(100)), (near_addr, Duration::from_millis(20))]
+        );
+
+        // The third server doesn't respond, the fastest server is selected after the probe window
+        let (packet, addr) = client.update(NETCODE_PROBE_WINDOW).unwrap();
+        assert_eq!(addr, near_addr);
+        let (_, packet) = Packet::decode(packet, 0, None, None).unwrap();
+        assert!(matches!(packet, Packet::ConnectionRequest { .. }));
+        assert_eq!(client.server_addr(), near_addr);
+
+        // The other servers are tried in order of latency
+        let len = Packet::ConnectionDenied(None)
+            .encode(&mut buffer, 0, Some((1, &server_key)))
+            .unwrap();
+        client.process_packet_from(near_addr, &mut buffer[..len]);
+        assert_eq!(client.server_addr(), far_addr);
+        assert_eq!(client.failed_server_addresses(), &[(near_addr, DisconnectReason::ConnectionDenied)]);
+    }
 }
@@ -53,7 +53,7 @@ impl fmt::Display for NetcodeError {
             PayloadAboveLimit => write!(fmt, "payload is above the {} bytes limit", NETCODE_MAX_PAYLOAD_BYTES),
             Expired => write!(fmt, "connection expired"),
             DuplicatedSequence => write!(fmt, "sequence already received"),
-            Disconnected(reason) => write!(fmt, "disconnected: {}", reason),
+            Disconnected(ref reason) => write!(fmt, "disconnected: {}", reason),
             NoMoreServers => write!(fmt, "client has no more servers to connect"),
             CryptoError => write!(fmt, "error while encoding or decoding"),
             NotInHostList => write!(fmt, "token does not contain the server address"),
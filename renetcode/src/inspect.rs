@@ -0,0 +1,125 @@
+//! Decoding of netcode packets outside of a client or server, used to inspect captures.
+use crate::packet::decode_prefix;
+use crate::token::PrivateConnectToken;
+use crate::{NetcodeError, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES};
+
+pub use crate::packet::{Packet, PacketType};
+
+/// The packet type and sequence from the packet prefix, available without the keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub prefix: u8,
+    pub packet_type: PacketType,
+    /// Always 0 for connection requests, they don't have a sequence.
+    pub sequence: u64,
+}
+
+/// The client id and packet keys of a connection, from the private connect token of its connection request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionKeys {
+    pub client_id: u64,
+    pub client_to_server_key: [u8; NETCODE_KEY_BYTES],
+    pub server_to_client_key: [u8; NETCODE_KEY_BYTES],
+}
+
+/// Reads the prefix and the sequence of the packet without decrypting it.
+pub fn read_header(buffer: &[u8]) -> Result<PacketHeader, NetcodeError> {
+    if buffer.len() < 2 + NETCODE_MAC_BYTES {
+        return Err(NetcodeError::PacketTooSmall);
+    }
+
+    let prefix = buffer[0];
+    let (packet_type, sequence_len) = decode_prefix(prefix);
+    let packet_type = PacketType::from_u8(packet_type)?;
+    if !packet_type.is_encrypted() {
+        return Ok(PacketHeader {
+            prefix,
+            packet_type,
+            sequence: 0,
+        });
+    }
+
+    if sequence_len == 0 || sequence_len > 8 || buffer.len() < 1 + sequence_len + NETCODE_MAC_BYTES {
+        return Err(NetcodeError::PacketTooSmall);
+    }
+    let mut sequence = [0u8; 8];
+    sequence[..sequence_len].copy_from_slice(&buffer[1..1 + sequence_len]);
+
+    Ok(PacketHeader {
+        prefix,
+        packet_type,
+        sequence: u64::from_le_bytes(sequence),
+    })
+}
+
+/// Decrypts and decodes the packet with the key, without replay protection.
+/// Connection requests are not encrypted, the key is not used for them.
+pub fn decode_packet<'a>(buffer: &'a mut [u8], protocol_id: u64, key: &[u8; NETCODE_KEY_BYTES]) -> Result<Packet<'a>, NetcodeError> {
+    let (_, packet) = Packet::decode(buffer, protocol_id, Some(key), None)?;
+    Ok(packet)
+}
+
+/// Decrypts the private connect token of a connection request with the server private key.
+pub fn decrypt_connection_request(packet: &Packet, private_key: &[u8; NETCODE_KEY_BYTES]) -> Result<ConnectionKeys, NetcodeError> {
+    match packet {
+        Packet::ConnectionRequest {
+            protocol_id,
+            expire_timestamp,
+            xnonce,
+            data,
+            ..
+        } => {
+            let token = PrivateConnectToken::decode(data, *protocol_id, *expire_timestamp, xnonce, private_key)?;
+            Ok(ConnectionKeys {
+                client_id: token.client_id,
+                client_to_server_key: token.client_to_server_key,
+                server_to_client_key: token.server_to_client_key,
+            })
+        }
+        _ => Err(NetcodeError::InvalidPacketType),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{ConnectToken, NETCODE_MAX_PACKET_BYTES};
+
+    #[test]
+    fn inspect_connection_request_and_payload() {
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let protocol_id = 7;
+        let token = ConnectToken::builder()
+            .protocol_id(protocol_id)
+            .client_id(4)
+            .server_address("127.0.0.1:5000".parse().unwrap())
+            .generate(Duration::ZERO, private_key)
+            .unwrap();
+
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let len = Packet::connection_request_from_token(&token)
+            .encode(&mut buffer, protocol_id, None)
+            .unwrap();
+        let header = read_header(&buffer[..len]).unwrap();
+        assert!(matches!(header.packet_type, PacketType::ConnectionRequest));
+
+        let request = decode_packet(&mut buffer[..len], protocol_id, &[0; NETCODE_KEY_BYTES]).unwrap();
+        let keys = decrypt_connection_request(&request, private_key).unwrap();
+        assert_eq!(keys.client_id, 4);
+        assert_eq!(keys.client_to_server_key, token.client_to_server_key);
+        assert!(decrypt_connection_request(&request, &[1; NETCODE_KEY_BYTES]).is_err());
+
+        let len = Packet::Payload(&[3; 10])
+            .encode(&mut buffer, protocol_id, Some((300, &keys.client_to_server_key)))
+            .unwrap();
+        let header = read_header(&buffer[..len]).unwrap();
+        assert!(matches!(header.packet_type, PacketType::Payload));
+        assert_eq!(header.sequence, 300);
+
+        assert!(decode_packet(&mut buffer[..len].to_vec(), protocol_id, &keys.server_to_client_key).is_err());
+        let packet = decode_packet(&mut buffer[..len], protocol_id, &keys.client_to_server_key).unwrap();
+        assert_eq!(packet, Packet::Payload(&[3; 10]));
+    }
+}
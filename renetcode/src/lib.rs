@@ -16,28 +16,38 @@
 //!
 //! [standard]: https://github.com/networkprotocol/netcode/blob/master/STANDARD.md
 //! [netcode]: https://github.com/networkprotocol/netcode
+#[macro_use]
+mod trace;
+
+mod ban_list;
 mod client;
 mod crypto;
 mod error;
+// Used in the renet_inspect crate
+#[doc(hidden)]
+pub mod inspect;
 mod packet;
+mod rate_limit;
 mod replay_protection;
 mod serialize;
 mod server;
 mod token;
 
-pub use client::{DisconnectReason, NetcodeClient};
+pub use ban_list::IpRange;
+pub use client::{ApplicationReason, DisconnectReason, NetcodeClient};
 pub use crypto::generate_random_bytes;
 pub use error::NetcodeError;
-pub use server::{NetcodeServer, ServerResult};
-pub use token::{ConnectToken, TokenGenerationError};
+pub use rate_limit::{RateLimitConfig, RateLimitStats};
+pub use server::{HandshakeStats, NetcodeServer, ServerResult};
+pub use token::{ConnectToken, ConnectTokenBuilder, TokenGenerationError};
 
 use std::time::Duration;
 
 type ClientID = u64;
 
 const NETCODE_VERSION_INFO: &[u8; 13] = b"NETCODE 1.02\0";
-const NETCODE_MAX_CLIENTS: usize = 1024;
-const NETCODE_MAX_PENDING_CLIENTS: usize = NETCODE_MAX_CLIENTS * 4;
+// Maximum number of pending clients, fixed regardless of the max clients of the server.
+const NETCODE_MAX_PENDING_CLIENTS: usize = 4096;
 
 const NETCODE_ADDRESS_NONE: u8 = 0;
 const NETCODE_ADDRESS_IPV4: u8 = 1;
@@ -51,7 +61,10 @@ pub const NETCODE_MAX_PAYLOAD_BYTES: usize = 1200;
 
 /// The number of bytes in a private key;
 pub const NETCODE_KEY_BYTES: usize = 32;
-const NETCODE_MAC_BYTES: usize = 16;
+/// The number of bytes of a MAC, used to identify connect tokens.
+pub const NETCODE_MAC_BYTES: usize = 16;
+/// The maximum number of bytes of an [ApplicationReason] message.
+pub const NETCODE_MAX_REASON_MESSAGE_BYTES: usize = 255;
 /// The number of bytes that an user data can contain in the ConnectToken.
 pub const NETCODE_USER_DATA_BYTES: usize = 256;
 const NETCODE_CHALLENGE_TOKEN_BYTES: usize = 300;
@@ -60,5 +73,8 @@ const NETCODE_CONNECT_TOKEN_XNONCE_BYTES: usize = 24;
 const NETCODE_ADDITIONAL_DATA_SIZE: usize = 13 + 8 + 8;
 
 const NETCODE_TIMEOUT_SECONDS: i32 = 15;
+const NETCODE_EXPIRE_SECONDS: u64 = 300;
 
 const NETCODE_SEND_RATE: Duration = Duration::from_millis(200);
+// Time to wait for other servers to respond after the first probe response.
+const NETCODE_PROBE_WINDOW: Duration = Duration::from_millis(500);
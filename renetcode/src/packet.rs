@@ -1,15 +1,16 @@
-use std::io::{self, Cursor, Write};
+use std::io::{self, Cursor, Read, Write};
 
 use crate::crypto::{dencrypted_in_place, encrypt_in_place};
 use crate::replay_protection::ReplayProtection;
 use crate::token::ConnectToken;
+use crate::ApplicationReason;
 use crate::{
     serialize::*, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES,
     NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
 };
 use crate::{NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum PacketType {
     ConnectionRequest = 0,
@@ -19,6 +20,9 @@ pub enum PacketType {
     KeepAlive = 4,
     Payload = 5,
     Disconnect = 6,
+    // Not in the netcode standard, used to measure the round trip time to the servers.
+    Ping = 7,
+    Pong = 8,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -31,7 +35,7 @@ pub enum Packet<'a> {
         xnonce: [u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES],
         data: [u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
     },
-    ConnectionDenied,
+    ConnectionDenied(Option<ApplicationReason>),
     Challenge {
         token_sequence: u64,
         token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES], // encrypted ChallengeToken
@@ -45,7 +49,19 @@ pub enum Packet<'a> {
         max_clients: u32,
     },
     Payload(&'a [u8]),
-    Disconnect,
+    Disconnect(Option<ApplicationReason>),
+    /// Sent by the client before connecting, the server answers with a pong with the same sequence.
+    /// Both have the same size so the server can't be used to amplify traffic.
+    Ping {
+        version_info: [u8; 13],
+        protocol_id: u64,
+        sequence: u64,
+    },
+    Pong {
+        version_info: [u8; 13],
+        protocol_id: u64,
+        sequence: u64,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -55,7 +71,7 @@ pub struct ChallengeToken {
 }
 
 impl PacketType {
-    fn from_u8(value: u8) -> Result<Self, NetcodeError> {
+    pub(crate) fn from_u8(value: u8) -> Result<Self, NetcodeError> {
         use PacketType::*;
 
         let packet_type = match value {
@@ -66,6 +82,8 @@ impl PacketType {
             4 => KeepAlive,
             5 => Payload,
             6 => Disconnect,
+            7 => Ping,
+            8 => Pong,
             _ => return Err(NetcodeError::InvalidPacketType),
         };
         Ok(packet_type)
@@ -76,18 +94,27 @@ impl PacketType {
 
         matches!(self, KeepAlive | Payload | Disconnect)
     }
+
+    /// Connection requests and pings are sent before the client has the keys, they are not encrypted.
+    pub(crate) fn is_encrypted(&self) -> bool {
+        use PacketType::*;
+
+        !matches!(self, ConnectionRequest | Ping | Pong)
+    }
 }
 
 impl<'a> Packet<'a> {
     pub fn packet_type(&self) -> PacketType {
         match self {
             Packet::ConnectionRequest { .. } => PacketType::ConnectionRequest,
-            Packet::ConnectionDenied => PacketType::ConnectionDenied,
+            Packet::ConnectionDenied(_) => PacketType::ConnectionDenied,
             Packet::Challenge { .. } => PacketType::Challenge,
             Packet::Response { .. } => PacketType::Response,
             Packet::KeepAlive { .. } => PacketType::KeepAlive,
             Packet::Payload { .. } => PacketType::Payload,
-            Packet::Disconnect => PacketType::Disconnect,
+            Packet::Disconnect(_) => PacketType::Disconnect,
+            Packet::Ping { .. } => PacketType::Ping,
+            Packet::Pong { .. } => PacketType::Pong,
         }
     }
 
@@ -105,6 +132,14 @@ impl<'a> Packet<'a> {
         }
     }
 
+    pub fn ping(protocol_id: u64, sequence: u64) -> Self {
+        Packet::Ping {
+            version_info: *NETCODE_VERSION_INFO,
+            protocol_id,
+            sequence,
+        }
+    }
+
     pub fn generate_challenge(
         client_id: u64,
         user_data: &[u8; NETCODE_USER_DATA_BYTES],
@@ -155,7 +190,29 @@ impl<'a> Packet<'a> {
             Packet::Payload(p) => {
                 writer.write_all(p)?;
             }
-            Packet::ConnectionDenied | Packet::Disconnect => {}
+            Packet::Ping {
+                version_info,
+                protocol_id,
+                sequence,
+            }
+            | Packet::Pong {
+                version_info,
+                protocol_id,
+                sequence,
+            } => {
+                writer.write_all(version_info)?;
+                writer.write_all(&protocol_id.to_le_bytes())?;
+                writer.write_all(&sequence.to_le_bytes())?;
+            }
+            // The reason is not in the netcode standard, packets without it are the same as the standard ones.
+            Packet::ConnectionDenied(reason) | Packet::Disconnect(reason) => {
+                if let Some(reason) = reason {
+                    let message = reason.message().as_bytes();
+                    writer.write_all(&reason.code().to_le_bytes())?;
+                    writer.write_all(&(message.len() as u8).to_le_bytes())?;
+                    writer.write_all(message)?;
+                }
+            }
         }
 
         Ok(())
@@ -208,17 +265,36 @@ impl<'a> Packet<'a> {
 
                 Ok(Packet::KeepAlive { client_index, max_clients })
             }
-            PacketType::ConnectionDenied => Ok(Packet::ConnectionDenied),
-            PacketType::Disconnect => Ok(Packet::Disconnect),
+            PacketType::ConnectionDenied => Ok(Packet::ConnectionDenied(read_reason(src)?)),
+            PacketType::Disconnect => Ok(Packet::Disconnect(read_reason(src)?)),
+            PacketType::Ping | PacketType::Pong => {
+                let version_info = read_bytes(src)?;
+                let protocol_id = read_u64(src)?;
+                let sequence = read_u64(src)?;
+
+                if packet_type == PacketType::Ping {
+                    Ok(Packet::Ping {
+                        version_info,
+                        protocol_id,
+                        sequence,
+                    })
+                } else {
+                    Ok(Packet::Pong {
+                        version_info,
+                        protocol_id,
+                        sequence,
+                    })
+                }
+            }
             PacketType::Payload => unreachable!(),
         }
     }
 
     pub fn encode(&self, buffer: &mut [u8], protocol_id: u64, crypto_info: Option<(u64, &[u8; 32])>) -> Result<usize, NetcodeError> {
-        if matches!(self, Packet::ConnectionRequest { .. }) {
+        if !self.packet_type().is_encrypted() {
+            // Packets without encryption have no sequence, the prefix byte is only the packet type
             let mut writer = io::Cursor::new(buffer);
-            let prefix_byte = encode_prefix(self.id(), 0);
-            writer.write_all(&prefix_byte.to_le_bytes())?;
+            writer.write_all(&[self.id()])?;
 
             self.write(&mut writer)?;
             Ok(writer.position() as usize)
@@ -266,8 +342,8 @@ impl<'a> Packet<'a> {
         let (packet_type, sequence_len) = decode_prefix(prefix_byte);
         let packet_type = PacketType::from_u8(packet_type)?;
 
-        if matches!(packet_type, PacketType::ConnectionRequest) {
-            Ok((0, Packet::read(PacketType::ConnectionRequest, &buffer[1..])?))
+        if !packet_type.is_encrypted() {
+            Ok((0, Packet::read(packet_type, &buffer[1..])?))
         } else if let Some(private_key) = private_key {
             let (sequence, aad, read_pos) = {
                 let src = &mut io::Cursor::new(&mut buffer);
@@ -343,17 +419,33 @@ fn get_additional_data(prefix: u8, protocol_id: u64) -> [u8; 13 + 8 + 1] {
     buffer
 }
 
-fn decode_prefix(value: u8) -> (u8, usize) {
+pub(crate) fn decode_prefix(value: u8) -> (u8, usize) {
     ((value & 0xF) as u8, (value >> 4) as usize)
 }
 
+fn read_reason(src: &mut Cursor<&[u8]>) -> Result<Option<ApplicationReason>, io::Error> {
+    if src.position() as usize == src.get_ref().len() {
+        return Ok(None);
+    }
+
+    let code = read_u16(src)?;
+    let len = read_u8(src)?;
+    let mut message = vec![0u8; len as usize];
+    src.read_exact(&mut message)?;
+    let message = String::from_utf8(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(Some(ApplicationReason::new(code, message)))
+}
+
 fn encode_prefix(value: u8, sequence: u64) -> u8 {
     value | ((sequence_bytes_required(sequence) as u8) << 4)
 }
 
+// The sequence is always written with at least one byte, so packets without content,
+// like the connection denied with sequence 0, still have the minimum packet size.
 fn sequence_bytes_required(sequence: u64) -> usize {
     let mut mask: u64 = 0xFF00_0000_0000_0000;
-    for i in 0..8 {
+    for i in 0..7 {
         if (sequence & mask) != 0x00 {
             return 8 - i;
         }
@@ -361,7 +453,7 @@ fn sequence_bytes_required(sequence: u64) -> usize {
         mask >>= 8;
     }
 
-    0
+    1
 }
 
 fn write_sequence(out: &mut impl io::Write, seq: u64) -> Result<usize, io::Error> {
@@ -396,6 +488,12 @@ mod tests {
         let deserialized = Packet::read(PacketType::ConnectionRequest, &buffer).unwrap();
 
         assert_eq!(deserialized, connection_request);
+
+        // The standard connection request starts with the prefix byte 0
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let len = connection_request.encode(&mut buffer, 1, None).unwrap();
+        assert_eq!(buffer[0], 0);
+        assert_eq!(len, 1078);
     }
 
     #[test]
@@ -428,7 +526,7 @@ mod tests {
 
     #[test]
     fn prefix_sequence() {
-        let packet_type = Packet::Disconnect.id();
+        let packet_type = Packet::Disconnect(None).id();
         let sequence = 99999;
 
         let mut buffer = vec![];
@@ -444,11 +542,42 @@ mod tests {
         assert_eq!(sequence, d_sequence);
     }
 
+    #[test]
+    fn sequence_zero_packet() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let key = b"an example very very secret key."; // 32-bytes
+        let packet = Packet::ConnectionDenied(None);
+        let protocol_id = 12;
+        let len = packet.encode(&mut buffer, protocol_id, Some((0, key))).unwrap();
+        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None).unwrap();
+        assert_eq!(d_sequence, 0);
+        assert_eq!(packet, d_packet);
+    }
+
+    #[test]
+    fn ping_pong_packets() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let ping = Packet::ping(12, 42);
+        let ping_len = ping.encode(&mut buffer, 12, None).unwrap();
+        let (_, d_packet) = Packet::decode(&mut buffer[..ping_len], 12, None, None).unwrap();
+        assert_eq!(ping, d_packet);
+
+        let pong = Packet::Pong {
+            version_info: *NETCODE_VERSION_INFO,
+            protocol_id: 12,
+            sequence: 42,
+        };
+        let pong_len = pong.encode(&mut buffer, 12, None).unwrap();
+        let (_, d_packet) = Packet::decode(&mut buffer[..pong_len], 12, None, None).unwrap();
+        assert_eq!(pong, d_packet);
+        assert_eq!(ping_len, pong_len);
+    }
+
     #[test]
     fn encrypt_decrypt_disconnect_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
         let key = b"an example very very secret key."; // 32-bytes
-        let packet = Packet::Disconnect;
+        let packet = Packet::Disconnect(None);
         let protocol_id = 12;
         let sequence = 1;
         let len = packet.encode(&mut buffer, protocol_id, Some((sequence, key))).unwrap();
@@ -461,7 +590,7 @@ mod tests {
     fn encrypt_decrypt_denied_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
         let key = b"an example very very secret key."; // 32-bytes
-        let packet = Packet::ConnectionDenied;
+        let packet = Packet::ConnectionDenied(Some(ApplicationReason::new(3, "wrong version")));
         let protocol_id = 12;
         let sequence = 2;
         let len = packet.encode(&mut buffer, protocol_id, Some((sequence, key))).unwrap();
@@ -470,6 +599,22 @@ mod tests {
         assert_eq!(packet, d_packet);
     }
 
+    #[test]
+    fn long_reason_message() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let key = b"an example very very secret key."; // 32-bytes
+        let message = "é".repeat(150);
+        assert_eq!(message.len(), 300);
+        let reason = ApplicationReason::new(4, message);
+        assert_eq!(reason.message().len(), 254);
+
+        let packet = Packet::Disconnect(Some(reason));
+        let protocol_id = 12;
+        let len = packet.encode(&mut buffer, protocol_id, Some((0, key))).unwrap();
+        let (_, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None).unwrap();
+        assert_eq!(packet, d_packet);
+    }
+
     #[test]
     fn encrypt_decrypt_payload_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, net::IpAddr, time::Duration};
+
+use crate::IpRange;
+
+/// Limits for the connection packets (requests and responses) received from addresses that are
+/// not connected. Each IP address and each subnet have a bucket that refills at the configured
+/// rate, packets are dropped when the bucket is empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// Connection packets per second allowed from a single IP address.
+    pub address_packets_per_second: f64,
+    /// Maximum burst of connection packets from a single IP address.
+    pub address_burst: u32,
+    /// Connection packets per second allowed from all the addresses in a subnet.
+    pub subnet_packets_per_second: f64,
+    /// Maximum burst of connection packets from all the addresses in a subnet.
+    pub subnet_burst: u32,
+    /// Prefix length used to group IPv4 addresses in subnets.
+    pub ipv4_subnet_prefix_len: u8,
+    /// Prefix length used to group IPv6 addresses in subnets.
+    pub ipv6_subnet_prefix_len: u8,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            address_packets_per_second: 10.0,
+            address_burst: 20,
+            subnet_packets_per_second: 50.0,
+            subnet_burst: 100,
+            ipv4_subnet_prefix_len: 24,
+            ipv6_subnet_prefix_len: 64,
+        }
+    }
+}
+
+/// Number of connection packets dropped by the rate limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// Packets dropped by the IP address limit.
+    pub dropped_by_address: u64,
+    /// Packets dropped by the subnet limit.
+    pub dropped_by_subnet: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    last_update: Duration,
+}
+
+impl TokenBucket {
+    fn new(burst: u32, current_time: Duration) -> Self {
+        Self {
+            tokens: burst as f64,
+            last_update: current_time,
+        }
+    }
+
+    fn refill(&mut self, current_time: Duration, rate: f64, burst: u32) {
+        let elapsed = current_time.saturating_sub(self.last_update).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst as f64);
+        self.last_update = current_time;
+    }
+
+    fn try_consume(&mut self, current_time: Duration, rate: f64, burst: u32) -> bool {
+        self.refill(current_time, rate, burst);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+
+        false
+    }
+
+    fn is_full(&self, burst: u32) -> bool {
+        self.tokens >= burst as f64
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    addresses: HashMap<IpAddr, TokenBucket>,
+    subnets: HashMap<IpRange, TokenBucket>,
+    stats: RateLimitStats,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            addresses: HashMap::new(),
+            subnets: HashMap::new(),
+            stats: RateLimitStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        self.stats
+    }
+
+    /// Returns true if a connection packet from the address is allowed.
+    pub fn allow(&mut self, ip: IpAddr, current_time: Duration) -> bool {
+        let subnet = self.subnet(ip);
+        let config = &self.config;
+        let address = self
+            .addresses
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(config.address_burst, current_time));
+        if !address.try_consume(current_time, config.address_packets_per_second, config.address_burst) {
+            self.stats.dropped_by_address += 1;
+            return false;
+        }
+
+        let subnet = self
+            .subnets
+            .entry(subnet)
+            .or_insert_with(|| TokenBucket::new(config.subnet_burst, current_time));
+        if !subnet.try_consume(current_time, config.subnet_packets_per_second, config.subnet_burst) {
+            self.stats.dropped_by_subnet += 1;
+            return false;
+        }
+
+        true
+    }
+
+    /// Removes the buckets that are full again, they behave the same as new ones.
+    pub fn update(&mut self, current_time: Duration) {
+        let config = self.config;
+        self.addresses.retain(|_, bucket| {
+            bucket.refill(current_time, config.address_packets_per_second, config.address_burst);
+            !bucket.is_full(config.address_burst)
+        });
+        self.subnets.retain(|_, bucket| {
+            bucket.refill(current_time, config.subnet_packets_per_second, config.subnet_burst);
+            !bucket.is_full(config.subnet_burst)
+        });
+    }
+
+    fn subnet(&self, ip: IpAddr) -> IpRange {
+        let prefix_len = match ip {
+            IpAddr::V4(_) => self.config.ipv4_subnet_prefix_len,
+            IpAddr::V6(_) => self.config.ipv6_subnet_prefix_len,
+        };
+
+        IpRange::new(ip, prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_address_and_subnet() {
+        let config = RateLimitConfig {
+            address_packets_per_second: 1.0,
+            address_burst: 2,
+            subnet_packets_per_second: 2.0,
+            subnet_burst: 3,
+            ..Default::default()
+        };
+        let mut rate_limiter = RateLimiter::new(config);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let same_subnet: IpAddr = "10.0.0.2".parse().unwrap();
+        let other_subnet: IpAddr = "10.0.1.1".parse().unwrap();
+
+        assert!(rate_limiter.allow(ip, Duration::ZERO));
+        assert!(rate_limiter.allow(ip, Duration::ZERO));
+        assert!(!rate_limiter.allow(ip, Duration::ZERO));
+
+        assert!(rate_limiter.allow(same_subnet, Duration::ZERO));
+        assert!(!rate_limiter.allow(same_subnet, Duration::ZERO));
+        assert!(rate_limiter.allow(other_subnet, Duration::ZERO));
+
+        assert_eq!(
+            rate_limiter.stats(),
+            RateLimitStats {
+                dropped_by_address: 1,
+                dropped_by_subnet: 1
+            }
+        );
+
+        // Buckets refill over time
+        assert!(rate_limiter.allow(ip, Duration::from_secs(1)));
+
+        rate_limiter.update(Duration::from_secs(10));
+        assert!(rate_limiter.addresses.is_empty());
+        assert!(rate_limiter.subnets.is_empty());
+    }
+}
@@ -1,13 +1,19 @@
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
 
 use crate::{
+    ban_list::{BanList, IpRange},
     crypto::generate_random_bytes,
     packet::{ChallengeToken, Packet},
+    rate_limit::{RateLimitConfig, RateLimitStats, RateLimiter},
     replay_protection::ReplayProtection,
-    token::PrivateConnectToken,
-    ClientID, NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
-    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
-    NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
+    token::{PrivateConnectToken, TokenGenerationError},
+    ApplicationReason, ClientID, DisconnectReason, NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES,
+    NETCODE_KEY_BYTES, NETCODE_MAC_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS,
+    NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,11 +40,48 @@ struct Connection {
     replay_protection: ReplayProtection,
 }
 
+/// Time without failed requests before a connect token failure is counted again.
+const FAILED_CONNECT_TOKEN_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug)]
+struct PendingApproval {
+    addr: SocketAddr,
+    expire_timestamp: u64,
+    connect_token: PrivateConnectToken,
+}
+
+/// Counters of the connection handshakes processed by the server.
+/// The failed connection requests are counted once per connect token, not for each resent request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeStats {
+    /// Connection request packets received, clients resend them until they receive a challenge.
+    pub requests: u64,
+    /// Challenges sent to clients with valid connection requests.
+    pub challenges_sent: u64,
+    /// Clients that completed the handshake and connected.
+    pub connected: u64,
+    /// Requests or responses with an invalid version, protocol id, connect token or challenge token.
+    pub invalid: u64,
+    /// Requests with an expired connect token.
+    pub expired: u64,
+    /// Requests from banned ips or client ids, or with revoked connect tokens.
+    pub banned: u64,
+    /// Requests from clients already connected, or with a connect token used by another address.
+    pub already_connected: u64,
+    /// Requests dropped because there were too many pending clients or approvals.
+    pub too_many_pending: u64,
+    /// Requests or responses denied because the server was full.
+    pub server_full: u64,
+    /// Requests denied by the application.
+    pub denied: u64,
+    /// Pending clients that did not complete the handshake in time.
+    pub timed_out: u64,
+}
+
 #[derive(Debug, Copy, Clone)]
 struct ConnectTokenEntry {
-    time: Duration,
     address: SocketAddr,
-    mac: [u8; NETCODE_MAC_BYTES],
+    expire_timestamp: u64,
 }
 
 /// A server that can generate packets from connect clients, that are encrypted, or process
@@ -47,10 +90,23 @@ struct ConnectTokenEntry {
 #[derive(Debug)]
 pub struct NetcodeServer {
     clients: Box<[Option<Connection>]>,
+    clients_by_id: HashMap<ClientID, usize>,
+    clients_by_addr: HashMap<SocketAddr, usize>,
+    free_slots: Vec<usize>,
     pending_clients: HashMap<SocketAddr, Connection>,
-    connect_token_entries: Box<[Option<ConnectTokenEntry>; NETCODE_MAX_CLIENTS * 2]>,
+    pending_approvals: HashMap<ClientID, PendingApproval>,
+    require_connection_approval: bool,
+    ban_list: BanList,
+    rate_limiter: Option<RateLimiter>,
+    handshake_stats: HandshakeStats,
+    // Connect tokens of failed connection requests, by their MAC, with the time of the last failure.
+    failed_connect_tokens: HashMap<[u8; NETCODE_MAC_BYTES], Duration>,
+    // Connect tokens used, by their MAC, kept until they expire.
+    connect_token_entries: HashMap<[u8; NETCODE_MAC_BYTES], ConnectTokenEntry>,
     protocol_id: u64,
     connect_key: [u8; NETCODE_KEY_BYTES],
+    // Keys still accepted for connect tokens generated before a key rotation.
+    previous_connect_keys: Vec<[u8; NETCODE_KEY_BYTES]>,
     max_clients: usize,
     challenge_sequence: u64,
     challenge_key: [u8; NETCODE_KEY_BYTES],
@@ -69,6 +125,13 @@ pub enum ServerResult<'a, 's> {
     PacketToSend { addr: SocketAddr, payload: &'s mut [u8] },
     /// A payload received from the client.
     Payload { client_id: ClientID, payload: &'a [u8] },
+    /// A client with a valid connect token is requesting to connect, only returned when the
+    /// connection approval is required. The request must be approved or denied.
+    ConnectionRequested {
+        client_id: ClientID,
+        addr: SocketAddr,
+        user_data: Box<[u8; NETCODE_USER_DATA_BYTES]>,
+    },
     /// A new client has connected
     ClientConnected {
         client_id: ClientID,
@@ -80,6 +143,7 @@ pub enum ServerResult<'a, 's> {
     ClientDisconnected {
         client_id: ClientID,
         addr: SocketAddr,
+        reason: DisconnectReason,
         payload: Option<&'s mut [u8]>,
     },
 }
@@ -102,20 +166,27 @@ impl NetcodeServer {
         public_address: SocketAddr,
         private_key: [u8; NETCODE_KEY_BYTES],
     ) -> Self {
-        if max_clients > NETCODE_MAX_CLIENTS {
-            // TODO: do we really need to set a max?
-            //       only using for token entries
-            panic!("The max clients allowed is {}", NETCODE_MAX_CLIENTS);
-        }
         let challenge_key = generate_random_bytes();
         let clients = vec![None; max_clients].into_boxed_slice();
+        // Reversed so the lowest slots are used first.
+        let free_slots = (0..max_clients).rev().collect();
 
         Self {
             clients,
-            connect_token_entries: Box::new([None; NETCODE_MAX_CLIENTS * 2]),
+            clients_by_id: HashMap::new(),
+            clients_by_addr: HashMap::new(),
+            free_slots,
+            connect_token_entries: HashMap::new(),
             pending_clients: HashMap::new(),
+            pending_approvals: HashMap::new(),
+            require_connection_approval: false,
+            ban_list: BanList::default(),
+            rate_limiter: None,
+            handshake_stats: HandshakeStats::default(),
+            failed_connect_tokens: HashMap::new(),
             protocol_id,
             connect_key: private_key,
+            previous_connect_keys: Vec::new(),
             max_clients,
             challenge_sequence: 0,
             global_sequence: 0,
@@ -135,47 +206,107 @@ impl NetcodeServer {
         self.public_address
     }
 
+    pub fn protocol_id(&self) -> u64 {
+        self.protocol_id
+    }
+
+    /// Returns the key used to encrypt the challenge tokens, used to replay packet captures.
+    #[doc(hidden)]
+    pub fn challenge_key(&self) -> &[u8; NETCODE_KEY_BYTES] {
+        &self.challenge_key
+    }
+
+    #[doc(hidden)]
+    pub fn set_challenge_key(&mut self, challenge_key: [u8; NETCODE_KEY_BYTES]) {
+        self.challenge_key = challenge_key;
+    }
+
     pub fn current_time(&self) -> Duration {
         self.current_time
     }
 
-    fn find_or_add_connect_token_entry(&mut self, new_entry: ConnectTokenEntry) -> bool {
-        let mut min = Duration::MAX;
-        let mut oldest_entry = 0;
-        let mut empty_entry = false;
-        let mut matching_entry = None;
-        for (i, entry) in self.connect_token_entries.iter().enumerate() {
-            match entry {
-                Some(e) => {
-                    if e.mac == new_entry.mac {
-                        matching_entry = Some(e);
-                    }
-                    if !empty_entry && e.time < min {
-                        oldest_entry = i;
-                        min = e.time;
-                    }
-                }
-                None => {
-                    if !empty_entry {
-                        empty_entry = true;
-                        oldest_entry = i;
-                    }
-                }
+    /// Returns the current private key, new connect tokens should be generated with it.
+    pub fn private_key(&self) -> &[u8; NETCODE_KEY_BYTES] {
+        &self.connect_key
+    }
+
+    /// Returns the previous private keys, connect tokens generated with them are still accepted.
+    pub fn previous_private_keys(&self) -> &[[u8; NETCODE_KEY_BYTES]] {
+        &self.previous_connect_keys
+    }
+
+    /// Replaces the current private key, the current one becomes the only previous key so connect
+    /// tokens already issued with it are still accepted. Older previous keys are dropped, rotate
+    /// keys less often than the connect tokens expire.
+    pub fn rotate_private_key(&mut self, private_key: [u8; NETCODE_KEY_BYTES]) {
+        let previous_key = std::mem::replace(&mut self.connect_key, private_key);
+        self.previous_connect_keys = vec![previous_key];
+    }
+
+    /// Sets the previous private keys that are still accepted, use an empty list to accept
+    /// only connect tokens generated with the current key.
+    pub fn set_previous_private_keys(&mut self, private_keys: Vec<[u8; NETCODE_KEY_BYTES]>) {
+        self.previous_connect_keys = private_keys;
+    }
+
+    /// Decodes the private connect token with the current key, or with the previous keys.
+    fn decode_connect_token(
+        &self,
+        data: &[u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
+        expire_timestamp: u64,
+        xnonce: &[u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES],
+    ) -> Result<PrivateConnectToken, TokenGenerationError> {
+        let result = PrivateConnectToken::decode(data, self.protocol_id, expire_timestamp, xnonce, &self.connect_key);
+        if result.is_ok() {
+            return result;
+        }
+
+        for private_key in self.previous_connect_keys.iter() {
+            if let Ok(connect_token) = PrivateConnectToken::decode(data, self.protocol_id, expire_timestamp, xnonce, private_key) {
+                return Ok(connect_token);
             }
         }
 
-        if let Some(entry) = matching_entry {
-            return entry.address == new_entry.address;
+        result
+    }
+
+    /// Registers the connect token MAC, returns false if the token was already used by another address.
+    fn find_or_add_connect_token_entry(&mut self, mac: [u8; NETCODE_MAC_BYTES], new_entry: ConnectTokenEntry) -> bool {
+        match self.connect_token_entries.get(&mac) {
+            Some(entry) => entry.address == new_entry.address,
+            None => {
+                self.connect_token_entries.insert(mac, new_entry);
+                true
+            }
         }
+    }
 
-        self.connect_token_entries[oldest_entry] = Some(new_entry);
+    fn client_slot_by_id(&self, client_id: ClientID) -> Option<usize> {
+        self.clients_by_id.get(&client_id).copied()
+    }
+
+    fn find_client_by_id(&self, client_id: ClientID) -> Option<&Connection> {
+        let slot = self.client_slot_by_id(client_id)?;
+        self.clients[slot].as_ref()
+    }
+
+    fn add_client(&mut self, slot: usize, client: Connection) {
+        self.clients_by_id.insert(client.client_id, slot);
+        self.clients_by_addr.insert(client.addr, slot);
+        self.clients[slot] = Some(client);
+    }
 
-        true
+    fn remove_client(&mut self, slot: usize) -> Option<Connection> {
+        let client = self.clients[slot].take()?;
+        self.clients_by_id.remove(&client.client_id);
+        self.clients_by_addr.remove(&client.addr);
+        self.free_slots.push(slot);
+        Some(client)
     }
 
     /// Returns the user data from the connected client.
     pub fn user_data(&self, client_id: ClientID) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
-        if let Some(client) = find_client_by_id(&self.clients, client_id) {
+        if let Some(client) = self.find_client_by_id(client_id) {
             return Some(client.user_data);
         }
 
@@ -184,7 +315,7 @@ impl NetcodeServer {
 
     /// Returns the client address if connected.
     pub fn client_addr(&self, client_id: ClientID) -> Option<SocketAddr> {
-        if let Some(client) = find_client_by_id(&self.clients, client_id) {
+        if let Some(client) = self.find_client_by_id(client_id) {
             return Some(client.addr);
         }
 
@@ -200,53 +331,101 @@ impl NetcodeServer {
         xnonce: [u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES],
         data: [u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
     ) -> Result<ServerResult<'a, 's>, NetcodeError> {
+        self.handshake_stats.requests += 1;
+        let mut mac = [0u8; NETCODE_MAC_BYTES];
+        mac.copy_from_slice(&data[NETCODE_CONNECT_TOKEN_PRIVATE_BYTES - NETCODE_MAC_BYTES..]);
         if version_info != *NETCODE_VERSION_INFO {
+            self.count_failure(mac, |stats| &mut stats.invalid);
             return Err(NetcodeError::InvalidVersion);
         }
 
         if protocol_id != self.protocol_id {
+            self.count_failure(mac, |stats| &mut stats.invalid);
             return Err(NetcodeError::InvalidProtocolID);
         }
 
         if self.current_time.as_secs() >= expire_timestamp {
+            self.count_failure(mac, |stats| &mut stats.expired);
             return Err(NetcodeError::Expired);
         }
 
-        let connect_token = PrivateConnectToken::decode(&data, self.protocol_id, expire_timestamp, &xnonce, &self.connect_key)?;
+        if self.ban_list.is_ip_banned(addr.ip()) {
+            trace_event!(debug, "dropped connection request from banned ip", addr = addr);
+            self.count_failure(mac, |stats| &mut stats.banned);
+            return Ok(ServerResult::None);
+        }
+
+        let connect_token = self
+            .decode_connect_token(&data, expire_timestamp, &xnonce)
+            .inspect_err(|_| self.count_failure(mac, |stats| &mut stats.invalid))?;
 
         let in_host_list = connect_token.server_addresses.iter().any(|host| *host == Some(self.public_address));
         if !in_host_list {
+            self.count_failure(mac, |stats| &mut stats.invalid);
             return Err(NetcodeError::NotInHostList);
         }
 
-        let addr_already_connected = find_client_mut_by_addr(&mut self.clients, addr).is_some();
-        let id_already_connected = find_client_mut_by_id(&mut self.clients, connect_token.client_id).is_some();
+        if self.ban_list.is_client_id_banned(connect_token.client_id) || self.ban_list.is_token_revoked(&mac) {
+            trace_event!(
+                debug,
+                "dropped connection request with banned client id or revoked token",
+                client_id = connect_token.client_id,
+                addr = addr
+            );
+            self.count_failure(mac, |stats| &mut stats.banned);
+            return Ok(ServerResult::None);
+        }
+
+        let addr_already_connected = self.clients_by_addr.contains_key(&addr);
+        let id_already_connected = self.clients_by_id.contains_key(&connect_token.client_id);
         if id_already_connected || addr_already_connected {
-            // TODO(log): debug
+            trace_event!(
+                debug,
+                "dropped connection request from client already connected",
+                client_id = connect_token.client_id,
+                addr = addr
+            );
+            self.count_failure(mac, |stats| &mut stats.already_connected);
             return Ok(ServerResult::None);
         }
 
         if !self.pending_clients.contains_key(&addr) && self.pending_clients.len() >= NETCODE_MAX_PENDING_CLIENTS {
-            // TODO(log): debug
+            trace_event!(
+                debug,
+                "dropped connection request, too many pending clients",
+                client_id = connect_token.client_id,
+                addr = addr
+            );
+            self.count_failure(mac, |stats| &mut stats.too_many_pending);
             return Ok(ServerResult::None);
         }
 
-        let mut mac = [0u8; NETCODE_MAC_BYTES];
-        mac.copy_from_slice(&data[NETCODE_CONNECT_TOKEN_PRIVATE_BYTES - NETCODE_MAC_BYTES..]);
         let connect_token_entry = ConnectTokenEntry {
             address: addr,
-            time: self.current_time,
-            mac,
+            expire_timestamp,
         };
 
-        if !self.find_or_add_connect_token_entry(connect_token_entry) {
-            // TODO(log): debug
+        if !self.find_or_add_connect_token_entry(mac, connect_token_entry) {
+            trace_event!(
+                debug,
+                "dropped connection request, token already used by another address",
+                client_id = connect_token.client_id,
+                addr = addr
+            );
+            self.count_failure(mac, |stats| &mut stats.already_connected);
             return Ok(ServerResult::None);
         }
 
-        if self.clients.iter().flatten().count() >= self.max_clients {
+        if self.free_slots.is_empty() {
+            trace_event!(
+                debug,
+                "denied connection request, server is full",
+                client_id = connect_token.client_id,
+                addr = addr
+            );
+            self.count_failure(mac, |stats| &mut stats.server_full);
             self.pending_clients.remove(&addr);
-            let packet = Packet::ConnectionDenied;
+            let packet = Packet::ConnectionDenied(None);
             let len = packet.encode(
                 &mut self.out,
                 self.protocol_id,
@@ -259,7 +438,67 @@ impl NetcodeServer {
             });
         }
 
+        if self.require_connection_approval && !self.pending_clients.contains_key(&addr) {
+            if let Some(pending) = self.pending_approvals.get(&connect_token.client_id) {
+                // The connect token is bound to the address, so the same address is a resend of the same request
+                if pending.addr != addr {
+                    trace_event!(
+                        debug,
+                        "dropped connection request, client id already waiting for approval",
+                        client_id = connect_token.client_id,
+                        addr = addr
+                    );
+                    self.count_failure(mac, |stats| &mut stats.already_connected);
+                }
+                return Ok(ServerResult::None);
+            }
+            if self.pending_approvals.len() >= NETCODE_MAX_PENDING_CLIENTS {
+                trace_event!(
+                    debug,
+                    "dropped connection request, too many pending approvals",
+                    client_id = connect_token.client_id,
+                    addr = addr
+                );
+                self.count_failure(mac, |stats| &mut stats.too_many_pending);
+                return Ok(ServerResult::None);
+            }
+
+            trace_event!(
+                debug,
+                "connection request waiting for approval",
+                client_id = connect_token.client_id,
+                addr = addr
+            );
+
+            let client_id = connect_token.client_id;
+            let user_data = Box::new(connect_token.user_data);
+            self.pending_approvals.insert(
+                client_id,
+                PendingApproval {
+                    addr,
+                    expire_timestamp,
+                    connect_token,
+                },
+            );
+            return Ok(ServerResult::ConnectionRequested {
+                client_id,
+                addr,
+                user_data,
+            });
+        }
+
+        self.generate_challenge(addr, expire_timestamp, &connect_token)
+    }
+
+    fn generate_challenge<'a, 's>(
+        &'s mut self,
+        addr: SocketAddr,
+        expire_timestamp: u64,
+        connect_token: &PrivateConnectToken,
+    ) -> Result<ServerResult<'a, 's>, NetcodeError> {
         self.challenge_sequence += 1;
+        self.handshake_stats.challenges_sent += 1;
+        trace_event!(debug, "sending challenge", client_id = connect_token.client_id, addr = addr);
         let packet = Packet::generate_challenge(
             connect_token.client_id,
             &connect_token.user_data,
@@ -298,6 +537,168 @@ impl NetcodeServer {
         })
     }
 
+    /// Enables or disables the connection approval. When enabled, valid connection requests are
+    /// returned as [ServerResult::ConnectionRequested] and the client only receives a challenge
+    /// after the request is accepted with [NetcodeServer::approve_connection].
+    pub fn set_require_connection_approval(&mut self, require_connection_approval: bool) {
+        self.require_connection_approval = require_connection_approval;
+    }
+
+    /// Returns the client ids of the connection requests waiting for approval. Requests that are
+    /// not approved or denied before their connect token expires are dropped.
+    pub fn pending_connection_requests(&self) -> Vec<ClientID> {
+        self.pending_approvals.keys().copied().collect()
+    }
+
+    /// Returns the client ids of the pending clients, that are in the connection handshake.
+    pub fn pending_clients_id(&self) -> Vec<ClientID> {
+        self.pending_clients.values().map(|pending| pending.client_id).collect()
+    }
+
+    /// Sets the rate limits for connection packets from addresses that are not connected,
+    /// `None` disables the rate limits.
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimitConfig>) {
+        self.rate_limiter = rate_limit.map(RateLimiter::new);
+    }
+
+    /// Returns the number of connection packets dropped by the rate limits.
+    pub fn rate_limit_stats(&self) -> RateLimitStats {
+        match &self.rate_limiter {
+            Some(rate_limiter) => rate_limiter.stats(),
+            None => RateLimitStats::default(),
+        }
+    }
+
+    /// Returns the counters of the connection handshakes processed by the server.
+    pub fn handshake_stats(&self) -> HandshakeStats {
+        self.handshake_stats
+    }
+
+    /// Counts a failed connection request once per connect token, clients resend the requests
+    /// until they get an answer.
+    fn count_failure(&mut self, mac: [u8; NETCODE_MAC_BYTES], counter: fn(&mut HandshakeStats) -> &mut u64) {
+        if let Some(last_failure_time) = self.failed_connect_tokens.get_mut(&mac) {
+            *last_failure_time = self.current_time;
+            return;
+        }
+
+        *counter(&mut self.handshake_stats) += 1;
+        // Past the limit, the failures are counted for each request
+        if self.failed_connect_tokens.len() < NETCODE_MAX_PENDING_CLIENTS {
+            self.failed_connect_tokens.insert(mac, self.current_time);
+        }
+    }
+
+    /// Bans a client id, connection requests from it are dropped. The ban expires after the
+    /// duration, or never if no duration is given. Connected clients are not disconnected,
+    /// use [NetcodeServer::disconnect] for that.
+    pub fn ban_client_id(&mut self, client_id: ClientID, duration: Option<Duration>) {
+        let expire_time = duration.map(|duration| self.current_time + duration);
+        self.ban_list.ban_client_id(client_id, expire_time);
+        self.pending_clients.retain(|_, pending| pending.client_id != client_id);
+        self.pending_approvals.remove(&client_id);
+    }
+
+    pub fn unban_client_id(&mut self, client_id: ClientID) {
+        self.ban_list.unban_client_id(client_id);
+    }
+
+    /// Bans an IP address or range, connection requests from it are dropped. The ban expires after the
+    /// duration, or never if no duration is given. Connected clients are not disconnected,
+    /// use [NetcodeServer::disconnect] for that.
+    pub fn ban_ip<R: Into<IpRange>>(&mut self, ip_range: R, duration: Option<Duration>) {
+        let ip_range = ip_range.into();
+        let expire_time = duration.map(|duration| self.current_time + duration);
+        self.ban_list.ban_ip_range(ip_range, expire_time);
+        self.pending_clients.retain(|addr, _| !ip_range.contains(addr.ip()));
+        self.pending_approvals.retain(|_, approval| !ip_range.contains(approval.addr.ip()));
+    }
+
+    pub fn unban_ip<R: Into<IpRange>>(&mut self, ip_range: R) {
+        self.ban_list.unban_ip_range(ip_range.into());
+    }
+
+    /// Revokes a connect token by its MAC and expire timestamp, see [ConnectToken::mac][crate::ConnectToken::mac]
+    /// and [ConnectToken::expire_timestamp][crate::ConnectToken::expire_timestamp].
+    /// Connection requests with the token are dropped, the token is forgotten once it expires.
+    pub fn revoke_connect_token(&mut self, mac: [u8; NETCODE_MAC_BYTES], expire_timestamp: u64) {
+        self.ban_list.revoke_token(mac, expire_timestamp);
+    }
+
+    pub fn is_client_id_banned(&self, client_id: ClientID) -> bool {
+        self.ban_list.is_client_id_banned(client_id)
+    }
+
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        self.ban_list.is_ip_banned(ip)
+    }
+
+    /// Accepts a connection request, returns the challenge packet to be sent to the client.
+    pub fn approve_connection(&mut self, client_id: ClientID) -> ServerResult<'_, '_> {
+        let approval = match self.pending_approvals.remove(&client_id) {
+            Some(approval) => approval,
+            None => return ServerResult::None,
+        };
+
+        match self.generate_challenge(approval.addr, approval.expire_timestamp, &approval.connect_token) {
+            Ok(result) => result,
+            Err(e) => {
+                trace_event!(
+                    error,
+                    "failed to generate challenge for approved connection",
+                    client_id = client_id,
+                    error = e
+                );
+                ServerResult::None
+            }
+        }
+    }
+
+    /// Denies a connection request, returns the connection denied packet to be sent to the client.
+    pub fn deny_connection(&mut self, client_id: ClientID) -> ServerResult<'_, '_> {
+        self.deny_connection_internal(client_id, None)
+    }
+
+    /// Denies a connection request with a reason that is sent to the client, returns the
+    /// connection denied packet to be sent to the client.
+    pub fn deny_connection_with_reason(&mut self, client_id: ClientID, reason: ApplicationReason) -> ServerResult<'_, '_> {
+        self.deny_connection_internal(client_id, Some(reason))
+    }
+
+    fn deny_connection_internal(&mut self, client_id: ClientID, reason: Option<ApplicationReason>) -> ServerResult<'_, '_> {
+        let approval = match self.pending_approvals.remove(&client_id) {
+            Some(approval) => approval,
+            None => return ServerResult::None,
+        };
+
+        trace_event!(
+            debug,
+            "denied connection request",
+            client_id = client_id,
+            addr = approval.addr,
+            reason = reason
+        );
+        self.handshake_stats.denied += 1;
+        let packet = Packet::ConnectionDenied(reason);
+        let len = match packet.encode(
+            &mut self.out,
+            self.protocol_id,
+            Some((self.global_sequence, &approval.connect_token.server_to_client_key)),
+        ) {
+            Ok(len) => len,
+            Err(e) => {
+                trace_event!(error, "failed to encode connection denied packet", client_id = client_id, error = e);
+                return ServerResult::None;
+            }
+        };
+        self.global_sequence += 1;
+
+        ServerResult::PacketToSend {
+            addr: approval.addr,
+            payload: &mut self.out[..len],
+        }
+    }
+
     /// Returns an encoded packet payload to be sent to the client
     pub fn generate_payload_packet<'s>(
         &'s mut self,
@@ -308,10 +709,11 @@ impl NetcodeServer {
             return Err(NetcodeError::PayloadAboveLimit);
         }
 
-        if let Some(client) = find_client_mut_by_id(&mut self.clients, client_id) {
+        if let Some(client) = self.client_slot_by_id(client_id).and_then(|slot| self.clients[slot].as_mut()) {
             let packet = Packet::Payload(payload);
             let len = packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key)))?;
             client.sequence += 1;
+            trace_event!(trace, "sending payload packet", client_id = client_id, len = len);
 
             return Ok((client.addr, &mut self.out[..len]));
         }
@@ -322,9 +724,11 @@ impl NetcodeServer {
     /// Process an packet from the especifed address. Returns a server result, check out
     /// [ServerResult].
     pub fn process_packet<'a, 's>(&'s mut self, addr: SocketAddr, buffer: &'a mut [u8]) -> ServerResult<'a, 's> {
+        trace_span!(trace, "netcode_server_packet", addr = addr);
+        let len = buffer.len();
         match self.process_packet_internal(addr, buffer) {
-            Err(_) => {
-                // TODO(log): error
+            Err(e) => {
+                trace_event!(debug, "discarded packet", len = len, error = e);
                 ServerResult::None
             }
             Ok(r) => r,
@@ -337,7 +741,8 @@ impl NetcodeServer {
         }
 
         // Handle connected client
-        if let Some((slot, client)) = find_client_mut_by_addr(&mut self.clients, addr) {
+        if let Some(&slot) = self.clients_by_addr.get(&addr) {
+            let client = self.clients[slot].as_mut().unwrap();
             let (_, packet) = Packet::decode(
                 buffer,
                 self.protocol_id,
@@ -345,22 +750,35 @@ impl NetcodeServer {
                 Some(&mut client.replay_protection),
             )?;
             client.last_packet_received_time = self.current_time;
+            trace_event!(
+                trace,
+                "received packet",
+                client_id = client.client_id,
+                packet_type = packet.packet_type()
+            );
             match client.state {
                 ConnectionState::Connected => match packet {
-                    Packet::Disconnect => {
+                    Packet::Disconnect(_) => {
                         client.state = ConnectionState::Disconnected;
                         let client_id = client.client_id;
-                        self.clients[slot] = None;
-                        // TODO(log): debug
+                        self.remove_client(slot);
+                        trace_event!(
+                            info,
+                            "client disconnected",
+                            client_id = client_id,
+                            addr = addr,
+                            reason = "disconnect packet"
+                        );
                         return Ok(ServerResult::ClientDisconnected {
                             client_id,
                             addr,
+                            reason: DisconnectReason::DisconnectedByClient,
                             payload: None,
                         });
                     }
                     Packet::Payload(payload) => {
                         if !client.confirmed {
-                            // TODO(log): debug
+                            trace_event!(debug, "client connection confirmed", client_id = client.client_id);
                             client.confirmed = true;
                         }
                         return Ok(ServerResult::Payload {
@@ -370,7 +788,7 @@ impl NetcodeServer {
                     }
                     Packet::KeepAlive { .. } => {
                         if !client.confirmed {
-                            // TODO(log): debug
+                            trace_event!(debug, "client connection confirmed", client_id = client.client_id);
                             client.confirmed = true;
                         }
                         return Ok(ServerResult::None);
@@ -381,6 +799,14 @@ impl NetcodeServer {
             }
         }
 
+        // Only connection requests and responses are expected from addresses not connected.
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            if !rate_limiter.allow(addr.ip(), self.current_time) {
+                trace_event!(debug, "dropped connection packet, rate limited");
+                return Ok(ServerResult::None);
+            }
+        }
+
         // Handle pending client
         if let Some(pending) = self.pending_clients.get_mut(&addr) {
             let (_, packet) = Packet::decode(
@@ -404,15 +830,27 @@ impl NetcodeServer {
                     token_data,
                     token_sequence,
                 } => {
-                    let challenge_token = ChallengeToken::decode(token_data, token_sequence, &self.challenge_key)?;
+                    let challenge_token = ChallengeToken::decode(token_data, token_sequence, &self.challenge_key)
+                        .inspect_err(|_| self.handshake_stats.invalid += 1)?;
                     let mut pending = self.pending_clients.remove(&addr).unwrap();
-                    if find_client_slot_by_id(&self.clients, challenge_token.client_id).is_some() {
-                        // TODO(log): debug
+                    if self.clients_by_id.contains_key(&challenge_token.client_id) {
+                        trace_event!(
+                            debug,
+                            "dropped connection response from client already connected",
+                            client_id = challenge_token.client_id
+                        );
+                        self.handshake_stats.already_connected += 1;
                         return Ok(ServerResult::None);
                     }
-                    match self.clients.iter().position(|c| c.is_none()) {
+                    match self.free_slots.pop() {
                         None => {
-                            let packet = Packet::ConnectionDenied;
+                            trace_event!(
+                                debug,
+                                "denied connection response, server is full",
+                                client_id = challenge_token.client_id
+                            );
+                            self.handshake_stats.server_full += 1;
+                            let packet = Packet::ConnectionDenied(None);
                             let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, &pending.send_key)))?;
                             pending.state = ConnectionState::Disconnected;
                             self.global_sequence += 1;
@@ -436,7 +874,15 @@ impl NetcodeServer {
 
                             let client_id: ClientID = pending.client_id;
                             let user_data: [u8; NETCODE_USER_DATA_BYTES] = pending.user_data;
-                            self.clients[client_index] = Some(pending);
+                            self.add_client(client_index, pending);
+                            self.handshake_stats.connected += 1;
+                            trace_event!(
+                                info,
+                                "client connected",
+                                client_id = client_id,
+                                addr = addr,
+                                client_index = client_index
+                            );
 
                             return Ok(ServerResult::ClientConnected {
                                 client_id,
@@ -461,24 +907,40 @@ impl NetcodeServer {
                 xnonce,
                 version_info,
             } => self.handle_connection_request(addr, version_info, protocol_id, expire_timestamp, xnonce, data),
-            _ => unreachable!("Decoding packet without key can only return ConnectionRequest packets"),
+            Packet::Ping {
+                version_info,
+                protocol_id,
+                sequence,
+            } => {
+                if version_info != *NETCODE_VERSION_INFO {
+                    return Err(NetcodeError::InvalidVersion);
+                }
+                if protocol_id != self.protocol_id {
+                    return Err(NetcodeError::InvalidProtocolID);
+                }
+                trace_event!(trace, "answering ping", addr = addr);
+                let packet = Packet::Pong {
+                    version_info,
+                    protocol_id,
+                    sequence,
+                };
+                let len = packet.encode(&mut self.out, self.protocol_id, None)?;
+                Ok(ServerResult::PacketToSend {
+                    addr,
+                    payload: &mut self.out[..len],
+                })
+            }
+            _ => Ok(ServerResult::None),
         }
     }
 
     pub fn clients_slot(&self) -> Vec<usize> {
-        self.clients
-            .iter()
-            .enumerate()
-            .filter_map(|(index, slot)| if slot.is_some() { Some(index) } else { None })
-            .collect()
+        self.clients_by_id.values().copied().collect()
     }
 
     /// Returns the ids from the connected clients.
     pub fn clients_id(&self) -> Vec<ClientID> {
-        self.clients
-            .iter()
-            .filter_map(|slot| slot.as_ref().map(|client| client.client_id))
-            .collect()
+        self.clients_by_id.keys().copied().collect()
     }
 
     /// Returns the maximum number of clients that can be connected.
@@ -488,7 +950,7 @@ impl NetcodeServer {
 
     /// Returns the maximum number of clients that can be connected.
     pub fn connected_clients(&self) -> usize {
-        self.clients.iter().filter(|slot| slot.is_some()).count()
+        self.clients_by_id.len()
     }
 
     /// Advance the server current time, and remove any pending connections that have expired.
@@ -496,13 +958,33 @@ impl NetcodeServer {
         self.current_time += duration;
 
         for client in self.pending_clients.values_mut() {
-            if self.current_time.as_secs() > client.expire_timestamp {
-                // TODO(log): debug
+            let timed_out = client.timeout_seconds > 0
+                && (client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64) < self.current_time);
+            if self.current_time.as_secs() > client.expire_timestamp || timed_out {
+                trace_event!(debug, "pending client timed out", client_id = client.client_id, addr = client.addr);
+                self.handshake_stats.timed_out += 1;
                 client.state = ConnectionState::Disconnected;
             }
         }
 
         self.pending_clients.retain(|_, c| c.state != ConnectionState::Disconnected);
+
+        let current_timestamp = self.current_time.as_secs();
+        self.pending_approvals
+            .retain(|_, approval| current_timestamp <= approval.expire_timestamp);
+
+        // Expired tokens are rejected before checking their entry, so we don't need to keep them.
+        self.connect_token_entries
+            .retain(|_, entry| current_timestamp < entry.expire_timestamp);
+
+        let current_time = self.current_time;
+        self.failed_connect_tokens
+            .retain(|_, last_failure_time| *last_failure_time + FAILED_CONNECT_TOKEN_TIMEOUT > current_time);
+
+        self.ban_list.update(self.current_time);
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            rate_limiter.update(self.current_time);
+        }
     }
 
     /// Updates the client, returns a ServerResult.
@@ -520,7 +1002,7 @@ impl NetcodeServer {
     /// # fn send_to(p: &[u8], addr: std::net::SocketAddr) {}
     /// ```
     pub fn update_client<'s>(&'s mut self, client_id: ClientID) -> ServerResult<'_, 's> {
-        let slot = match find_client_slot_by_id(&self.clients, client_id) {
+        let slot = match self.client_slot_by_id(client_id) {
             None => return ServerResult::None,
             Some(slot) => slot,
         };
@@ -529,25 +1011,32 @@ impl NetcodeServer {
             let connection_timed_out = client.timeout_seconds > 0
                 && (client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64) < self.current_time);
             if connection_timed_out {
-                // TODO(log): debug
+                trace_event!(
+                    info,
+                    "client disconnected",
+                    client_id = client_id,
+                    addr = client.addr,
+                    reason = "timed out"
+                );
                 client.state = ConnectionState::Disconnected;
             }
 
             if client.state == ConnectionState::Disconnected {
-                let packet = Packet::Disconnect;
+                let packet = Packet::Disconnect(None);
                 let sequence = client.sequence;
                 let send_key = client.send_key;
                 let addr = client.addr;
-                self.clients[slot] = None;
+                self.remove_client(slot);
 
-                // TODO(log): error
                 let len = match packet.encode(&mut self.out, self.protocol_id, Some((sequence, &send_key))) {
-                    Err(_) => {
+                    Err(e) => {
+                        trace_event!(error, "failed to encode disconnect packet", client_id = client_id, error = e);
                         return ServerResult::ClientDisconnected {
                             client_id,
                             addr,
+                            reason: DisconnectReason::ConnectionTimedOut,
                             payload: None,
-                        }
+                        };
                     }
                     Ok(len) => len,
                 };
@@ -555,6 +1044,7 @@ impl NetcodeServer {
                 return ServerResult::ClientDisconnected {
                     client_id,
                     addr,
+                    reason: DisconnectReason::ConnectionTimedOut,
                     payload: Some(&mut self.out[..len]),
                 };
             }
@@ -565,9 +1055,11 @@ impl NetcodeServer {
                     max_clients: self.max_clients as u32,
                 };
 
-                // TODO(log): error
                 let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key))) {
-                    Err(_) => return ServerResult::None,
+                    Err(e) => {
+                        trace_event!(error, "failed to encode keep alive packet", client_id = client_id, error = e);
+                        return ServerResult::None;
+                    }
                     Ok(len) => len,
                 };
                 client.sequence += 1;
@@ -583,7 +1075,7 @@ impl NetcodeServer {
     }
 
     pub fn is_client_connected(&self, client_id: ClientID) -> bool {
-        find_client_slot_by_id(&self.clients, client_id).is_some()
+        self.clients_by_id.contains_key(&client_id)
     }
 
     /// Disconnect an client and returns its address and a disconnect packet to be sent to them.
@@ -591,24 +1083,47 @@ impl NetcodeServer {
     //       but the library user would need to be aware that he has to run
     //       the same code as Result::ClientDisconnected
     pub fn disconnect<'s>(&'s mut self, client_id: ClientID) -> ServerResult<'_, 's> {
-        if let Some(slot) = find_client_slot_by_id(&self.clients, client_id) {
-            let client = self.clients[slot].take().unwrap();
-            let packet = Packet::Disconnect;
+        self.disconnect_internal(client_id, None)
+    }
+
+    /// Disconnect an client with a reason that is sent to them, returns its address and a
+    /// disconnect packet to be sent to them.
+    pub fn disconnect_with_reason(&mut self, client_id: ClientID, reason: ApplicationReason) -> ServerResult<'_, '_> {
+        self.disconnect_internal(client_id, Some(reason))
+    }
+
+    fn disconnect_internal(&mut self, client_id: ClientID, reason: Option<ApplicationReason>) -> ServerResult<'_, '_> {
+        if let Some(slot) = self.client_slot_by_id(client_id) {
+            let client = self.remove_client(slot).unwrap();
+            trace_event!(
+                info,
+                "disconnecting client",
+                client_id = client_id,
+                addr = client.addr,
+                reason = reason
+            );
+            let disconnect_reason = match &reason {
+                Some(reason) => DisconnectReason::DisconnectedByServerWithReason(reason.clone()),
+                None => DisconnectReason::DisconnectedByServer,
+            };
+            let packet = Packet::Disconnect(reason);
 
-            // TODO(log): error
             let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key))) {
-                Err(_) => {
+                Err(e) => {
+                    trace_event!(error, "failed to encode disconnect packet", client_id = client_id, error = e);
                     return ServerResult::ClientDisconnected {
                         client_id,
                         addr: client.addr,
+                        reason: disconnect_reason,
                         payload: None,
-                    }
+                    };
                 }
                 Ok(len) => len,
             };
             return ServerResult::ClientDisconnected {
                 client_id,
                 addr: client.addr,
+                reason: disconnect_reason,
                 payload: Some(&mut self.out[..len]),
             };
         }
@@ -617,31 +1132,12 @@ impl NetcodeServer {
     }
 }
 
-fn find_client_mut_by_id(clients: &mut [Option<Connection>], client_id: ClientID) -> Option<&mut Connection> {
-    clients.iter_mut().flatten().find(|c| c.client_id == client_id)
-}
-
-fn find_client_by_id(clients: &[Option<Connection>], client_id: ClientID) -> Option<&Connection> {
-    clients.iter().flatten().find(|c| c.client_id == client_id)
-}
-
-fn find_client_slot_by_id(clients: &[Option<Connection>], client_id: ClientID) -> Option<usize> {
-    clients.iter().enumerate().find_map(|(i, c)| match c {
-        Some(c) if c.client_id == client_id => Some(i),
-        _ => None,
-    })
-}
-
-fn find_client_mut_by_addr(clients: &mut [Option<Connection>], addr: SocketAddr) -> Option<(usize, &mut Connection)> {
-    clients.iter_mut().enumerate().find_map(|(i, c)| match c {
-        Some(c) if c.addr == addr => Some((i, c)),
-        _ => None,
-    })
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::{client::NetcodeClient, token::ConnectToken};
+    use crate::{
+        client::{DisconnectReason, NetcodeClient},
+        token::ConnectToken,
+    };
 
     use super::*;
 
@@ -738,7 +1234,9 @@ mod tests {
         let result = server.disconnect(client_id);
         match result {
             ServerResult::ClientDisconnected {
-                payload: Some(payload), ..
+                reason: DisconnectReason::DisconnectedByServer,
+                payload: Some(payload),
+                ..
             } => {
                 assert!(client.connected());
                 assert!(client.process_packet(payload).is_none());
@@ -755,18 +1253,350 @@ mod tests {
         let mut server = new_server();
 
         let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mac = generate_random_bytes();
         let mut connect_token = ConnectTokenEntry {
-            time: Duration::ZERO,
             address: client_addr,
-            mac: generate_random_bytes(),
+            expire_timestamp: 3,
         };
         // Allow first entry
-        assert!(server.find_or_add_connect_token_entry(connect_token));
+        assert!(server.find_or_add_connect_token_entry(mac, connect_token));
         // Allow same token with the same address
-        assert!(server.find_or_add_connect_token_entry(connect_token));
+        assert!(server.find_or_add_connect_token_entry(mac, connect_token));
         connect_token.address = "127.0.0.1:3001".parse().unwrap();
 
         // Don't allow same token with different address
-        assert!(!server.find_or_add_connect_token_entry(connect_token));
+        assert!(!server.find_or_add_connect_token_entry(mac, connect_token));
+
+        // Expired entries are removed
+        server.update(Duration::from_secs(3));
+        assert!(server.connect_token_entries.is_empty());
+    }
+
+    #[test]
+    fn server_above_1024_clients() {
+        let max_clients = 1100;
+        let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let mut server = NetcodeServer::new(Duration::ZERO, max_clients, TEST_PROTOCOL_ID, server_addr, *TEST_KEY);
+
+        for client_id in 0..max_clients as u64 {
+            let connect_token =
+                ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, client_id, 5, vec![server_addr], None, TEST_KEY).unwrap();
+            let client_addr = SocketAddr::new(server_addr.ip(), 10000 + client_id as u16);
+            let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+                _ => unreachable!(),
+            };
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            assert!(matches!(
+                server.process_packet(client_addr, client_packet),
+                ServerResult::ClientConnected { client_id: id, .. } if id == client_id
+            ));
+        }
+
+        assert_eq!(server.connected_clients(), max_clients);
+        assert_eq!(server.client_addr(1050), Some(SocketAddr::new(server_addr.ip(), 11050)));
+
+        // Slots are reused after disconnections
+        server.disconnect(7);
+        assert!(!server.is_client_connected(7));
+        assert_eq!(server.free_slots, vec![7]);
+    }
+
+    #[test]
+    fn connection_approval() {
+        let mut server = new_server();
+        server.set_require_connection_approval(true);
+        let server_addresses: Vec<SocketAddr> = vec![server.address()];
+
+        // Denied connection
+        let connect_token =
+            ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 1, 5, server_addresses.clone(), None, TEST_KEY).unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::ConnectionRequested { client_id, addr, .. } => {
+                assert_eq!(client_id, 1);
+                assert_eq!(addr, client_addr);
+            }
+            _ => unreachable!(),
+        }
+
+        // Repeated requests are ignored while waiting for the approval, without counting as failures
+        let (client_packet, _) = client.update(NETCODE_SEND_RATE).unwrap();
+        assert_eq!(server.process_packet(client_addr, client_packet), ServerResult::None);
+
+        match server.deny_connection(1) {
+            ServerResult::PacketToSend { addr, payload } => {
+                assert_eq!(addr, client_addr);
+                client.process_packet(payload);
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(client.disconnected(), Some(DisconnectReason::ConnectionDenied));
+
+        // Approved connection
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 2, 5, server_addresses, None, TEST_KEY).unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        assert!(matches!(
+            server.process_packet(client_addr, client_packet),
+            ServerResult::ConnectionRequested { client_id: 2, .. }
+        ));
+        match server.approve_connection(2) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::ClientConnected { client_id, payload, .. } => {
+                assert_eq!(client_id, 2);
+                client.process_packet(payload);
+            }
+            _ => unreachable!(),
+        }
+        assert!(client.connected());
+        assert!(server.is_client_connected(2));
+
+        let stats = server.handshake_stats();
+        assert_eq!(
+            stats,
+            HandshakeStats {
+                requests: 3,
+                challenges_sent: 1,
+                connected: 1,
+                denied: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn banned_connection_requests() {
+        let mut server = new_server();
+        let server_addresses: Vec<SocketAddr> = vec![server.address()];
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let generate_token = |client_id: u64| {
+            ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                server_addresses.clone(),
+                None,
+                TEST_KEY,
+            )
+            .unwrap()
+        };
+        let challenged = |server: &mut NetcodeServer, connect_token: ConnectToken| {
+            let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            matches!(server.process_packet(client_addr, client_packet), ServerResult::PacketToSend { .. })
+        };
+
+        server.ban_client_id(1, Some(Duration::from_secs(10)));
+        assert!(!challenged(&mut server, generate_token(1)));
+        server.update(Duration::from_secs(10));
+        assert!(!server.is_client_id_banned(1));
+        assert!(challenged(&mut server, generate_token(1)));
+
+        let ip_range = IpRange::new("127.0.0.0".parse().unwrap(), 8);
+        server.ban_ip(ip_range, None);
+        assert!(server.is_ip_banned(client_addr.ip()));
+        assert!(!challenged(&mut server, generate_token(2)));
+        server.unban_ip(ip_range);
+        assert!(challenged(&mut server, generate_token(2)));
+
+        let connect_token = generate_token(3);
+        server.revoke_connect_token(connect_token.mac(), connect_token.expire_timestamp());
+        assert!(!challenged(&mut server, connect_token));
+        assert!(challenged(&mut server, generate_token(3)));
+        assert_eq!(server.handshake_stats().banned, 3);
+    }
+
+    #[test]
+    fn answer_ping() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+
+        let len = Packet::ping(TEST_PROTOCOL_ID, 7)
+            .encode(&mut buffer, TEST_PROTOCOL_ID, None)
+            .unwrap();
+        match server.process_packet(client_addr, &mut buffer[..len]) {
+            ServerResult::PacketToSend { addr, payload } => {
+                assert_eq!(addr, client_addr);
+                assert_eq!(payload.len(), len);
+                let (_, packet) = Packet::decode(payload, TEST_PROTOCOL_ID, None, None).unwrap();
+                assert!(matches!(packet, Packet::Pong { sequence: 7, .. }));
+            }
+            _ => unreachable!(),
+        }
+
+        let len = Packet::ping(TEST_PROTOCOL_ID + 1, 7)
+            .encode(&mut buffer, TEST_PROTOCOL_ID, None)
+            .unwrap();
+        assert_eq!(server.process_packet(client_addr, &mut buffer[..len]), ServerResult::None);
+        assert_eq!(server.handshake_stats().requests, 0);
+    }
+
+    #[test]
+    fn probing_server_without_pings() {
+        let mut server = new_server();
+        let server_addresses: Vec<SocketAddr> = vec![server.address()];
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 60, 1, 5, server_addresses, None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new_with_server_probing(Duration::ZERO, connect_token);
+
+        // Servers without pings ignore them, the client connects in order after the probes time out
+        let mut delta = Duration::ZERO;
+        for _ in 0..100 {
+            let mut client_packet = match client.update(delta) {
+                Some((packet, _)) => packet.to_vec(),
+                None => continue,
+            };
+            delta = NETCODE_SEND_RATE;
+            if matches!(
+                Packet::decode(&mut client_packet.clone(), TEST_PROTOCOL_ID, None, None),
+                Ok((_, Packet::Ping { .. }))
+            ) {
+                continue;
+            }
+
+            match server.process_packet(client_addr, &mut client_packet) {
+                ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+                ServerResult::ClientConnected { payload, .. } => client.process_packet(payload),
+                _ => None,
+            };
+            if client.connected() {
+                break;
+            }
+        }
+
+        assert!(client.connected());
+        assert!(client.failed_server_addresses().is_empty());
+        assert!(server.is_client_connected(1));
+    }
+
+    #[test]
+    fn failed_requests_counted_once_per_token() {
+        let mut server = new_server();
+        let server_addresses: Vec<SocketAddr> = vec![server.address()];
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 60, 1, 5, server_addresses, None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        let request = client_packet.to_vec();
+
+        // Clients resend the same request until they get an answer
+        server.ban_client_id(1, None);
+        for _ in 0..3 {
+            server.process_packet(client_addr, &mut request.clone());
+        }
+        assert_eq!(server.handshake_stats().requests, 3);
+        assert_eq!(server.handshake_stats().banned, 1);
+
+        // The token is counted again after some time without failures
+        server.update(FAILED_CONNECT_TOKEN_TIMEOUT);
+        server.process_packet(client_addr, &mut request.clone());
+        assert_eq!(server.handshake_stats().banned, 2);
+    }
+
+    #[test]
+    fn application_reasons() {
+        let mut server = new_server();
+        server.set_require_connection_approval(true);
+        let server_addresses: Vec<SocketAddr> = vec![server.address()];
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+
+        let connect_token =
+            ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 1, 5, server_addresses.clone(), None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        server.process_packet(client_addr, client_packet);
+
+        let reason = ApplicationReason::new(1, "server is in maintenance");
+        match server.deny_connection_with_reason(1, reason.clone()) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert_eq!(client.disconnected(), Some(DisconnectReason::ConnectionDeniedWithReason(reason)));
+
+        server.set_require_connection_approval(false);
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 2, 5, server_addresses, None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::ClientConnected { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert!(client.connected());
+
+        let reason = ApplicationReason::new(2, "kicked");
+        match server.disconnect_with_reason(2, reason.clone()) {
+            ServerResult::ClientDisconnected {
+                payload: Some(payload), ..
+            } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            client.disconnected(),
+            Some(DisconnectReason::DisconnectedByServerWithReason(reason))
+        );
+    }
+
+    #[test]
+    fn private_key_rotation() {
+        let mut server = new_server();
+        let server_addresses: Vec<SocketAddr> = vec![server.address()];
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let new_key = [7u8; NETCODE_KEY_BYTES];
+        let challenged = |server: &mut NetcodeServer, client_id: u64, private_key: &[u8; NETCODE_KEY_BYTES]| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                server_addresses.clone(),
+                None,
+                private_key,
+            )
+            .unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            matches!(server.process_packet(client_addr, client_packet), ServerResult::PacketToSend { .. })
+        };
+
+        assert!(!challenged(&mut server, 1, &new_key));
+
+        server.rotate_private_key(new_key);
+        assert_eq!(server.private_key(), &new_key);
+        assert_eq!(server.previous_private_keys(), &[*TEST_KEY]);
+        assert!(challenged(&mut server, 2, &new_key));
+        assert!(challenged(&mut server, 3, TEST_KEY));
+
+        let newer_key = [9u8; NETCODE_KEY_BYTES];
+        server.rotate_private_key(newer_key);
+        assert_eq!(server.previous_private_keys(), &[new_key]);
+        assert!(!challenged(&mut server, 4, TEST_KEY));
+        assert!(challenged(&mut server, 5, &new_key));
+
+        server.set_previous_private_keys(vec![]);
+        assert!(!challenged(&mut server, 6, &new_key));
+        assert!(challenged(&mut server, 7, &newer_key));
     }
 }
@@ -10,8 +10,8 @@ use crate::{
     crypto::{dencrypted_in_place_xnonce, encrypt_in_place_xnonce, generate_random_bytes},
     serialize::*,
     NetcodeError, NETCODE_ADDITIONAL_DATA_SIZE, NETCODE_ADDRESS_IPV4, NETCODE_ADDRESS_IPV6, NETCODE_ADDRESS_NONE,
-    NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_TIMEOUT_SECONDS,
-    NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
+    NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_EXPIRE_SECONDS, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
+    NETCODE_TIMEOUT_SECONDS, NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
 };
 use chacha20poly1305::aead::Error as CryptoError;
 
@@ -36,6 +36,116 @@ pub struct ConnectToken {
     pub(crate) timeout_seconds: i32,
 }
 
+/// Builder for a [ConnectToken], the token expires after 300 seconds and uses a timeout of 15
+/// seconds by default. At least one server address is required.
+///
+/// # Example
+/// ```
+/// # use renetcode::ConnectToken;
+/// # use std::time::Duration;
+/// let private_key = b"an example very very secret key."; // 32-bytes
+/// let connect_token = ConnectToken::builder()
+///     .protocol_id(7)
+///     .client_id(42)
+///     .expire_seconds(60)
+///     .timeout_seconds(10)
+///     .server_address("127.0.0.1:5000".parse().unwrap())
+///     .generate(Duration::ZERO, private_key)
+///     .unwrap();
+/// assert_eq!(connect_token.timeout_seconds(), 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectTokenBuilder {
+    protocol_id: u64,
+    client_id: u64,
+    expire_seconds: u64,
+    timeout_seconds: i32,
+    server_addresses: Vec<SocketAddr>,
+    user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>,
+}
+
+impl Default for ConnectTokenBuilder {
+    fn default() -> Self {
+        Self {
+            protocol_id: 0,
+            client_id: 0,
+            expire_seconds: NETCODE_EXPIRE_SECONDS,
+            timeout_seconds: NETCODE_TIMEOUT_SECONDS,
+            server_addresses: Vec::new(),
+            user_data: None,
+        }
+    }
+}
+
+impl ConnectTokenBuilder {
+    /// Unique identifier of the game/application, must be the same used in the server.
+    pub fn protocol_id(mut self, protocol_id: u64) -> Self {
+        self.protocol_id = protocol_id;
+        self
+    }
+
+    pub fn client_id(mut self, client_id: u64) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    /// Seconds until the token expires, counted from the time it is generated.
+    pub fn expire_seconds(mut self, expire_seconds: u64) -> Self {
+        self.expire_seconds = expire_seconds;
+        self
+    }
+
+    /// Seconds without receiving packets before the connection times out.
+    /// Negative values disable the timeout, only use them for development.
+    pub fn timeout_seconds(mut self, timeout_seconds: i32) -> Self {
+        self.timeout_seconds = timeout_seconds;
+        self
+    }
+
+    /// Adds a server address, the client tries to connect to them in order.
+    pub fn server_address(mut self, server_address: SocketAddr) -> Self {
+        self.server_addresses.push(server_address);
+        self
+    }
+
+    /// Sets the server addresses, the client tries to connect to them in order.
+    pub fn server_addresses(mut self, server_addresses: Vec<SocketAddr>) -> Self {
+        self.server_addresses = server_addresses;
+        self
+    }
+
+    /// User data available to the server after the client connects.
+    pub fn user_data(mut self, user_data: [u8; NETCODE_USER_DATA_BYTES]) -> Self {
+        self.user_data = Some(user_data);
+        self
+    }
+
+    /// Generates the token, the private key must be the same used in the server.
+    pub fn generate(self, current_time: Duration, private_key: &[u8; NETCODE_KEY_BYTES]) -> Result<ConnectToken, TokenGenerationError> {
+        let expire_timestamp = current_time.as_secs() + self.expire_seconds;
+
+        let private_connect_token =
+            PrivateConnectToken::generate(self.client_id, self.timeout_seconds, self.server_addresses, self.user_data.as_ref())?;
+        let mut private_data = [0u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES];
+        let xnonce = generate_random_bytes();
+        private_connect_token.encode(&mut private_data, self.protocol_id, expire_timestamp, &xnonce, private_key)?;
+
+        Ok(ConnectToken {
+            client_id: self.client_id,
+            version_info: *NETCODE_VERSION_INFO,
+            protocol_id: self.protocol_id,
+            private_data,
+            create_timestamp: current_time.as_secs(),
+            expire_timestamp,
+            xnonce,
+            server_addresses: private_connect_token.server_addresses,
+            client_to_server_key: private_connect_token.client_to_server_key,
+            server_to_client_key: private_connect_token.server_to_client_key,
+            timeout_seconds: self.timeout_seconds,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct PrivateConnectToken {
     pub client_id: u64,       // globally unique identifier for an authenticated client
@@ -96,26 +206,44 @@ impl ConnectToken {
         user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
         private_key: &[u8; NETCODE_KEY_BYTES],
     ) -> Result<Self, TokenGenerationError> {
-        let expire_timestamp = current_time.as_secs() + expire_seconds;
+        let mut builder = Self::builder()
+            .protocol_id(protocol_id)
+            .client_id(client_id)
+            .expire_seconds(expire_seconds)
+            .timeout_seconds(timeout_seconds)
+            .server_addresses(server_addresses);
+        if let Some(user_data) = user_data {
+            builder = builder.user_data(*user_data);
+        }
 
-        let private_connect_token = PrivateConnectToken::generate(client_id, timeout_seconds, server_addresses, user_data)?;
-        let mut private_data = [0u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES];
-        let xnonce = generate_random_bytes();
-        private_connect_token.encode(&mut private_data, protocol_id, expire_timestamp, &xnonce, private_key)?;
+        builder.generate(current_time, private_key)
+    }
 
-        Ok(Self {
-            client_id,
-            version_info: *NETCODE_VERSION_INFO,
-            protocol_id,
-            private_data,
-            create_timestamp: current_time.as_secs(),
-            expire_timestamp,
-            xnonce,
-            server_addresses: private_connect_token.server_addresses,
-            client_to_server_key: private_connect_token.client_to_server_key,
-            server_to_client_key: private_connect_token.server_to_client_key,
-            timeout_seconds: NETCODE_TIMEOUT_SECONDS,
-        })
+    /// Returns a builder to generate a token, see [ConnectTokenBuilder].
+    pub fn builder() -> ConnectTokenBuilder {
+        ConnectTokenBuilder::default()
+    }
+
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// Seconds without receiving packets before the connection times out, negative values disable the timeout.
+    pub fn timeout_seconds(&self) -> i32 {
+        self.timeout_seconds
+    }
+
+    /// Timestamp in seconds when the token expires, in the same time base used to generate it.
+    pub fn expire_timestamp(&self) -> u64 {
+        self.expire_timestamp
+    }
+
+    /// Returns the MAC of the encrypted private data, it uniquely identifies the token in the server.
+    /// Can be used to revoke the token with [NetcodeServer::revoke_connect_token][crate::NetcodeServer::revoke_connect_token].
+    pub fn mac(&self) -> [u8; NETCODE_MAC_BYTES] {
+        let mut mac = [0u8; NETCODE_MAC_BYTES];
+        mac.copy_from_slice(&self.private_data[NETCODE_CONNECT_TOKEN_PRIVATE_BYTES - NETCODE_MAC_BYTES..]);
+        mac
     }
 
     pub fn write(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
@@ -166,6 +294,72 @@ impl ConnectToken {
             timeout_seconds,
         })
     }
+
+    /// Encodes the token as an URL-safe base64 string without padding, so it can be embedded in
+    /// JSON, URLs or launch arguments.
+    pub fn to_base64(&self) -> String {
+        let mut buffer = Vec::new();
+        self.write(&mut buffer).expect("writing to a Vec should not fail");
+        base64::encode_config(buffer, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Decodes a token encoded with [ConnectToken::to_base64].
+    pub fn from_base64(encoded: &str) -> Result<Self, NetcodeError> {
+        let buffer = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+            .map_err(|e| NetcodeError::IoError(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        Self::read(&mut buffer.as_slice())
+    }
+}
+
+// Human readable formats (JSON, TOML...) use the base64 string, other formats use the binary form.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ConnectToken {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_base64())
+        } else {
+            let mut buffer = Vec::new();
+            self.write(&mut buffer).map_err(serde::ser::Error::custom)?;
+            serializer.serialize_bytes(&buffer)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ConnectToken {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ConnectTokenVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ConnectTokenVisitor {
+            type Value = ConnectToken;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a connect token as a base64 string or bytes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                ConnectToken::from_base64(value).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+                ConnectToken::read(&mut &value[..]).map_err(E::custom)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut buffer = Vec::new();
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    buffer.push(byte);
+                }
+                self.visit_bytes(&buffer)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(ConnectTokenVisitor)
+        } else {
+            deserializer.deserialize_bytes(ConnectTokenVisitor)
+        }
+    }
 }
 
 impl PrivateConnectToken {
@@ -409,6 +603,7 @@ mod tests {
             private_key,
         )
         .unwrap();
+        assert_eq!(timeout_seconds, result.timeout_seconds);
         assert_eq!(timeout_seconds, private.timeout_seconds);
         assert_eq!(client_id, private.client_id);
         assert_eq!(user_data, private.user_data);
@@ -416,4 +611,44 @@ mod tests {
         assert_eq!(token.client_to_server_key, private.client_to_server_key);
         assert_eq!(token.server_to_client_key, private.server_to_client_key);
     }
+
+    fn test_connect_token() -> ConnectToken {
+        let private_key = b"an example very very secret key."; // 32-bytes
+        ConnectToken::builder()
+            .protocol_id(2)
+            .client_id(4)
+            .server_address("127.0.0.1:8080".parse().unwrap())
+            .server_address("[::1]:3000".parse().unwrap())
+            .user_data(generate_random_bytes())
+            .generate(Duration::ZERO, private_key)
+            .unwrap()
+    }
+
+    #[test]
+    fn connect_token_base64() {
+        let token = test_connect_token();
+        let encoded = token.to_base64();
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        let result = ConnectToken::from_base64(&encoded).unwrap();
+        assert_eq!(token, result);
+
+        assert!(ConnectToken::from_base64("not a token!").is_err());
+        assert!(ConnectToken::from_base64(&encoded[..100]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn connect_token_serde() {
+        let token = test_connect_token();
+
+        let json = serde_json::to_string(&token).unwrap();
+        assert_eq!(json, format!("\"{}\"", token.to_base64()));
+        let result: ConnectToken = serde_json::from_str(&json).unwrap();
+        assert_eq!(token, result);
+
+        let bytes = bincode::serialize(&token).unwrap();
+        let result: ConnectToken = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(token, result);
+    }
 }
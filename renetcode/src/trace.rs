@@ -0,0 +1,27 @@
+// Events and spans are sent to `tracing` when the `tracing` feature is enabled, otherwise they are
+// compiled out. The fields are still borrowed without the feature, so they don't become unused.
+macro_rules! trace_event {
+    ($level:ident, $message:literal $(, $field:ident = $value:expr)* $(,)?) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($field = ?$value,)* $message);
+        #[cfg(not(feature = "tracing"))]
+        {
+            $(let _ = &$value;)*
+        }
+    }};
+}
+
+// Enters a span until the end of the current scope.
+macro_rules! trace_span {
+    ($level:ident, $name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(trace_span!(@level $level), $name, $($field = ?$value),*).entered();
+        #[cfg(not(feature = "tracing"))]
+        {
+            $(let _ = &$value;)*
+        }
+    };
+    (@level trace) => { tracing::Level::TRACE };
+    (@level debug) => { tracing::Level::DEBUG };
+    (@level info) => { tracing::Level::INFO };
+}